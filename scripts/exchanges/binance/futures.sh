@@ -0,0 +1,51 @@
+ro-soku() {
+    ./target/release/ro-soku_x86_64_linux "$@"
+}
+
+results=()
+
+execute_command() {
+    ro-soku "$@"
+    results+=($?)
+    sleep 3
+}
+
+execute_command \
+    --exchange binance \
+    --type futures \
+    --expiry 2023-12-29 \
+    --symbol BTC/USDT \
+    --interval 1hour \
+    --term-start 2023-09-25T00:00:00Z \
+    --term-end 2023-10-05T00:00:00Z \
+    --pick c \
+    --order asc \
+    --format csv
+
+execute_command \
+    --exchange binance \
+    --type futures \
+    --expiry 2023-12-29 \
+    --margin inverse \
+    --symbol BTC/USD \
+    --interval 1hour \
+    --term-start 2023-11-01T00:00:00Z \
+    --term-end 2023-11-02T00:00:00Z \
+    --pick c \
+    --order asc \
+    --format csv
+
+all_successful=true
+for result in "${results[@]}"; do
+    if [ "$result" -ne 0 ]; then
+        all_successful=false
+        break
+    fi
+done
+
+if $all_successful; then
+    echo "✅ Succeeded!"
+else
+    echo "❌ Failed."
+    exit 1
+fi
@@ -0,0 +1,48 @@
+ro-soku() {
+    ./target/release/ro-soku_x86_64_linux "$@"
+}
+
+results=()
+
+execute_command() {
+    ro-soku "$@"
+    results+=($?)
+    sleep 3
+}
+
+execute_command \
+    --exchange bitflyer \
+    --type perpetual \
+    --symbol BTC/JPY \
+    --interval 1min \
+    --past \
+    --range 1hour \
+    --pick c \
+    --order asc \
+    --format csv
+
+execute_command \
+    --exchange bitflyer \
+    --type perpetual \
+    --symbol BTC/JPY \
+    --interval 15min \
+    --past \
+    --range 1hour \
+    --pick c \
+    --order asc \
+    --format csv
+
+all_successful=true
+for result in "${results[@]}"; do
+    if [ "$result" -ne 0 ]; then
+        all_successful=false
+        break
+    fi
+done
+
+if $all_successful; then
+    echo "✅ Succeeded!"
+else
+    echo "❌ Failed."
+    exit 1
+fi
@@ -0,0 +1,48 @@
+ro-soku() {
+    ./target/release/ro-soku_x86_64_linux "$@"
+}
+
+results=()
+
+execute_command() {
+    ro-soku "$@"
+    results+=($?)
+    sleep 3
+}
+
+execute_command \
+    --exchange bitget \
+    --type perpetual \
+    --symbol BTC/USDT \
+    --interval 1min \
+    --term-start 2023-01-11T23:00:00Z \
+    --term-end 2023-01-12T00:00:00Z \
+    --pick c \
+    --order asc \
+    --format csv
+
+execute_command \
+    --exchange bitget \
+    --type perpetual \
+    --symbol BTC/USDT \
+    --interval 15min \
+    --term-start 2023-01-11T23:00:00Z \
+    --term-end 2023-01-12T00:00:00Z \
+    --pick c \
+    --order asc \
+    --format csv
+
+all_successful=true
+for result in "${results[@]}"; do
+    if [ "$result" -ne 0 ]; then
+        all_successful=false
+        break
+    fi
+done
+
+if $all_successful; then
+    echo "✅ Succeeded!"
+else
+    echo "❌ Failed."
+    exit 1
+fi
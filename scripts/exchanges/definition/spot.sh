@@ -0,0 +1,51 @@
+ro-soku() {
+    ./target/release/ro-soku_x86_64_linux "$@"
+}
+
+results=()
+
+execute_command() {
+    ro-soku "$@"
+    results+=($?)
+    sleep 3
+}
+
+execute_command \
+    --definition ./definitions/binance-spot.json \
+    --type spot \
+    --symbol BTC/USDT \
+    --interval 1min \
+    --term-start 2023-01-11T23:00:00Z \
+    --term-end 2023-01-12T00:00:00Z \
+    --pick c \
+    --order asc \
+    --format csv
+
+if [ -z "$CI" ]; then
+    # Not run from within the CI environment, same as the built-in Bybit adapter
+    execute_command \
+        --definition ./definitions/bybit-spot.json \
+        --type spot \
+        --symbol BTC/USDT \
+        --interval 1min \
+        --term-start 2023-01-11T23:00:00Z \
+        --term-end 2023-01-12T00:00:00Z \
+        --pick c \
+        --order asc \
+        --format csv
+fi
+
+all_successful=true
+for result in "${results[@]}"; do
+    if [ "$result" -ne 0 ]; then
+        all_successful=false
+        break
+    fi
+done
+
+if $all_successful; then
+    echo "✅ Succeeded!"
+else
+    echo "❌ Failed."
+    exit 1
+fi
@@ -13,20 +13,56 @@ execute_tests "Binance spot" bash ./scripts/exchanges/binance/spot.sh
 if [ -z "$CI" ]; then
   # Not run from within the CI environment, as it will always fail, probably due to IP address issues
   execute_tests "Binance perpetual" bash ./scripts/exchanges/binance/perpetual.sh
+  execute_tests "Binance inverse perpetual" bash ./scripts/exchanges/binance/inverse-perpetual.sh
+  execute_tests "Binance futures" bash ./scripts/exchanges/binance/futures.sh
 fi
 
 execute_tests "bitbank spot" bash ./scripts/exchanges/bitbank/spot.sh
 
+execute_tests "bitFlyer spot" bash ./scripts/exchanges/bitflyer/spot.sh
+execute_tests "bitFlyer perpetual" bash ./scripts/exchanges/bitflyer/perpetual.sh
+
+execute_tests "Bitget spot" bash ./scripts/exchanges/bitget/spot.sh
+execute_tests "Bitget perpetual" bash ./scripts/exchanges/bitget/perpetual.sh
+
+execute_tests "Bithumb spot" bash ./scripts/exchanges/bithumb/spot.sh
+
 execute_tests "bitmex perpetual" bash ./scripts/exchanges/bitmex/perpetual.sh
 
 if [ -z "$CI" ]; then
     # Same as above (2023/7 ~)
     execute_tests "Bybit spot" bash ./scripts/exchanges/bybit/spot.sh
     execute_tests "Bybit perpetual" bash ./scripts/exchanges/bybit/perpetual.sh
+    execute_tests "Bybit inverse perpetual" bash ./scripts/exchanges/bybit/inverse-perpetual.sh
 fi
 
+execute_tests "Coinbase spot" bash ./scripts/exchanges/coinbase/spot.sh
+
+execute_tests "Definition spot" bash ./scripts/exchanges/definition/spot.sh
+
+execute_tests "Deribit perpetual" bash ./scripts/exchanges/deribit/perpetual.sh
+
+execute_tests "Gate.io spot" bash ./scripts/exchanges/gate/spot.sh
+execute_tests "Gate.io perpetual" bash ./scripts/exchanges/gate/perpetual.sh
+
+execute_tests "GMO Coin spot" bash ./scripts/exchanges/gmo/spot.sh
+execute_tests "GMO Coin perpetual" bash ./scripts/exchanges/gmo/perpetual.sh
+
+execute_tests "Hyperliquid perpetual" bash ./scripts/exchanges/hyperliquid/perpetual.sh
+
+execute_tests "Kraken spot" bash ./scripts/exchanges/kraken/spot.sh
+
+execute_tests "KuCoin spot" bash ./scripts/exchanges/kucoin/spot.sh
+execute_tests "KuCoin perpetual" bash ./scripts/exchanges/kucoin/perpetual.sh
+
 execute_tests "OKX spot" bash ./scripts/exchanges/okx/spot.sh
 execute_tests "OKX perpetual" bash ./scripts/exchanges/okx/perpetual.sh
+execute_tests "OKX price types" bash ./scripts/exchanges/okx/price-types.sh
+execute_tests "OKX funding" bash ./scripts/exchanges/okx/funding.sh
+execute_tests "OKX open interest" bash ./scripts/exchanges/okx/open-interest.sh
+execute_tests "OKX symbols" bash ./scripts/exchanges/okx/symbols.sh
+
+execute_tests "Upbit spot" bash ./scripts/exchanges/upbit/spot.sh
 
 all_successful=true
 for result in "${results[@]}"; do
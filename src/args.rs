@@ -1,23 +1,17 @@
 use std::{fmt::Debug, str::FromStr};
 
-use anyhow::{anyhow, ensure, Error};
-use chrono::{DateTime, Utc};
+use anyhow::{anyhow, bail, ensure, Error};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
 use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 use regex::Regex;
 
 use crate::{
-    exchange::{
-        binance::*,
-        bitbank::*,
-        bitmex::*,
-        bybit::*,
-        // kraken::*,
-        okx::*,
-        *,
-    },
+    clock::*,
+    exchange::{definition::*, *},
     format::*,
     order::*,
     pick::*,
+    timestamp::*,
     unit::*,
 };
 
@@ -31,6 +25,10 @@ pub struct Cli {
     #[arg(short = 'x', long, value_enum, default_value = "binance")]
     pub exchange: ExchangeChoices,
 
+    /// Path to a JSON file which defines an exchange not built in (overrides `--exchange`, see `definitions/` for examples)
+    #[arg(long)]
+    pub definition: Option<String>,
+
     /// Symbol pair with slashes (if you enter the format like BTC/USDT, ro-soku will automatically convert it for the respective exchanges)
     #[arg(short = 's', long, default_value = "BTC/USDT")]
     pub symbol: String,
@@ -39,6 +37,34 @@ pub struct Cli {
     #[arg(short = 't', long = "type", default_value = "spot")]
     pub type_: MarketType,
 
+    /// Expiry date of the contract like `2023-12-29` (required with `--type futures` and `--type option`)
+    #[arg(long)]
+    pub expiry: Option<String>,
+
+    /// Strike price of the option (required with `--type option`)
+    #[arg(long)]
+    pub strike: Option<String>,
+
+    /// Call or put of the option (required with `--type option`)
+    #[arg(long, value_enum)]
+    pub option_right: Option<OptionRight>,
+
+    /// Linear (margined in the quote currency) or inverse (margined in the base currency) contracts, for exchanges which have both (perpetual and futures only)
+    #[arg(long, value_enum)]
+    pub margin: Option<Margin>,
+
+    /// Kind of data: candlesticks, or the funding rate history and the open interest history of perpetuals
+    #[arg(long, value_enum, default_value = "kline")]
+    pub kind: DataKind,
+
+    /// Which price the candles are of: the last traded price, or the mark price, the index price and the premium index of derivatives (available on some exchanges only)
+    #[arg(long, value_enum, default_value = "last")]
+    pub price_type: PriceType,
+
+    /// Convert `v` into the base currency, the quote currency or contracts, with the contract size fetched from the exchange (available on some exchanges only) [default: the unit of the exchange]
+    #[arg(long, value_enum)]
+    pub volume_unit: Option<VolumeUnit>,
+
     /// Specify if you want the latest data for the past range (cannot be used with `--term-start`, `--term-end`)
     #[arg(long, action = ArgAction::SetTrue)]
     pub past: Option<bool>,
@@ -55,20 +81,47 @@ pub struct Cli {
     #[arg(long)]
     pub term_end: Option<String>,
 
+    /// Number of the most recent closed candles to retrieve (can be used alone or with `--term-end`, cannot be used with `--past`, `--range` and `--term-start`)
+    #[arg(long)]
+    pub count: Option<u32>,
+
     /// Unit and duration of the candlestick
     #[arg(short = 'i', long, default_value = "15min")]
     // This may also be received by `value_delimiter` to implement `FromVec`
     pub interval: String,
 
-    /// Select data which you want from t(imestamp as unixtime)/o/h/l/c/v, in any order you like and allow multiple specifications (except for output type: json)
-    #[arg(
-        short = 'p',
-        long,
-        value_delimiter = ',',
-        default_value = "t,o,h,l,c,v"
-    )]
+    /// Select data which you want from t(imestamp as unixtime)/o/h/l/c/v/is_closed/qv/trades/tbv/tbqv/vwap (or t/rate/mark with `--kind funding`, t/oi/oi_value with `--kind open-interest`), in any order you like and allow multiple specifications (except for output type: json) [default: t,o,h,l,c,v (or t,rate,mark, t,oi,oi_value)]
+    #[arg(short = 'p', long, value_delimiter = ',')]
     pub pick: Vec<Pick>,
 
+    /// How to print timestamps: `ms` (unixtime), `sec` (unixtime), `rfc3339` or any strftime pattern like `%Y-%m-%d %H:%M:%S`
+    #[arg(long, default_value = "ms")]
+    pub timestamp_format: String,
+
+    /// Timezone for `rfc3339` and strftime timestamps: `utc`, `local` or a name like `Asia/Tokyo`
+    #[arg(long, default_value = "utc")]
+    pub timezone: String,
+
+    /// Which end of the candle the timestamp shows, its opening time or its closing time (the opening time of the next candle)
+    #[arg(long, value_enum, default_value = "open")]
+    pub timestamp_at: TimestampAt,
+
+    /// How to treat the latest candle which is still forming (`mark` adds the `is_closed` column to the output)
+    #[arg(long, value_enum, default_value = "include")]
+    pub unclosed: Unclosed,
+
+    /// Skip checking the symbol pair against the instruments listed by the exchange (cached for a day) before retrieving
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub no_symbol_check: Option<bool>,
+
+    /// Use the clock of this machine as is, instead of correcting it with the server time of the exchange
+    #[arg(long, action = ArgAction::SetTrue)]
+    pub local_clock: Option<bool>,
+
+    /// Pretend the current time is this, to reproduce a past `--past` or `--count` query exactly (unixtime (millisecond) or RFC3339 timestamp)
+    #[arg(long, conflicts_with = "local_clock")]
+    pub now: Option<String>,
+
     /// Order by (sorted by only datetime)
     #[arg(short = 'o', long, value_enum, default_value = "asc")]
     pub order: Order,
@@ -79,6 +132,19 @@ pub struct Cli {
 }
 
 impl Cli {
+    /// The clock given by `--now` or `--local-clock`, `None` if it should follow the exchange.
+    pub fn pinned_clock(&self) -> Result<Option<Clock>, Error> {
+        match &self.now {
+            Some(now) => Ok(Some(Clock::Fixed(
+                Utc.timestamp_millis_opt(ParsedArgs::parse_terms(now.clone())?.unwrap())
+                    .single()
+                    .ok_or(anyhow!("The `--now` time is out of range."))?,
+            ))),
+            None if self.local_clock.unwrap_or(false) => Ok(Some(Clock::local())),
+            None => Ok(None),
+        }
+    }
+
     pub fn valdate(&self) -> Result<(), Error> {
         let mut errors: Vec<String> = Vec::new();
 
@@ -94,6 +160,14 @@ impl Cli {
             errors.push(format!("  - {e}"));
         }
 
+        if let Err(e) = self.check_contract_options() {
+            errors.push(format!("  - {e}"));
+        }
+
+        if let Err(e) = self.check_kind_options() {
+            errors.push(format!("  - {e}"));
+        }
+
         if !errors.is_empty() {
             errors.push(format!(
                 "Failed to parse arguments due to {} error(s)",
@@ -115,8 +189,12 @@ impl Cli {
                 || (!self.past.unwrap()
                     && self.range.is_none()
                     && self.term_start.is_some()
-                    && self.term_end.is_some()),
-            "You must use set of `--past` and `--range`, or set of `--term-start` and `--term-end`."
+                    && self.term_end.is_some())
+                || (!self.past.unwrap()
+                    && self.range.is_none()
+                    && self.term_start.is_none()
+                    && self.count.is_some()),
+            "You must use set of `--past` and `--range`, set of `--term-start` and `--term-end`, or `--count` (optionally with `--term-end`)."
         );
 
         Ok(())
@@ -151,6 +229,17 @@ impl Cli {
             );
         }
 
+        if let Some(count) = self.count {
+            ensure!(
+                0 < count,
+                "The argument `--count` must be greater than or equal to 1."
+            );
+            ensure!(
+                !self.past.unwrap() && self.range.is_none() && self.term_start.is_none(),
+                "The argument `--count` cannot be used with `--past`, `--range` and `--term-start`."
+            );
+        }
+
         Ok(())
     }
 
@@ -164,12 +253,110 @@ impl Cli {
         );
         Ok(())
     }
+
+    fn check_contract_options(&self) -> Result<(), Error> {
+        match self.type_ {
+            MarketType::Spot | MarketType::Perpetual => ensure!(
+                self.expiry.is_none() && self.strike.is_none() && self.option_right.is_none(),
+                "The arguments `--expiry`, `--strike` and `--option-right` can only be used with `--type futures` or `--type option`."
+            ),
+            MarketType::Futures => ensure!(
+                self.expiry.is_some() && self.strike.is_none() && self.option_right.is_none(),
+                "You must use `--expiry` (and cannot use `--strike` and `--option-right`) with `--type futures`."
+            ),
+            MarketType::Option => ensure!(
+                self.expiry.is_some() && self.strike.is_some() && self.option_right.is_some(),
+                "You must use `--expiry`, `--strike` and `--option-right` with `--type option`."
+            ),
+        }
+
+        if self.margin.is_some() {
+            ensure!(
+                matches!(self.type_, MarketType::Perpetual | MarketType::Futures),
+                "The argument `--margin` can only be used with `--type perpetual` or `--type futures`."
+            );
+        }
+
+        if let Some(strike) = &self.strike {
+            ensure!(
+                Regex::new(r"^\d+(\.\d+)?$").unwrap().is_match(strike),
+                "The argument `--strike` must be a positive number."
+            );
+        }
+
+        Ok(())
+    }
+
+    fn check_kind_options(&self) -> Result<(), Error> {
+        if let Some(pick) = self
+            .pick
+            .iter()
+            .find(|pick| !self.kind.picks().contains(pick))
+        {
+            bail!(
+                "The pick `{}` cannot be used with `--kind {}`.",
+                pick.to_possible_value().unwrap().get_name(),
+                self.kind.to_possible_value().unwrap().get_name()
+            );
+        }
+
+        ensure!(
+            !(matches!(self.type_, MarketType::Spot)
+                && matches!(self.volume_unit, Some(VolumeUnit::Contracts))),
+            "The argument `--volume-unit contracts` cannot be used with `--type spot`."
+        );
+
+        if let DataKind::Funding | DataKind::OpenInterest = self.kind {
+            let kind = self.kind.to_possible_value().unwrap();
+            ensure!(
+                matches!(self.type_, MarketType::Perpetual),
+                "The argument `--kind {}` can only be used with `--type perpetual`.",
+                kind.get_name()
+            );
+            ensure!(
+                self.count.is_none()
+                    && matches!(self.price_type, PriceType::Last)
+                    && self.volume_unit.is_none()
+                    && matches!(self.timestamp_at, TimestampAt::Open),
+                "The arguments `--count`, `--price-type`, `--volume-unit` and `--timestamp-at` cannot be used with `--kind {}`.",
+                kind.get_name()
+            );
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
     /// Start interactive mode to build a command with all options (there is no valid option)
     Guide {},
+    /// List symbol pairs of the exchange and the market type, in the notation of `--symbol`
+    Symbols {
+        /// Name of the exchange
+        #[arg(short = 'x', long, value_enum, default_value = "binance")]
+        exchange: ExchangeChoices,
+
+        /// Market type (option is not supported)
+        #[arg(short = 't', long = "type", default_value = "spot")]
+        type_: MarketType,
+
+        /// Linear or inverse contracts, for exchanges which have both (perpetual and futures only) [default: the same as retrieving]
+        #[arg(long, value_enum)]
+        margin: Option<Margin>,
+
+        /// Only the pairs of this base currency like `BTC`
+        #[arg(long)]
+        base: Option<String>,
+
+        /// Only the pairs of this quote currency like `USDT`
+        #[arg(long)]
+        quote: Option<String>,
+
+        /// Output format
+        #[arg(short = 'f', long, value_enum, default_value = "raw")]
+        format: FormatType,
+    },
 }
 
 #[derive(
@@ -178,6 +365,151 @@ pub enum Commands {
 pub enum MarketType {
     Spot,
     Perpetual,
+    /// Futures with an expiry date (`--expiry` is required)
+    Futures,
+    /// Options (`--expiry`, `--strike` and `--option-right` are required)
+    Option,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    ValueEnum,
+    strum::Display,
+    strum::IntoStaticStr,
+    strum::EnumIter,
+    strum::AsRefStr,
+)]
+pub enum OptionRight {
+    Call,
+    Put,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    ValueEnum,
+    strum::Display,
+    strum::IntoStaticStr,
+    strum::EnumIter,
+    strum::AsRefStr,
+)]
+pub enum Margin {
+    Linear,
+    Inverse,
+}
+
+/// Which contract of the derivative market types to retrieve.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Contract {
+    pub expiry: Option<NaiveDate>,
+    pub strike: Option<String>,
+    pub right: Option<OptionRight>,
+    /// `None` is the default of each exchange (linear for most of them)
+    pub margin: Option<Margin>,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    ValueEnum,
+    strum::Display,
+    strum::IntoStaticStr,
+    strum::EnumIter,
+    strum::AsRefStr,
+)]
+pub enum DataKind {
+    Kline,
+    Funding,
+    /// Open interest at each `--interval`
+    OpenInterest,
+}
+
+impl DataKind {
+    /// Picks available for the kind.
+    pub fn picks(&self) -> &'static [Pick] {
+        match self {
+            DataKind::Kline => &[
+                Pick::T,
+                Pick::O,
+                Pick::H,
+                Pick::L,
+                Pick::C,
+                Pick::V,
+                Pick::IsClosed,
+                Pick::Qv,
+                Pick::Trades,
+                Pick::Tbv,
+                Pick::Tbqv,
+                Pick::Vwap,
+            ],
+            DataKind::Funding => &[Pick::T, Pick::Rate, Pick::Mark],
+            DataKind::OpenInterest => &[Pick::T, Pick::Oi, Pick::OiValue],
+        }
+    }
+
+    /// OHLCV for klines, and all the picks for the others.
+    pub fn default_picks(&self) -> Vec<Pick> {
+        match self {
+            DataKind::Kline => self.picks()[..6].to_vec(),
+            _ => self.picks().to_vec(),
+        }
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    ValueEnum,
+    strum::Display,
+    strum::IntoStaticStr,
+    strum::EnumIter,
+    strum::AsRefStr,
+)]
+pub enum PriceType {
+    Last,
+    Mark,
+    Index,
+    Premium,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    ValueEnum,
+    strum::Display,
+    strum::IntoStaticStr,
+    strum::EnumIter,
+    strum::AsRefStr,
+)]
+pub enum VolumeUnit {
+    Base,
+    Quote,
+    /// Derivatives only
+    Contracts,
+}
+
+#[derive(
+    Debug, Clone, ValueEnum, strum::Display, strum::IntoStaticStr, strum::EnumIter, strum::AsRefStr,
+)]
+pub enum TimestampAt {
+    Open,
+    /// Klines only
+    Close,
+}
+
+#[derive(
+    Debug, Clone, ValueEnum, strum::Display, strum::IntoStaticStr, strum::EnumIter, strum::AsRefStr,
+)]
+pub enum Unclosed {
+    Include,
+    Exclude,
+    Mark,
 }
 
 #[derive(Debug, Clone)]
@@ -185,22 +517,64 @@ pub struct ParsedArgs {
     pub exchange: Exchange,
     pub symbol: String,
     pub type_: MarketType,
+    pub contract: Contract,
+    pub kind: DataKind,
+    pub price_type: PriceType,
+    pub volume_unit: Option<VolumeUnit>,
     pub past: bool,
     pub range: Option<DurationAndUnit>,
     pub term_start: Option<i64>,
     pub term_end: Option<i64>,
+    pub count: Option<u32>,
     pub interval: DurationAndUnit,
     pub pick: Vec<Pick>,
+    pub timestamp: TimestampStyle,
+    pub timestamp_at: TimestampAt,
+    pub unclosed: Unclosed,
+    pub symbol_check: bool,
+    pub clock: Clock,
     pub order: Order,
     pub output: FormatType,
 }
 
 impl ParsedArgs {
     pub fn new(value: Cli, exchange: Exchange) -> Result<Self, anyhow::Error> {
+        let clock = match value.pinned_clock()? {
+            Some(clock) => clock,
+            None => Clock::System {
+                offset: exchange.clock_offset(&value.type_),
+            },
+        };
+
+        let mut pick = match value.pick.is_empty() {
+            true => value.kind.default_picks(),
+            false => value.pick,
+        };
+        if let (Unclosed::Mark, DataKind::Kline) = (&value.unclosed, &value.kind) {
+            if !pick.contains(&Pick::IsClosed) {
+                pick.push(Pick::IsClosed);
+            }
+        }
+
         let parsed_args = ParsedArgs {
             exchange,
             symbol: value.symbol,
             type_: value.type_,
+            contract: Contract {
+                expiry: match value.expiry {
+                    Some(expiry) => Some(
+                        NaiveDate::parse_from_str(&expiry, "%Y-%m-%d")
+                            .map_err(|e| anyhow!("Invalid expiry date format: {}", e))?,
+                    ),
+                    _ => None,
+                },
+                strike: value.strike,
+                right: value.option_right,
+                margin: value.margin,
+            },
+            kind: value.kind,
+            price_type: value.price_type,
+            volume_unit: value.volume_unit,
             past: value.past.unwrap_or(false),
             range: match value.range {
                 Some(range) => Some(range.parse::<DurationAndUnit>()?),
@@ -214,8 +588,17 @@ impl ParsedArgs {
                 Some(term_end) => Self::parse_terms(term_end)?,
                 _ => None,
             },
+            count: value.count,
             interval: value.interval.parse::<DurationAndUnit>()?,
-            pick: value.pick,
+            pick,
+            timestamp: TimestampStyle {
+                format: value.timestamp_format.parse::<TimestampFormat>()?,
+                timezone: value.timezone.parse::<Timezone>()?,
+            },
+            timestamp_at: value.timestamp_at,
+            unclosed: value.unclosed,
+            symbol_check: !value.no_symbol_check.unwrap_or(false),
+            clock,
             order: value.order,
             output: value.format,
         };
@@ -228,6 +611,11 @@ impl ParsedArgs {
         Ok(parsed_args)
     }
 
+    /// Current time of the exchange (or of this machine with `--local-clock`, or pinned with `--now`).
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
     /// Parse of `term_start` and `term_end` when unixtime is entered
     /// directly and when RFC3339 format timestamps are entered.
     fn parse_terms(term: String) -> Result<Option<i64>, Error> {
@@ -244,23 +632,30 @@ impl ParsedArgs {
     }
 
     /// Create a new `ParsedArgs` structure with the corresponding `term_start` and `term_end`
-    /// fields for the `--past` and non-past` cases, respectively.
+    /// fields for the `--past`, `--count` and non-past` cases, respectively.
     /// After this method is executed, `past` and `range` are no longer needed at all.
+    /// With `--count`, `term_start` is only the first guess (the actual span depends on the gaps of the exchange).
     fn fit_to_term_args(self) -> Self {
         let start_time;
         let mut end_time;
 
         if self.past {
-            let now = Utc::now();
+            let now = self.now();
             start_time = (now - self.range.clone().unwrap().past_duration()).timestamp() * 1000;
             end_time = now.timestamp() * 1000;
+        } else if let Some(count) = self.count {
+            end_time = self
+                .term_end
+                .unwrap_or_else(|| self.now().timestamp_millis())
+                .min(self.now().timestamp_millis());
+            start_time = self.interval.shift(end_time, -(count as i64));
         } else {
             start_time = self.term_start.unwrap();
             end_time = self.term_end.unwrap();
         }
 
         // If `end_time` is far in the future, fix the current time as the maximum value
-        let now = Utc::now().timestamp_millis();
+        let now = self.now().timestamp_millis();
         if now <= end_time {
             end_time = now;
         }
@@ -291,13 +686,43 @@ impl TryFrom<Cli> for ParsedArgs {
     type Error = anyhow::Error;
 
     fn try_from(value: Cli) -> Result<Self, Self::Error> {
-        match value.exchange {
-            ExchangeChoices::Binance => Self::new(value, Exchange::Binance(Binance::new())),
-            ExchangeChoices::Bitbank => Self::new(value, Exchange::Bitbank(Bitbank::new())),
-            ExchangeChoices::Bitmex => Self::new(value, Exchange::Bitmex(Bitmex::new())),
-            ExchangeChoices::Bybit => Self::new(value, Exchange::Bybit(Bybit::new())),
-            ExchangeChoices::Okx => Self::new(value, Exchange::Okx(Okx::new())),
-            // ExchangeChoices::Kraken => Self::new(value, Exchange::Kraken(Kraken::new())),
+        if let Some(path) = &value.definition {
+            let definition = Definition::from_file(path)?;
+            return Self::new(value, Exchange::Custom(Custom::new(definition)));
+        }
+
+        let exchange = Exchange::from(&value.exchange);
+        Self::new(value, exchange)
+    }
+}
+
+/// Base of `ParsedArgs` in tests, which override only the fields under test with `..Default::default()`.
+/// Mostly the defaults of `Cli`, but nothing is picked and the clock is the local one.
+#[cfg(test)]
+impl Default for ParsedArgs {
+    fn default() -> Self {
+        ParsedArgs {
+            exchange: Exchange::Binance(binance::Binance::new()),
+            symbol: "BTC/USDT".to_string(),
+            type_: MarketType::Spot,
+            contract: Contract::default(),
+            kind: DataKind::Kline,
+            price_type: PriceType::Last,
+            volume_unit: None,
+            past: false,
+            range: None,
+            term_start: None,
+            term_end: None,
+            count: None,
+            interval: DurationAndUnit(15, TermUnit::Min),
+            pick: vec![],
+            timestamp: TimestampStyle::new(),
+            timestamp_at: TimestampAt::Open,
+            unclosed: Unclosed::Include,
+            symbol_check: true,
+            clock: Clock::local(),
+            order: Order::Asc,
+            output: FormatType::Raw,
         }
     }
 }
@@ -309,6 +734,48 @@ mod tests {
 
     use super::*;
 
+    #[rstest]
+    #[case(&["--type", "perpetual"], true)]
+    #[case(&["--type", "perpetual", "--expiry", "2023-12-29"], false)]
+    #[case(&["--type", "futures", "--expiry", "2023-12-29"], true)]
+    #[case(&["--type", "futures"], false)]
+    #[case(&["--type", "futures", "--expiry", "2023-12-29", "--strike", "40000"], false)]
+    #[case(&["--type", "option", "--expiry", "2023-12-29", "--strike", "40000", "--option-right", "call"], true)]
+    #[case(&["--type", "option", "--expiry", "2023-12-29", "--option-right", "put"], false)]
+    #[case(&["--type", "option", "--expiry", "2023-12-29", "--strike", "1e5", "--option-right", "put"], false)]
+    #[case(&["--type", "perpetual", "--margin", "inverse"], true)]
+    #[case(&["--type", "futures", "--expiry", "2023-12-29", "--margin", "inverse"], true)]
+    #[case(&["--type", "spot", "--margin", "linear"], false)]
+    fn test_check_contract_options(#[case] input: &[&str], #[case] expected: bool) {
+        let cli = Cli::try_parse_from(["ro-soku"].iter().chain(input)).unwrap();
+        assert_eq!(cli.check_contract_options().is_ok(), expected);
+    }
+
+    #[rstest]
+    #[case(&["--pick", "t,o,h,l,c"], true)]
+    #[case(&["--kind", "funding", "--type", "perpetual"], true)]
+    #[case(&["--kind", "funding", "--type", "perpetual", "--pick", "t,rate,mark"], true)]
+    #[case(&["--kind", "funding"], false)]
+    #[case(&["--kind", "funding", "--type", "perpetual", "--pick", "o"], false)]
+    #[case(&["--kind", "funding", "--type", "perpetual", "--count", "10"], false)]
+    #[case(&["--kind", "funding", "--type", "perpetual", "--price-type", "mark"], false)]
+    #[case(&["--pick", "rate"], false)]
+    #[case(&["--pick", "t,qv,trades,tbv,tbqv,vwap"], true)]
+    #[case(&["--volume-unit", "quote"], true)]
+    #[case(&["--volume-unit", "contracts"], false)]
+    #[case(&["--type", "perpetual", "--volume-unit", "contracts"], true)]
+    #[case(&["--kind", "funding", "--type", "perpetual", "--volume-unit", "base"], false)]
+    #[case(&["--timestamp-at", "close"], true)]
+    #[case(&["--kind", "open-interest", "--type", "perpetual", "--timestamp-at", "close"], false)]
+    #[case(&["--kind", "funding", "--type", "perpetual", "--pick", "t,vwap"], false)]
+    #[case(&["--kind", "open-interest", "--type", "perpetual", "--pick", "t,oi"], true)]
+    #[case(&["--kind", "open-interest", "--type", "perpetual", "--pick", "rate"], false)]
+    #[case(&["--kind", "open-interest", "--type", "spot"], false)]
+    fn test_check_kind_options(#[case] input: &[&str], #[case] expected: bool) {
+        let cli = Cli::try_parse_from(["ro-soku"].iter().chain(input)).unwrap();
+        assert_eq!(cli.check_kind_options().is_ok(), expected);
+    }
+
     #[test]
     fn test_parse_terms_unixtime() {
         let term = "1144937572000".to_string();
@@ -331,17 +798,10 @@ mod tests {
     #[case(Some(1144937572000), Some(1000000000000))]
     fn test_check_term_relations(#[case] term_start: Option<i64>, #[case] term_end: Option<i64>) {
         let args = ParsedArgs {
-            exchange: Exchange::Binance(Binance::new()),
-            symbol: String::new(),
-            type_: MarketType::Spot,
-            past: false,
-            range: None,
             term_start,
             term_end,
             interval: DurationAndUnit(1, TermUnit::Min),
-            pick: vec![],
-            order: Order::Asc,
-            output: FormatType::Json,
+            ..Default::default()
         };
 
         assert!(args.check_term_relations().is_ok(),);
@@ -349,26 +809,20 @@ mod tests {
 
     #[test]
     fn test_fit_to_term_args_past() {
+        let now = DateTime::parse_from_rfc3339("2000-01-02T00:00:00.0000Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
         let args = ParsedArgs {
-            exchange: Exchange::Binance(Binance::new()),
-            symbol: String::new(),
-            type_: MarketType::Spot,
             past: true,
             range: Some(DurationAndUnit(1, TermUnit::Day)),
-            term_start: None,
-            term_end: None,
             interval: DurationAndUnit(1, TermUnit::Min),
-            pick: vec![],
-            order: Order::Asc,
-            output: FormatType::Json,
+            clock: Clock::Fixed(now),
+            ..Default::default()
         };
 
         let args = args.fit_to_term_args();
 
-        // Assume that 1 second cannot pass since `fit_to_term_args' was executed (I can't find a way to freeze it now)
-        let now = Utc::now();
-        // let now = DateTime::parse_from_rfc3339("2000-01-02T00:00:00.0000Z").unwrap().with_timezone(&Utc);
-
         let expected_start_time = (now - Duration::days(1)).timestamp() * 1000;
         let expected_end_time = now.timestamp() * 1000;
 
@@ -379,17 +833,10 @@ mod tests {
     #[test]
     fn test_fit_to_term_args_terms() {
         let args = ParsedArgs {
-            exchange: Exchange::Binance(Binance::new()),
-            symbol: String::new(),
-            type_: MarketType::Spot,
-            past: false,
-            range: None,
             term_start: Some(946684800000),
             term_end: Some(946771200000),
             interval: DurationAndUnit(1, TermUnit::Min),
-            pick: vec![],
-            order: Order::Asc,
-            output: FormatType::Json,
+            ..Default::default()
         };
 
         let args = args.fit_to_term_args();
@@ -403,26 +850,90 @@ mod tests {
 
     #[test]
     fn test_fit_to_term_args_end_now() {
+        let now = DateTime::parse_from_rfc3339("2000-01-02T00:00:00.0000Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
         let args = ParsedArgs {
-            exchange: Exchange::Binance(Binance::new()),
-            symbol: String::new(),
-            type_: MarketType::Spot,
-            past: false,
-            range: None,
             term_start: Some(946684800000),
             term_end: Some(9000000000000000000),
             interval: DurationAndUnit(1, TermUnit::Min),
-            pick: vec![],
-            order: Order::Asc,
-            output: FormatType::Json,
+            clock: Clock::Fixed(now),
+            ..Default::default()
         };
 
         let args = args.fit_to_term_args();
 
         let expected_start_time = 946684800000;
-        let expected_end_time = Utc::now().timestamp_millis();
+        let expected_end_time = now.timestamp_millis();
 
         assert_eq!(args.term_start.unwrap(), expected_start_time);
         assert_eq!(args.term_end.unwrap(), expected_end_time);
     }
+
+    #[rstest]
+    #[case(946771200000, 60, DurationAndUnit(15, TermUnit::Min), 946771200000 - 60 * 15 * 60 * 1000)]
+    // From 2023-06-15 back to 2023-03-15 by the calendar
+    #[case(1686787200000, 3, DurationAndUnit(1, TermUnit::Month), 1678838400000)]
+    fn test_fit_to_term_args_count(
+        #[case] term_end: i64,
+        #[case] count: u32,
+        #[case] interval: DurationAndUnit,
+        #[case] expected_start_time: i64,
+    ) {
+        let args = ParsedArgs {
+            term_end: Some(term_end),
+            count: Some(count),
+            interval,
+            clock: Clock::Fixed(Utc.timestamp_millis_opt(term_end).unwrap()),
+            ..Default::default()
+        };
+
+        let args = args.fit_to_term_args();
+
+        assert_eq!(args.term_start.unwrap(), expected_start_time);
+        assert_eq!(args.term_end.unwrap(), term_end);
+    }
+
+    #[test]
+    fn test_fit_to_term_args_clock_offset() {
+        // The exchange clock is one hour ahead of this machine
+        let offset = Duration::hours(1);
+        let args = ParsedArgs {
+            past: true,
+            range: Some(DurationAndUnit(1, TermUnit::Day)),
+            interval: DurationAndUnit(1, TermUnit::Min),
+            clock: Clock::System {
+                offset: offset.num_milliseconds(),
+            },
+            ..Default::default()
+        };
+
+        let before = Utc::now() + offset;
+        let args = args.fit_to_term_args();
+        let after = Utc::now() + offset;
+
+        // `--past` is counted in seconds
+        let term_end = args.term_end.unwrap();
+        assert!(before.timestamp() * 1000 <= term_end && term_end <= after.timestamp_millis());
+        assert_eq!(
+            args.term_start.unwrap(),
+            term_end - Duration::days(1).num_milliseconds()
+        );
+    }
+
+    #[rstest]
+    #[case(&["--now", "2000-01-02T00:00:00Z"], Some(Clock::Fixed(Utc.timestamp_millis_opt(946771200000).unwrap())))]
+    #[case(&["--local-clock"], Some(Clock::local()))]
+    #[case(&[], None)]
+    fn test_pinned_clock(#[case] input: &[&str], #[case] expected: Option<Clock>) {
+        let cli = Cli::try_parse_from(["ro-soku"].iter().chain(input)).unwrap();
+        assert_eq!(cli.pinned_clock().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_now_conflicts_with_local_clock() {
+        let input = ["ro-soku", "--now", "2000-01-02T00:00:00Z", "--local-clock"];
+        assert!(Cli::try_parse_from(input).is_err());
+    }
 }
@@ -0,0 +1,54 @@
+use chrono::{DateTime, Duration, Utc};
+
+/// Source of "now" for everything that depends on the current time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Clock {
+    /// Clock of this machine, corrected by the milliseconds to add to get the exchange time
+    System { offset: i64 },
+    /// Pinned time, used by tests and to replay a historical `--past` query exactly
+    Fixed(DateTime<Utc>),
+}
+
+impl Clock {
+    pub fn local() -> Self {
+        Clock::System { offset: 0 }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        match self {
+            Clock::System { offset } => Utc::now() + Duration::milliseconds(*offset),
+            Clock::Fixed(now) => *now,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_now_fixed() {
+        let now = DateTime::parse_from_rfc3339("2000-01-02T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let clock = Clock::Fixed(now);
+
+        assert_eq!(clock.now(), now);
+        assert_eq!(clock.now(), now);
+    }
+
+    #[test]
+    fn test_now_system_offset() {
+        // One hour ahead of this machine, wherever the machine clock is
+        let offset = Duration::hours(1);
+        let clock = Clock::System {
+            offset: offset.num_milliseconds(),
+        };
+
+        let before = Utc::now();
+        let now = clock.now();
+        let after = Utc::now();
+
+        assert!(before + offset <= now && now <= after + offset);
+    }
+}
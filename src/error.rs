@@ -11,6 +11,21 @@ impl ExchangeResponseError {
         anyhow!("The specified symbol pair does not exist in this exchange.")
     }
 
+    /// Same as `symbol()`, with the listed ones closest to it.
+    pub fn symbol_of(suggestions: &[String]) -> Error {
+        match suggestions.is_empty() {
+            true => anyhow!(
+                "The specified symbol pair does not exist in this exchange.\n\
+                The available ones can be listed with `ro-soku symbols`."
+            ),
+            false => anyhow!(
+                "The specified symbol pair does not exist in this exchange.\n\
+                Did you mean {}?",
+                suggestions.join(", ")
+            ),
+        }
+    }
+
     pub fn interval(exchange: &ExchangeChoices, market_type: &MarketType) -> Error {
         let data = include_str!("data/intervals.json");
 
@@ -21,6 +36,11 @@ impl ExchangeResponseError {
             .and_then(|market_type_map| market_type_map.get(&market_type.to_string()))
             .unwrap();
 
+        Self::interval_of(intervals)
+    }
+
+    /// Same as `interval()`, for exchanges that are not listed in `data/intervals.json`.
+    pub fn interval_of(intervals: &[String]) -> Error {
         anyhow!(
             "The specified interval of candlestick does not exist in this exchange.\n\
             Possible values:\n  \
@@ -37,6 +57,36 @@ impl ExchangeResponseError {
         anyhow!("This exchange does not support the market type.")
     }
 
+    pub fn no_support_margin() -> Error {
+        anyhow!("This exchange does not support `--margin`, its contracts are not distinguished by the margin type.")
+    }
+
+    pub fn no_support_price_type() -> Error {
+        anyhow!("This exchange does not support `--price-type` other than `last`.")
+    }
+
+    pub fn no_support_volume_unit() -> Error {
+        anyhow!("This exchange does not support `--volume-unit` with the market type, its contract size is unknown.")
+    }
+
+    pub fn no_support_kind() -> Error {
+        anyhow!("This exchange does not support the data kind.")
+    }
+
+    pub fn no_support_symbols() -> Error {
+        anyhow!("This exchange does not support listing its symbols.")
+    }
+
+    /// The exchange keeps only the recent history of the data kind.
+    pub fn look_back(days: i64) -> Error {
+        anyhow!("This exchange provides only the last {days} days of the data kind, specify a later `--term-start`.")
+    }
+
+    /// The exchange provides only the current value of the data kind, not its history.
+    pub fn no_history() -> Error {
+        anyhow!("This exchange provides only the current value of the data kind, specify a period which includes the present.")
+    }
+
     pub fn wrap_error(err: String) -> Error {
         anyhow!(err)
     }
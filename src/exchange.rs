@@ -1,32 +1,64 @@
 use std::fmt::Debug;
 
-use anyhow::Error;
+use anyhow::{anyhow, Error};
+use chrono::{Datelike, NaiveDate, TimeZone, Utc};
 use clap::ValueEnum;
+use console::style;
+use regex::Regex;
 use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
 
 pub mod binance;
 pub mod bitbank;
+pub mod bitflyer;
+pub mod bitget;
+pub mod bithumb;
 pub mod bitmex;
 pub mod bybit;
+pub mod coinbase;
+pub mod definition;
+pub mod deribit;
+pub mod gate;
+pub mod gmo;
+pub mod hyperliquid;
+pub mod kraken;
+pub mod kucoin;
 pub mod okx;
-// pub mod kraken;
+pub mod upbit;
 
 use crate::{
     args::*,
+    error::*,
     exchange::{
         binance::*,
         bitbank::*,
+        bitflyer::*,
+        bitget::*,
+        bithumb::*,
         bitmex::*,
         bybit::*,
-        // kraken::*,
+        coinbase::*,
+        definition::*,
+        deribit::*,
+        gate::*,
+        gmo::*,
+        hyperliquid::*,
+        kraken::*,
+        kucoin::*,
         okx::*,
+        upbit::*,
     },
     order::*,
     pick::*,
+    symbols,
     types::*,
     unit::*,
 };
 
+/// Number of empty windows in a row to give up looking further back with `--count`,
+/// for exchanges whose history has no known start (the last window is 1024 times the first one)
+const MAX_EMPTY_WINDOWS: u32 = 10;
+
 #[derive(
     Debug, Clone, ValueEnum, strum::Display, strum::IntoStaticStr, strum::EnumIter, strum::AsRefStr,
 )]
@@ -34,33 +66,273 @@ pub enum ExchangeChoices {
     Binance,
     #[strum(serialize = "bitbank")]
     Bitbank,
+    #[strum(serialize = "bitFlyer")]
+    Bitflyer,
+    Bitget,
+    Bithumb,
     #[strum(serialize = "BitMEX")]
     Bitmex,
     Bybit,
+    Coinbase,
+    Deribit,
+    #[strum(serialize = "Gate.io")]
+    Gate,
+    #[strum(serialize = "GMO Coin")]
+    Gmo,
+    Hyperliquid,
+    Kraken,
+    #[strum(serialize = "KuCoin")]
+    Kucoin,
     #[strum(serialize = "OKX")]
     Okx,
-    // Kraken,
+    Upbit,
 }
 
 #[derive(Debug, Clone)]
 pub enum Exchange {
     Binance(Binance),
     Bitbank(Bitbank),
+    Bitflyer(Bitflyer),
+    Bitget(Bitget),
+    Bithumb(Bithumb),
     Bitmex(Bitmex),
     Bybit(Bybit),
+    Coinbase(Coinbase),
+    /// Loaded from a definition file with `--definition`
+    Custom(Custom),
+    Deribit(Deribit),
+    Gate(Gate),
+    Gmo(Gmo),
+    Hyperliquid(Hyperliquid),
+    Kraken(Kraken),
+    Kucoin(Kucoin),
     Okx(Okx),
-    // Kraken(Kraken),
+    Upbit(Upbit),
+}
+
+impl From<&ExchangeChoices> for Exchange {
+    fn from(value: &ExchangeChoices) -> Self {
+        match value {
+            ExchangeChoices::Binance => Exchange::Binance(Binance::new()),
+            ExchangeChoices::Bitbank => Exchange::Bitbank(Bitbank::new()),
+            ExchangeChoices::Bitflyer => Exchange::Bitflyer(Bitflyer::new()),
+            ExchangeChoices::Bitget => Exchange::Bitget(Bitget::new()),
+            ExchangeChoices::Bithumb => Exchange::Bithumb(Bithumb::new()),
+            ExchangeChoices::Bitmex => Exchange::Bitmex(Bitmex::new()),
+            ExchangeChoices::Bybit => Exchange::Bybit(Bybit::new()),
+            ExchangeChoices::Coinbase => Exchange::Coinbase(Coinbase::new()),
+            ExchangeChoices::Deribit => Exchange::Deribit(Deribit::new()),
+            ExchangeChoices::Gate => Exchange::Gate(Gate::new()),
+            ExchangeChoices::Gmo => Exchange::Gmo(Gmo::new()),
+            ExchangeChoices::Hyperliquid => Exchange::Hyperliquid(Hyperliquid::new()),
+            ExchangeChoices::Kraken => Exchange::Kraken(Kraken::new()),
+            ExchangeChoices::Kucoin => Exchange::Kucoin(Kucoin::new()),
+            ExchangeChoices::Okx => Exchange::Okx(Okx::new()),
+            ExchangeChoices::Upbit => Exchange::Upbit(Upbit::new()),
+        }
+    }
 }
 
 impl Exchange {
     pub fn retrieve(&mut self, args: &mut ParsedArgs) -> Result<Vec<Row>, Error> {
-        match self {
+        // Only the exchanges which have both linear and inverse contracts of the same pair
+        // need `--margin`, the others should not silently ignore it
+        if args.contract.margin.is_some()
+            && !matches!(self, Exchange::Binance(_) | Exchange::Bybit(_))
+        {
+            return Err(ExchangeResponseError::no_support_margin());
+        }
+
+        if args.price_type != PriceType::Last
+            && !matches!(
+                self,
+                Exchange::Binance(_) | Exchange::Bybit(_) | Exchange::Okx(_)
+            )
+        {
+            return Err(ExchangeResponseError::no_support_price_type());
+        }
+
+        // Delisted pairs are no longer listed, but still served by some exchanges
+        let unlisted = match args.symbol_check {
+            true => self
+                .check_symbol(&args.symbol, &args.type_, &args.contract)
+                .err(),
+            false => None,
+        };
+
+        let result = match self {
             Exchange::Binance(binance) => binance.retrieve(args),
             Exchange::Bitbank(bitbank) => bitbank.retrieve(args),
+            Exchange::Bitflyer(bitflyer) => bitflyer.retrieve(args),
+            Exchange::Bitget(bitget) => bitget.retrieve(args),
+            Exchange::Bithumb(bithumb) => bithumb.retrieve(args),
             Exchange::Bitmex(bitmex) => bitmex.retrieve(args),
             Exchange::Bybit(bybit) => bybit.retrieve(args),
+            Exchange::Coinbase(coinbase) => coinbase.retrieve(args),
+            Exchange::Custom(custom) => custom.retrieve(args),
+            Exchange::Deribit(deribit) => deribit.retrieve(args),
+            Exchange::Gate(gate) => gate.retrieve(args),
+            Exchange::Gmo(gmo) => gmo.retrieve(args),
+            Exchange::Hyperliquid(hyperliquid) => hyperliquid.retrieve(args),
+            Exchange::Kraken(kraken) => kraken.retrieve(args),
+            Exchange::Kucoin(kucoin) => kucoin.retrieve(args),
             Exchange::Okx(okx) => okx.retrieve(args),
-            // Exchange::Kraken(kraken) => kraken.retrieve(args),
+            Exchange::Upbit(upbit) => upbit.retrieve(args),
+        };
+
+        match unlisted {
+            Some(unlisted) => settle_unlisted(result, unlisted, &args.symbol),
+            None => result,
+        }
+    }
+
+    /// Symbol pairs of `market_type` that can be retrieved, sorted by the pair (and the expiry).
+    /// `contract.margin` chooses linear or inverse contracts as with retrieving.
+    pub fn list_symbols(
+        &self,
+        market_type: &MarketType,
+        contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        let client = Client::new();
+
+        let mut listings = match self {
+            Exchange::Binance(binance) => binance.list_symbols(&client, market_type, contract),
+            Exchange::Bitbank(bitbank) => bitbank.list_symbols(&client, market_type, contract),
+            Exchange::Bitflyer(bitflyer) => bitflyer.list_symbols(&client, market_type, contract),
+            Exchange::Bitget(bitget) => bitget.list_symbols(&client, market_type, contract),
+            Exchange::Bithumb(bithumb) => bithumb.list_symbols(&client, market_type, contract),
+            Exchange::Bitmex(bitmex) => bitmex.list_symbols(&client, market_type, contract),
+            Exchange::Bybit(bybit) => bybit.list_symbols(&client, market_type, contract),
+            Exchange::Coinbase(coinbase) => coinbase.list_symbols(&client, market_type, contract),
+            Exchange::Custom(custom) => custom.list_symbols(&client, market_type, contract),
+            Exchange::Deribit(deribit) => deribit.list_symbols(&client, market_type, contract),
+            Exchange::Gate(gate) => gate.list_symbols(&client, market_type, contract),
+            Exchange::Gmo(gmo) => gmo.list_symbols(&client, market_type, contract),
+            Exchange::Hyperliquid(hyperliquid) => {
+                hyperliquid.list_symbols(&client, market_type, contract)
+            }
+            Exchange::Kraken(kraken) => kraken.list_symbols(&client, market_type, contract),
+            Exchange::Kucoin(kucoin) => kucoin.list_symbols(&client, market_type, contract),
+            Exchange::Okx(okx) => okx.list_symbols(&client, market_type, contract),
+            Exchange::Upbit(upbit) => upbit.list_symbols(&client, market_type, contract),
+        }?;
+
+        listings.sort();
+        listings.dedup();
+        Ok(listings)
+    }
+
+    /// The built-in exchange, `None` for the ones loaded from definition files.
+    pub fn choice(&self) -> Option<ExchangeChoices> {
+        match self {
+            Exchange::Binance(_) => Some(ExchangeChoices::Binance),
+            Exchange::Bitbank(_) => Some(ExchangeChoices::Bitbank),
+            Exchange::Bitflyer(_) => Some(ExchangeChoices::Bitflyer),
+            Exchange::Bitget(_) => Some(ExchangeChoices::Bitget),
+            Exchange::Bithumb(_) => Some(ExchangeChoices::Bithumb),
+            Exchange::Bitmex(_) => Some(ExchangeChoices::Bitmex),
+            Exchange::Bybit(_) => Some(ExchangeChoices::Bybit),
+            Exchange::Coinbase(_) => Some(ExchangeChoices::Coinbase),
+            Exchange::Custom(_) => None,
+            Exchange::Deribit(_) => Some(ExchangeChoices::Deribit),
+            Exchange::Gate(_) => Some(ExchangeChoices::Gate),
+            Exchange::Gmo(_) => Some(ExchangeChoices::Gmo),
+            Exchange::Hyperliquid(_) => Some(ExchangeChoices::Hyperliquid),
+            Exchange::Kraken(_) => Some(ExchangeChoices::Kraken),
+            Exchange::Kucoin(_) => Some(ExchangeChoices::Kucoin),
+            Exchange::Okx(_) => Some(ExchangeChoices::Okx),
+            Exchange::Upbit(_) => Some(ExchangeChoices::Upbit),
+        }
+    }
+
+    /// Whether `symbol` is one of `listings`, also by the name of a currency on the exchange like `XBT`.
+    pub fn is_listed(&self, symbol: &str, listings: &[Listing]) -> bool {
+        let aliases = match self {
+            Exchange::Bitmex(_) | Exchange::Kucoin(_) => BTC_AS_XBT,
+            // Perpetuals are settled in USDC, and `USD` is also accepted for them
+            Exchange::Hyperliquid(_) => &[("USD", "USDC")],
+            Exchange::Kraken(_) => ALIASES,
+            _ => &[],
+        };
+        let Ok(requested) = split_symbol(&symbol.to_uppercase(), aliases) else {
+            return false;
+        };
+
+        listings.iter().any(|listing| {
+            split_symbol(&listing.symbol(), aliases).ok().as_ref() == Some(&requested)
+        })
+    }
+
+    /// Fails with the closest symbols if `symbol` is not listed on the exchange.
+    /// Nothing is checked if the symbols cannot be listed, as retrieving will tell it anyway.
+    pub fn check_symbol(
+        &self,
+        symbol: &str,
+        market_type: &MarketType,
+        contract: &Contract,
+    ) -> Result<(), Error> {
+        let Some(choice) = self.choice() else {
+            return Ok(());
+        };
+        let Ok((mut listings, from_cache)) = symbols::load(self, &choice, market_type, contract)
+        else {
+            return Ok(());
+        };
+
+        // The cache may be older than the listing of the symbol
+        if from_cache && !self.is_listed(symbol, &listings) {
+            if let Ok(fetched) = symbols::fetch(self, &choice, market_type, contract) {
+                listings = fetched;
+            }
+        }
+
+        if listings.is_empty() || self.is_listed(symbol, &listings) {
+            return Ok(());
+        }
+
+        Err(ExchangeResponseError::symbol_of(&symbols::suggest(
+            symbol, &listings,
+        )))
+    }
+
+    /// How many milliseconds the exchange clock is ahead of the local clock.
+    /// The half of the round trip is regarded as the time the server took to respond.
+    /// If the server time is not available, the local clock is used as is.
+    pub fn clock_offset(&self, market_type: &MarketType) -> i64 {
+        let client = Client::new();
+
+        let before = Utc::now().timestamp_millis();
+        let server_time = match self {
+            Exchange::Binance(binance) => binance.server_time(&client, market_type),
+            Exchange::Bitbank(bitbank) => bitbank.server_time(&client, market_type),
+            Exchange::Bitflyer(bitflyer) => bitflyer.server_time(&client, market_type),
+            Exchange::Bitget(bitget) => bitget.server_time(&client, market_type),
+            Exchange::Bithumb(bithumb) => bithumb.server_time(&client, market_type),
+            Exchange::Bitmex(bitmex) => bitmex.server_time(&client, market_type),
+            Exchange::Bybit(bybit) => bybit.server_time(&client, market_type),
+            Exchange::Coinbase(coinbase) => coinbase.server_time(&client, market_type),
+            Exchange::Custom(custom) => custom.server_time(&client, market_type),
+            Exchange::Deribit(deribit) => deribit.server_time(&client, market_type),
+            Exchange::Gate(gate) => gate.server_time(&client, market_type),
+            Exchange::Gmo(gmo) => gmo.server_time(&client, market_type),
+            Exchange::Hyperliquid(hyperliquid) => hyperliquid.server_time(&client, market_type),
+            Exchange::Kraken(kraken) => kraken.server_time(&client, market_type),
+            Exchange::Kucoin(kucoin) => kucoin.server_time(&client, market_type),
+            Exchange::Okx(okx) => okx.server_time(&client, market_type),
+            Exchange::Upbit(upbit) => upbit.server_time(&client, market_type),
+        };
+        let after = Utc::now().timestamp_millis();
+
+        match server_time {
+            Ok(Some(server_time)) => server_time - (before + after) / 2,
+            Ok(None) => 0,
+            Err(e) => {
+                eprintln!(
+                    "{} Failed to get the server time, the local clock is used instead ({e}).",
+                    style("warn:").yellow()
+                );
+                0
+            }
         }
     }
 }
@@ -69,9 +341,128 @@ pub trait Retrieve: Debug {
     fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error>;
 
     fn retrieve(&mut self, args: &mut ParsedArgs) -> Result<Vec<Row>, Error> {
-        let mut result: Vec<Kline> = Vec::new();
+        if let DataKind::Funding = args.kind {
+            let result = self.collect_funding(args)?;
+            let data = Order::sort(result, &args.order);
+            return Ok(Pick::up_funding(data, &args.pick, &args.timestamp));
+        }
+
+        if let DataKind::OpenInterest = args.kind {
+            let result = self.collect_open_interest(args)?;
+            let data = Order::sort(result, &args.order);
+            return Ok(Pick::up_open_interest(data, &args.pick, &args.timestamp));
+        }
+
+        let mut result = match args.count {
+            Some(count) => self.collect_latest_klines(args, count as usize)?,
+            None => self.collect_klines(args)?,
+        };
+
+        // A candle is still forming until its closing time comes (months are of the calendar)
+        let now = args.now().timestamp_millis();
+        let is_closed = |opening: i64| args.interval.shift(opening, 1) <= now;
+
+        if let Unclosed::Exclude = args.unclosed {
+            result.retain(|kline| is_closed(kline.unixtime_msec));
+        }
+
+        if let Some(unit) = &args.volume_unit {
+            let native = self.native_volume(&Client::new(), args)?;
+            for kline in result.iter_mut() {
+                kline.v = native.convert(kline, unit);
+            }
+        }
+
+        // Every exchange returns candles by their opening time so far
+        if let TimestampAt::Close = args.timestamp_at {
+            for kline in result.iter_mut() {
+                kline.unixtime_msec = args.interval.shift(kline.unixtime_msec, 1);
+            }
+        }
+
+        let data = Order::sort(result, &args.order);
+        let mut data = Pick::up(data, &args.pick, &args.timestamp, |kline| {
+            match args.timestamp_at {
+                TimestampAt::Open => is_closed(kline.unixtime_msec),
+                // Already stamped at its closing time
+                TimestampAt::Close => kline.unixtime_msec <= now,
+            }
+        });
+
+        // Nothing is traded at the mark, index or premium price, and the volume of its candles is
+        // only a placeholder of exchanges (like 0)
+        if args.price_type != PriceType::Last {
+            for value in data
+                .iter_mut()
+                .flatten()
+                .filter_map(|cell| cell.get_mut(&Pick::V))
+            {
+                *value = KlineNumber::Null;
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Repeat requests from `term_start` to `term_end` until all the data in the period is collected.
+    fn collect_klines(&mut self, args: &mut ParsedArgs) -> Result<Vec<Kline>, Error> {
+        let step = args.interval.to_msec();
+        let window = self.window(args);
+        self.paginate(
+            args,
+            step,
+            window,
+            Self::prepare,
+            Self::parse_as_kline,
+            Self::remove_unnecessary_raws,
+        )
+    }
+
+    /// Same as `collect_klines()`, for funding rates.
+    fn collect_funding(&mut self, args: &mut ParsedArgs) -> Result<Vec<Funding>, Error> {
+        // Rates are settled at their own times, which have nothing to do with `--interval`
+        self.paginate(
+            args,
+            1,
+            None,
+            Self::prepare_funding,
+            Self::parse_as_funding,
+            retain_in_term,
+        )
+    }
+
+    /// Same as `collect_klines()`, for open interest.
+    fn collect_open_interest(&mut self, args: &mut ParsedArgs) -> Result<Vec<OpenInterest>, Error> {
+        let step = args.interval.to_msec();
+        self.paginate(
+            args,
+            step,
+            None,
+            Self::prepare_open_interest,
+            Self::parse_as_open_interest,
+            retain_in_term,
+        )
+    }
+
+    /// The pagination loop shared by all data kinds. The next page starts right after the latest
+    /// record (or ends right before the oldest one, for exchanges that return newest first)
+    /// of the previous page, and it stops when a page is empty or reaches the end of the period.
+    /// `step` is the least gap between two records, which the next page is moved by.
+    /// With `window` (see `window()`), an empty page only ends its window, not the whole period.
+    fn paginate<T: Timestamped>(
+        &mut self,
+        args: &mut ParsedArgs,
+        step: i64,
+        window: Option<i64>,
+        prepare: fn(&mut Self, &ParsedArgs) -> Result<(), Error>,
+        parse: fn(&Self, String) -> Vec<T>,
+        remove: fn(Vec<T>, i64, i64) -> Vec<T>,
+    ) -> Result<Vec<T>, Error> {
+        let mut result: Vec<T> = Vec::new();
         let mut should_continue = true;
         let client = reqwest::blocking::Client::new();
+        // Kept from the last page of two or more records, a single record tells nothing
+        let mut sort_order: Order = Order::Asc;
 
         while should_continue {
             // If the number of limits is exactly the same as the number of data required,
@@ -80,28 +471,22 @@ pub trait Retrieve: Debug {
             args.term_start = Some(args.term_start.unwrap() - 1);
             args.term_end = Some(args.term_end.unwrap() + 1);
 
-            self.prepare(&args.clone())?;
+            prepare(self, &args.clone())?;
 
             let res = self.fetch(&client)?;
-            let klines = self.parse_as_kline(res);
+            let records = parse(self, res);
 
             // Most exchanges do nothing
-            let klines = Self::remove_unnecessary_raws(
-                klines,
-                args.term_start.unwrap(),
-                args.term_end.unwrap(),
-            );
+            let records = remove(records, args.term_start.unwrap(), args.term_end.unwrap());
 
-            // This initialization is meaningless, only for compile error prevention
-            let mut sort_order: Order = Order::Asc;
-            if 2 <= klines.len() {
-                sort_order = determine_sort_order(&[&klines[0], &klines[1]]);
+            if 2 <= records.len() {
+                sort_order = determine_sort_order(&[&records[0], &records[1]]);
             }
 
-            match klines.last() {
+            match records.last() {
                 Some(latest) => match sort_order {
                     Order::Asc => {
-                        let next_term_start = latest.unixtime_msec + args.interval.to_msec();
+                        let next_term_start = latest.unixtime_msec() + step;
 
                         if (args.term_end.unwrap()) < next_term_start {
                             should_continue = false;
@@ -110,7 +495,7 @@ pub trait Retrieve: Debug {
                         }
                     }
                     Order::Desc => {
-                        let next_term_end = latest.unixtime_msec - args.interval.to_msec();
+                        let next_term_end = latest.unixtime_msec() - step;
 
                         if next_term_end < args.term_start.unwrap() {
                             should_continue = false;
@@ -119,20 +504,109 @@ pub trait Retrieve: Debug {
                         }
                     }
                 },
-                None => should_continue = false,
+                None => match window {
+                    // Like before the listing, the next window may still have some
+                    Some(window) if args.term_start.unwrap() + window < args.term_end.unwrap() => {
+                        args.term_start = Some(args.term_start.unwrap() + window + 1);
+                    }
+                    _ => should_continue = false,
+                },
             };
 
+            result.extend(records);
+        }
+
+        Ok(result)
+    }
+
+    /// Collect the latest `count` closed candles up to `term_end` by walking backwards window by window.
+    /// Each window is sized to the number of candles still missing, so gaps of the exchange (maintenance,
+    /// delisting, etc.) are filled by the following windows. When a window is empty, the next one is
+    /// doubled in size. It stops at `history_start()`, or after `MAX_EMPTY_WINDOWS` empty windows
+    /// in a row if the exchange does not tell where its history starts, and warns if fewer were found.
+    fn collect_latest_klines(
+        &mut self,
+        args: &mut ParsedArgs,
+        count: usize,
+    ) -> Result<Vec<Kline>, Error> {
+        // No exchange has candles before the epoch
+        let history_start = self.history_start(args).unwrap_or(0);
+        let mut result: Vec<Kline> = Vec::new();
+        let mut empty_windows = 0;
+
+        // Exclusive upper bound of the opening time, only candles already closed at `term_end` are counted
+        // (months are of the calendar)
+        let mut window_end = args.interval.shift(args.term_end.unwrap(), -1) + 1;
+
+        while result.len() < count
+            && empty_windows < MAX_EMPTY_WINDOWS
+            && history_start < window_end
+        {
+            let candles = ((count - result.len()) as i64).saturating_mul(2_i64.pow(empty_windows));
+            let window_start = args.interval.shift(window_end, -candles).max(history_start);
+
+            let mut window_args = args.clone();
+            window_args.term_start = Some(window_start);
+            window_args.term_end = Some(window_end - 1);
+
+            let mut klines = self.collect_klines(&mut window_args)?;
+            klines.retain(|kline| {
+                window_start <= kline.unixtime_msec && kline.unixtime_msec < window_end
+            });
+
+            if klines.is_empty() {
+                empty_windows += 1;
+            } else {
+                empty_windows = 0;
+            }
+
             result.extend(klines);
+            window_end = window_start;
         }
 
-        let data = Order::sort(result, &args.order);
-        let data = Pick::up(data, &args.pick);
+        // Walking backwards may collect a little more than needed
+        let mut result = Order::sort(result, &Order::Desc);
+        result.truncate(count);
 
-        Ok(data)
+        if result.len() < count {
+            eprintln!(
+                "{} {} candles were requested, but only {} were found before the start of the history.",
+                style("warn:").yellow(),
+                count,
+                result.len()
+            );
+        }
+
+        Ok(result)
+    }
+
+    /// Opening time of the oldest candle the exchange serves for `args`, if its history is limited
+    /// (e.g. only the latest candles), where `--count` stops looking further back.
+    /// Exchanges that serve the whole history keep this default.
+    #[allow(unused_variables)]
+    fn history_start(&self, args: &ParsedArgs) -> Option<i64> {
+        None
     }
 
+    /// Span from `term_start` that a request of candles is cut to, for exchanges that need
+    /// the period cut into windows of their limit by themselves (the generic loop moves on to
+    /// the next one). Exchanges that page by the limit alone keep this default.
+    #[allow(unused_variables)]
+    fn window(&self, args: &ParsedArgs) -> Option<i64> {
+        None
+    }
+
+    /// Send the request built in `prepare()` and return the raw response body.
+    /// Most exchanges take GET with query parameters, but the method is up to each exchange
+    /// (e.g. Hyperliquid keeps a JSON body in `prepare()` and sends it with POST).
     fn fetch(&self, client: &Client) -> Result<String, Error>;
 
+    /// Current unixtime (millisecond) of the exchange, `None` if the exchange does not provide it.
+    #[allow(unused_variables)]
+    fn server_time(&self, client: &Client, market_type: &MarketType) -> Result<Option<i64>, Error> {
+        Ok(None)
+    }
+
     fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error>;
 
     // Some exchange intervals may be invalid (why using `Result`)
@@ -140,6 +614,55 @@ pub trait Retrieve: Debug {
 
     fn parse_as_kline(&self, data: String) -> Vec<Kline>;
 
+    /// Unit of `v` in the responses for `args`, to convert it with `--volume-unit`.
+    /// Called after the klines are collected, so the state of `prepare()` is available.
+    /// Volumes of spot are in the base currency on every exchange, while the contract size
+    /// of derivatives has to be provided by each exchange.
+    #[allow(unused_variables)]
+    fn native_volume(&self, client: &Client, args: &ParsedArgs) -> Result<NativeVolume, Error> {
+        match args.type_ {
+            MarketType::Spot => Ok(NativeVolume::Base),
+            _ => Err(ExchangeResponseError::no_support_volume_unit()),
+        }
+    }
+
+    /// Symbol pairs of `market_type` for `ro-soku symbols`, only the ones whose names on the exchange
+    /// are what `fit_symbol_to_req()` converts them into (so that they can be retrieved as listed).
+    /// Exchanges that do not provide their instruments keep this default.
+    #[allow(unused_variables)]
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        Err(ExchangeResponseError::no_support_symbols())
+    }
+
+    /// Same as `prepare()`, but for funding rates (perpetual only).
+    /// Exchanges that do not provide their history keep this default.
+    #[allow(unused_variables)]
+    fn prepare_funding(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        Err(ExchangeResponseError::no_support_kind())
+    }
+
+    #[allow(unused_variables)]
+    fn parse_as_funding(&self, data: String) -> Vec<Funding> {
+        unreachable!(/* `prepare_funding()` fails before fetching */)
+    }
+
+    /// Same as `prepare()`, but for open interest (perpetual only) at each `args.interval`.
+    /// Exchanges that do not provide its history keep this default.
+    #[allow(unused_variables)]
+    fn prepare_open_interest(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        Err(ExchangeResponseError::no_support_kind())
+    }
+
+    #[allow(unused_variables)]
+    fn parse_as_open_interest(&self, data: String) -> Vec<OpenInterest> {
+        unreachable!(/* `prepare_open_interest()` fails before fetching */)
+    }
+
     /// Use on exchanges where data must be parsed as `Kline` and then organized before the next fetch.
     #[allow(unused_variables)]
     fn remove_unnecessary_raws(raws: Vec<Kline>, term_start: i64, term_end: i64) -> Vec<Kline> {
@@ -155,27 +678,912 @@ pub struct Kline {
     pub l: f64,
     pub c: f64,
     pub v: f64,
+    pub extra: KlineExtra,
+}
+
+/// Fields of a candle which only some exchanges provide, `None` on the others.
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct KlineExtra {
+    /// Volume in the quote currency (turnover)
+    pub quote_volume: Option<f64>,
+    pub trades: Option<u64>,
+    /// Volume of trades whose taker was the buyer, in the base currency
+    pub taker_buy_volume: Option<f64>,
+    /// Same as `taker_buy_volume`, in the quote currency
+    pub taker_buy_quote_volume: Option<f64>,
+    pub vwap: Option<f64>,
+}
+
+/// Unit in which an exchange counts the volume of a market.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NativeVolume {
+    Base,
+    /// Contracts of `size` in the base currency (linear), or in the quote currency (inverse)
+    Contracts {
+        size: f64,
+        inverse: bool,
+    },
+}
+
+impl NativeVolume {
+    /// The volume of `kline` in `unit`. Between the base and the quote currency, the exact quote
+    /// volume is used if the exchange provides it, otherwise the VWAP or the close price.
+    /// `--volume-unit contracts` with spot is rejected in the validation of arguments.
+    pub fn convert(&self, kline: &Kline, unit: &VolumeUnit) -> f64 {
+        let price = kline.extra.vwap.unwrap_or(kline.c);
+
+        let (base, quote) = match self {
+            NativeVolume::Base => (kline.v, kline.extra.quote_volume.unwrap_or(kline.v * price)),
+            NativeVolume::Contracts {
+                size,
+                inverse: false,
+            } => (kline.v * size, kline.v * size * price),
+            NativeVolume::Contracts {
+                size,
+                inverse: true,
+            } => {
+                let quote = kline.v * size;
+                // No price means no trade, and so no volume either
+                (if 0.0 < price { quote / price } else { 0.0 }, quote)
+            }
+        };
+
+        match (unit, self) {
+            (VolumeUnit::Base, _) => base,
+            (VolumeUnit::Quote, _) => quote,
+            (VolumeUnit::Contracts, NativeVolume::Contracts { .. }) => kline.v,
+            (VolumeUnit::Contracts, NativeVolume::Base) => {
+                unreachable!(/* Checked in the arguments */)
+            }
+        }
+    }
+}
+
+/// Funding rate of a perpetual contract, settled at `unixtime_msec`.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Funding {
+    pub unixtime_msec: i64,
+    pub rate: f64,
+    /// Only some exchanges return the mark price at the settlement
+    pub mark_price: Option<f64>,
+}
+
+/// Open interest of a perpetual contract at `unixtime_msec`.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct OpenInterest {
+    pub unixtime_msec: i64,
+    /// In the base currency, or in contracts on exchanges which count them so (e.g. inverse contracts)
+    pub amount: f64,
+    /// Notional value, not provided by some exchanges
+    pub value: Option<f64>,
+}
+
+/// Any kind of data which is paged through and sorted by its time.
+pub trait Timestamped {
+    fn unixtime_msec(&self) -> i64;
+}
+
+impl Timestamped for Kline {
+    fn unixtime_msec(&self) -> i64 {
+        self.unixtime_msec
+    }
+}
+
+impl Timestamped for Funding {
+    fn unixtime_msec(&self) -> i64 {
+        self.unixtime_msec
+    }
+}
+
+impl Timestamped for OpenInterest {
+    fn unixtime_msec(&self) -> i64 {
+        self.unixtime_msec
+    }
+}
+
+/// Symbol pair listed by `ro-soku symbols`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Listing {
+    pub base: String,
+    pub quote: String,
+    /// Name of the instrument on the exchange
+    pub native: String,
+    /// Futures only
+    pub expiry: Option<NaiveDate>,
+}
+
+impl Listing {
+    /// `base` and `quote` are as named on the exchange, `aliases` are the ones of `split_symbol()`.
+    pub fn new(base: &str, quote: &str, native: &str, aliases: &[(&str, &str)]) -> Self {
+        let unalias = |currency: &str| {
+            let currency = currency.to_uppercase();
+            aliases
+                .iter()
+                .find(|(_, to)| *to == currency)
+                .map_or(currency.clone(), |(from, _)| from.to_string())
+        };
+
+        Listing {
+            base: unalias(base),
+            quote: unalias(quote),
+            native: native.to_string(),
+            expiry: None,
+        }
+    }
+
+    /// The date (UTC) of the delivery time of futures.
+    pub fn expiring_at(self, unixtime_msec: i64) -> Self {
+        Listing {
+            expiry: Utc
+                .timestamp_millis_opt(unixtime_msec)
+                .single()
+                .map(|datetime| datetime.date_naive()),
+            ..self
+        }
+    }
+
+    /// In the notation of `--symbol` like `BTC/USDT`
+    pub fn symbol(&self) -> String {
+        format!("{}/{}", self.base, self.quote)
+    }
+
+    /// Filter of `ro-soku symbols` (case-insensitive), `None` matches any currency.
+    pub fn is_of(&self, base: &Option<String>, quote: &Option<String>) -> bool {
+        let matches = |filter: &Option<String>, currency: &str| {
+            filter
+                .as_ref()
+                .is_none_or(|filter| filter.eq_ignore_ascii_case(currency))
+        };
+
+        matches(base, &self.base) && matches(quote, &self.quote)
+    }
+}
+
+/// A single execution, for exchanges that have no candlestick endpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub unixtime_msec: i64,
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Period of the trades to build the candles opening from `term_start` to `term_end` as a whole,
+/// so that they are the same as the ones of exchanges with candlestick endpoints: the candle containing
+/// `term_start` is left out if it opens before it, and the one containing `term_end` is covered until it closes.
+pub fn trade_term(term_start: i64, term_end: i64, interval: &DurationAndUnit) -> (i64, i64) {
+    let first_opening = match interval.opening_time(term_start) {
+        opening_time if opening_time == term_start => term_start,
+        _ => interval.closing_time(term_start),
+    };
+
+    (first_opening, interval.closing_time(term_end) - 1)
+}
+
+/// Build candles from trades. Periods without any trade have no candle, same as most exchanges.
+pub fn aggregate_trades(mut trades: Vec<Trade>, interval: &DurationAndUnit) -> Vec<Kline> {
+    // Stable sort keeps the execution order of trades in the same millisecond
+    trades.sort_by_key(|trade| trade.unixtime_msec);
+
+    let mut result: Vec<Kline> = Vec::new();
+
+    for trade in trades.iter() {
+        let opening_time = interval.opening_time(trade.unixtime_msec);
+
+        match result.last_mut() {
+            Some(kline) if kline.unixtime_msec == opening_time => {
+                kline.h = kline.h.max(trade.price);
+                kline.l = kline.l.min(trade.price);
+                kline.c = trade.price;
+                kline.v += trade.size;
+            }
+            _ => result.push(Kline {
+                unixtime_msec: opening_time,
+                o: trade.price,
+                h: trade.price,
+                l: trade.price,
+                c: trade.price,
+                v: trade.size,
+                extra: KlineExtra::default(),
+            }),
+        }
+    }
+
+    result
+}
+
+/// Currency aliases of exchanges that name bitcoin `XBT` after ISO 4217.
+pub const BTC_AS_XBT: &[(&str, &str)] = &[("BTC", "XBT")];
+
+/// Split a symbol pair like `BTC/USDT` into the base and quote currencies,
+/// replacing the ones named differently on the exchange with `aliases`.
+pub fn split_symbol(symbol: &str, aliases: &[(&str, &str)]) -> Result<(String, String), Error> {
+    let re = Regex::new(r"^(.*?)/(.*?)$").unwrap();
+    let matches = re.captures(symbol).ok_or(anyhow!(
+        "The symbol pair provided is incorrectly formatted."
+    ))?;
+
+    let alias = |currency: &str| {
+        aliases
+            .iter()
+            .find(|(from, _)| *from == currency)
+            .map_or(currency, |(_, to)| to)
+            .to_string()
+    };
+
+    Ok((alias(&matches[1]), alias(&matches[2])))
+}
+
+/// Partition of the candles on exchanges that return a whole day or year per request
+/// (with the date in the URL or query) instead of a period.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Partition {
+    Day,
+    Year,
+}
+
+impl Partition {
+    /// Date of the partition containing `term_start`, as `YYYYMMDD` or `YYYY`.
+    /// `offset_msec` is added to UTC when the exchange cuts partitions in another time zone.
+    /// If it crosses days or years, only the first one is returned (then retrieve will repeat itself).
+    pub fn date(&self, term_start: i64, offset_msec: i64) -> String {
+        // On all exchanges, there is no problem to do -1/+1 when actually fetching,
+        // but only when determining if the day has passed, otherwise you will get the wrong result
+        let term_start = term_start + 1;
+
+        let start = Utc.timestamp_millis_opt(term_start + offset_msec).unwrap();
+
+        match self {
+            Partition::Day => format!("{}{:02}{:02}", start.year(), start.month(), start.day()),
+            Partition::Year => format!("{}", start.year()),
+        }
+    }
+}
+
+/// Result of retrieving a symbol that `Exchange::check_symbol()` failed with `unlisted`,
+/// which only stands if something was retrieved anyway (like a delisted pair).
+/// Otherwise the closest symbols tell more than the error of the exchange (or no data at all).
+fn settle_unlisted(
+    result: Result<Vec<Row>, Error>,
+    unlisted: Error,
+    symbol: &str,
+) -> Result<Vec<Row>, Error> {
+    match result {
+        Ok(rows) if !rows.is_empty() => {
+            eprintln!(
+                "{} {} is not listed on the exchange (perhaps delisted), use `--no-symbol-check` to skip looking it up.",
+                style("warn:").yellow(),
+                symbol
+            );
+            Ok(rows)
+        }
+        _ => Err(unlisted),
+    }
+}
+
+/// Use as `remove_unnecessary_raws` on exchanges that may return candles outside the period
+/// (a whole partition, or a page specified only by its end).
+pub fn retain_in_term<T: Timestamped>(raws: Vec<T>, term_start: i64, term_end: i64) -> Vec<T> {
+    // Regardless of the order of the response
+    let mut result = raws;
+    result.retain(|raw| term_start < raw.unixtime_msec() && raw.unixtime_msec() < term_end);
+    result
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum KlineNumber {
     Unixtime(i64),
+    Datetime(String),
     Ohlcv(f64),
+    Flag(bool),
+    /// The exchange does not provide the value
+    Null,
 }
 
 impl KlineNumber {
     pub fn as_string(&self) -> String {
         match self {
             KlineNumber::Unixtime(n) => format!("{}", n),
+            KlineNumber::Datetime(s) => s.clone(),
             KlineNumber::Ohlcv(n) => format!("{}", n),
+            KlineNumber::Flag(b) => format!("{}", b),
+            KlineNumber::Null => "null".to_string(),
+        }
+    }
+
+    /// Same as `as_string()`, but a missing value is an empty cell (for CSV and TSV).
+    pub fn as_cell(&self) -> String {
+        match self {
+            KlineNumber::Null => String::new(),
+            number => number.as_string(),
         }
     }
 }
 
-fn determine_sort_order(first_two_klines: &[&Kline; 2]) -> Order {
-    if first_two_klines[0].unixtime_msec < first_two_klines[1].unixtime_msec {
+fn determine_sort_order<T: Timestamped>(first_two_records: &[&T; 2]) -> Order {
+    if first_two_records[0].unixtime_msec() < first_two_records[1].unixtime_msec() {
         Order::Asc
     } else {
         Order::Desc
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+    use crate::clock::*;
+
+    const MINUTE: i64 = 60 * 1000;
+    // Monthly candles of 2023-04-01, 2023-05-01 and 2023-06-01
+    const APRIL: i64 = 1680307200000;
+    const MAY: i64 = 1682899200000;
+    const JUNE: i64 = 1685577600000;
+
+    /// Returns up to 3 candles per page from the prepared data, just like a real exchange with a small limit
+    #[derive(Debug)]
+    struct Dummy {
+        unixtimes: Vec<i64>,
+        page_order: Order,
+        term: (i64, i64),
+        history_start: Option<i64>,
+        window: Option<i64>,
+    }
+
+    impl Retrieve for Dummy {
+        fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+            let term_start = args.term_start.unwrap();
+            let term_end = match self.window {
+                Some(window) => args.term_end.unwrap().min(term_start + window),
+                None => args.term_end.unwrap(),
+            };
+            self.term = (term_start, term_end);
+            Ok(())
+        }
+
+        fn history_start(&self, _args: &ParsedArgs) -> Option<i64> {
+            self.history_start
+        }
+
+        fn window(&self, _args: &ParsedArgs) -> Option<i64> {
+            self.window
+        }
+
+        fn fetch(&self, _client: &Client) -> Result<String, Error> {
+            let mut page: Vec<i64> = self
+                .unixtimes
+                .iter()
+                .filter(|&&t| self.term.0 <= t && t <= self.term.1)
+                .cloned()
+                .collect();
+            if let Order::Desc = self.page_order {
+                page.reverse();
+            }
+            page.truncate(3);
+
+            Ok(serde_json::to_string(&page)?)
+        }
+
+        fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+            Ok(symbol.to_string())
+        }
+
+        fn fit_interval_to_req(&self, _interval: &DurationAndUnit) -> Result<String, Error> {
+            Ok(String::new())
+        }
+
+        fn parse_as_kline(&self, data: String) -> Vec<Kline> {
+            serde_json::from_str::<Vec<i64>>(&data)
+                .unwrap()
+                .iter()
+                .map(|&t| Kline {
+                    unixtime_msec: t,
+                    o: 0.0,
+                    h: 0.0,
+                    l: 0.0,
+                    c: 0.0,
+                    v: 0.0,
+                    extra: KlineExtra::default(),
+                })
+                .collect()
+        }
+
+        fn prepare_funding(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+            self.prepare(args)
+        }
+
+        fn parse_as_funding(&self, data: String) -> Vec<Funding> {
+            serde_json::from_str::<Vec<i64>>(&data)
+                .unwrap()
+                .iter()
+                .map(|&t| Funding {
+                    unixtime_msec: t,
+                    rate: 0.0001,
+                    mark_price: None,
+                })
+                .collect()
+        }
+    }
+
+    #[rstest]
+    #[case(Order::Asc)]
+    #[case(Order::Desc)]
+    fn test_collect_latest_klines(#[case] page_order: Order) {
+        // Minutes 0 to 20 with a maintenance gap at 10 to 14, and 20 is still forming at `term_end`
+        let mut dummy = Dummy {
+            unixtimes: (0..=20)
+                .filter(|m| !(10..=14).contains(m))
+                .map(|m| m * MINUTE)
+                .collect(),
+            page_order,
+            term: (0, 0),
+            history_start: None,
+            window: None,
+        };
+        let mut args = ParsedArgs {
+            term_end: Some(20 * MINUTE + 30 * 1000),
+            count: Some(8),
+            interval: DurationAndUnit(1, TermUnit::Min),
+            ..Default::default()
+        };
+
+        let result: Vec<i64> = dummy
+            .collect_latest_klines(&mut args, 8)
+            .unwrap()
+            .iter()
+            .map(|kline| kline.unixtime_msec / MINUTE)
+            .collect();
+
+        assert_eq!(result, vec![19, 18, 17, 16, 15, 9, 8, 7]);
+    }
+
+    #[test]
+    fn test_collect_klines_empty_window() {
+        // Listed at minute 10, while a request covers 3 minutes from its start (oldest first)
+        let mut dummy = Dummy {
+            unixtimes: (10..=15).map(|m| m * MINUTE).collect(),
+            page_order: Order::Asc,
+            term: (0, 0),
+            history_start: None,
+            window: Some(3 * MINUTE),
+        };
+        let mut args = args_of_minute(MarketType::Spot, 0, 20 * MINUTE);
+
+        let result: Vec<i64> = Order::sort(dummy.collect_klines(&mut args).unwrap(), &Order::Asc)
+            .iter()
+            .map(|kline| kline.unixtime_msec / MINUTE)
+            .collect();
+
+        assert_eq!(result, (10..=15).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_collect_latest_klines_month() {
+        // 2023-01-01 to 2023-06-01, and June is still forming on 2023-06-15
+        let mut dummy = Dummy {
+            unixtimes: vec![
+                1672531200000,
+                1675209600000,
+                1677628800000,
+                APRIL,
+                MAY,
+                JUNE,
+            ],
+            page_order: Order::Asc,
+            term: (0, 0),
+            history_start: None,
+            window: None,
+        };
+        let mut args = ParsedArgs {
+            term_end: Some(1686787200000),
+            count: Some(3),
+            interval: DurationAndUnit(1, TermUnit::Month),
+            ..Default::default()
+        };
+
+        let result: Vec<i64> = dummy
+            .collect_latest_klines(&mut args, 3)
+            .unwrap()
+            .iter()
+            .map(|kline| kline.unixtime_msec)
+            .collect();
+
+        assert_eq!(result, vec![MAY, APRIL, 1677628800000]);
+    }
+
+    #[rstest]
+    // Aligned to the candles
+    #[case(10 * MINUTE, 20 * MINUTE, (10 * MINUTE, 21 * MINUTE - 1))]
+    // In the middle of candles, like `--past`
+    #[case(10 * MINUTE + 1, 20 * MINUTE + 30 * 1000, (11 * MINUTE, 21 * MINUTE - 1))]
+    #[case(10 * MINUTE, 21 * MINUTE - 1, (10 * MINUTE, 21 * MINUTE - 1))]
+    fn test_trade_term(
+        #[case] term_start: i64,
+        #[case] term_end: i64,
+        #[case] expected: (i64, i64),
+    ) {
+        assert_eq!(
+            trade_term(term_start, term_end, &DurationAndUnit(1, TermUnit::Min)),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_aggregate_trades() {
+        let trades = vec![
+            Trade {
+                unixtime_msec: 2 * MINUTE + 10,
+                price: 105.0,
+                size: 3.0,
+            },
+            Trade {
+                unixtime_msec: 10,
+                price: 100.0,
+                size: 1.0,
+            },
+            Trade {
+                unixtime_msec: 20,
+                price: 102.0,
+                size: 0.5,
+            },
+            Trade {
+                unixtime_msec: MINUTE - 1,
+                price: 99.0,
+                size: 0.25,
+            },
+        ];
+
+        let expected = vec![
+            Kline {
+                unixtime_msec: 0,
+                o: 100.0,
+                h: 102.0,
+                l: 99.0,
+                c: 99.0,
+                v: 1.75,
+                extra: KlineExtra::default(),
+            },
+            Kline {
+                unixtime_msec: 2 * MINUTE,
+                o: 105.0,
+                h: 105.0,
+                l: 105.0,
+                c: 105.0,
+                v: 3.0,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(
+            aggregate_trades(trades, &DurationAndUnit(1, TermUnit::Min)),
+            expected
+        );
+    }
+
+    #[rstest]
+    // Only 5 candles since the listing at minute 0
+    #[case((0..5).collect(), 5 * MINUTE, None, 5)]
+    // A gap of 97 minutes, longer than several doubled windows
+    #[case((0..3).chain(100..105).collect(), 105 * MINUTE, None, 8)]
+    // Nothing is served before minute 15, although older candles exist
+    #[case((0..20).collect(), 20 * MINUTE, Some(15 * MINUTE), 5)]
+    fn test_collect_latest_klines_no_more_history(
+        #[case] minutes: Vec<i64>,
+        #[case] term_end: i64,
+        #[case] history_start: Option<i64>,
+        #[case] expected: usize,
+    ) {
+        let mut dummy = Dummy {
+            unixtimes: minutes.iter().map(|m| m * MINUTE).collect(),
+            page_order: Order::Asc,
+            term: (0, 0),
+            history_start,
+            window: None,
+        };
+        let mut args = ParsedArgs {
+            term_end: Some(term_end),
+            count: Some(10),
+            interval: DurationAndUnit(1, TermUnit::Min),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            dummy.collect_latest_klines(&mut args, 10).unwrap().len(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(Order::Asc, DurationAndUnit(15, TermUnit::Min))]
+    #[case(Order::Desc, DurationAndUnit(15, TermUnit::Min))]
+    // Longer than the gap between rates, which must not be skipped at the edges of pages
+    #[case(Order::Asc, DurationAndUnit(1, TermUnit::Day))]
+    #[case(Order::Desc, DurationAndUnit(1, TermUnit::Day))]
+    fn test_collect_funding(#[case] page_order: Order, #[case] interval: DurationAndUnit) {
+        // Every 8 hours for 3 days, paged through by 3 records
+        const HOUR: i64 = 60 * MINUTE;
+        let mut dummy = Dummy {
+            unixtimes: (0..10).map(|n| n * 8 * HOUR).collect(),
+            page_order,
+            term: (0, 0),
+            history_start: None,
+            window: None,
+        };
+        let mut args = ParsedArgs {
+            type_: MarketType::Perpetual,
+            kind: DataKind::Funding,
+            term_start: Some(0),
+            term_end: Some(72 * HOUR),
+            interval,
+            ..Default::default()
+        };
+
+        let result: Vec<i64> = Order::sort(dummy.collect_funding(&mut args).unwrap(), &Order::Asc)
+            .iter()
+            .map(|funding| funding.unixtime_msec / HOUR)
+            .collect();
+
+        assert_eq!(result, (0..10).map(|n| n * 8).collect::<Vec<_>>());
+    }
+
+    #[rstest]
+    // Spot, with the exact quote volume or the close price
+    #[case(NativeVolume::Base, Some(2100.0), VolumeUnit::Base, 2.0)]
+    #[case(NativeVolume::Base, Some(2100.0), VolumeUnit::Quote, 2100.0)]
+    #[case(NativeVolume::Base, None, VolumeUnit::Quote, 2000.0)]
+    // Linear contracts of 0.01 coin
+    #[case(NativeVolume::Contracts { size: 0.01, inverse: false }, None, VolumeUnit::Base, 0.02)]
+    #[case(NativeVolume::Contracts { size: 0.01, inverse: false }, None, VolumeUnit::Quote, 20.0)]
+    #[case(NativeVolume::Contracts { size: 0.01, inverse: false }, None, VolumeUnit::Contracts, 2.0)]
+    // Inverse contracts of 100 dollars
+    #[case(NativeVolume::Contracts { size: 100.0, inverse: true }, None, VolumeUnit::Base, 0.2)]
+    #[case(NativeVolume::Contracts { size: 100.0, inverse: true }, None, VolumeUnit::Quote, 200.0)]
+    fn test_native_volume_convert(
+        #[case] native: NativeVolume,
+        #[case] quote_volume: Option<f64>,
+        #[case] unit: VolumeUnit,
+        #[case] expected: f64,
+    ) {
+        let kline = Kline {
+            unixtime_msec: 0,
+            o: 1000.0,
+            h: 1000.0,
+            l: 1000.0,
+            c: 1000.0,
+            v: 2.0,
+            extra: KlineExtra {
+                quote_volume,
+                ..Default::default()
+            },
+        };
+
+        assert!((native.convert(&kline, &unit) - expected).abs() < 1e-9);
+    }
+
+    fn args_of_minute(type_: MarketType, term_start: i64, term_end: i64) -> ParsedArgs {
+        ParsedArgs {
+            type_,
+            term_start: Some(term_start),
+            term_end: Some(term_end),
+            interval: DurationAndUnit(1, TermUnit::Min),
+            pick: vec![Pick::T, Pick::IsClosed],
+            clock: Clock::Fixed(Utc.timestamp_millis_opt(term_end).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    fn opening_times<T: Retrieve>(mut exchange: T, type_: MarketType, response: &str) -> Vec<i64> {
+        // 2023-11-01T00:00:00Z to 2023-11-01T00:10:00Z
+        exchange
+            .prepare(&args_of_minute(type_, 1698796800000, 1698797400000))
+            .unwrap();
+        exchange
+            .parse_as_kline(response.to_string())
+            .iter()
+            .map(|kline| kline.unixtime_msec)
+            .collect()
+    }
+
+    #[test]
+    fn test_same_candle_across_exchanges() {
+        // The candle of 2023-11-01T00:00:00Z, as each exchange returns it
+        let expected = vec![1698796800000];
+
+        assert_eq!(
+            opening_times(
+                Binance::new(),
+                MarketType::Spot,
+                r#"[[1698796800000, "34639.10", "34650.00", "34630.00", "34640.00", "12.3", 1698796859999, "426071.2", 812, "6.1", "211300.5", "0"]]"#,
+            ),
+            expected
+        );
+        assert_eq!(
+            opening_times(
+                Bybit::new(),
+                MarketType::Spot,
+                r#"{"retCode": 0, "retMsg": "OK", "result": {"category": "spot", "symbol": "BTCUSDT", "list": [["1698796800000", "34639.1", "34650", "34630", "34640", "12.3", "426071.2"]]}, "retExtInfo": {}, "time": 1698796900000}"#,
+            ),
+            expected
+        );
+        assert_eq!(
+            opening_times(
+                Okx::new(),
+                MarketType::Spot,
+                r#"{"code": "0", "msg": "", "data": [["1698796800000", "34639.1", "34650", "34630", "34640", "12.3", "426071.2", "426071.2", "1"]]}"#,
+            ),
+            expected
+        );
+        assert_eq!(
+            opening_times(
+                Bitbank::new(),
+                MarketType::Spot,
+                r#"{"success": 1, "data": {"candlestick": [{"type": "1min", "ohlcv": [["5210000", "5211000", "5209000", "5210500", "0.5", 1698796800000]]}], "timestamp": 1698796900000}}"#,
+            ),
+            expected
+        );
+        // Stamped at the end of the bucket
+        assert_eq!(
+            opening_times(
+                Bitmex::new(),
+                MarketType::Perpetual,
+                r#"[{"timestamp": "2023-11-01T00:01:00.000Z", "symbol": "XBTUSD", "open": 34639.5, "high": 34650, "low": 34630, "close": 34640, "trades": 120, "volume": 410000, "vwap": 34641.2, "foreignNotional": 410000}]"#,
+            ),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(TimestampAt::Open, DurationAndUnit(1, TermUnit::Min), vec![0, MINUTE], MINUTE + 1, vec![(0, true), (MINUTE, false)])]
+    #[case(TimestampAt::Close, DurationAndUnit(1, TermUnit::Min), vec![0, MINUTE], MINUTE + 1, vec![(MINUTE, true), (2 * MINUTE, false)])]
+    // Closed at the 1st of the next month, retrieved on 2023-06-15
+    #[case(TimestampAt::Close, DurationAndUnit(1, TermUnit::Month), vec![MAY, JUNE], 1686787200000, vec![(JUNE, true), (1688169600000, false)])]
+    fn test_retrieve_timestamp_at(
+        #[case] timestamp_at: TimestampAt,
+        #[case] interval: DurationAndUnit,
+        #[case] unixtimes: Vec<i64>,
+        #[case] now: i64,
+        #[case] expected: Vec<(i64, bool)>,
+    ) {
+        // The last candle is still forming
+        let mut dummy = Dummy {
+            unixtimes: unixtimes.clone(),
+            page_order: Order::Asc,
+            term: (0, 0),
+            history_start: None,
+            window: None,
+        };
+        let mut args = args_of_minute(MarketType::Spot, unixtimes[0], now);
+        args.interval = interval;
+        args.timestamp_at = timestamp_at;
+
+        let expected: Vec<Row> = expected
+            .into_iter()
+            .map(|(t, is_closed)| {
+                vec![
+                    [(Pick::T, KlineNumber::Unixtime(t))].into_iter().collect(),
+                    [(Pick::IsClosed, KlineNumber::Flag(is_closed))]
+                        .into_iter()
+                        .collect(),
+                ]
+            })
+            .collect();
+
+        assert_eq!(dummy.retrieve(&mut args).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(Unclosed::Include, vec![(APRIL, true), (MAY, true), (JUNE, false)])]
+    #[case(Unclosed::Exclude, vec![(APRIL, true), (MAY, true)])]
+    fn test_retrieve_unclosed_month(
+        #[case] unclosed: Unclosed,
+        #[case] expected: Vec<(i64, bool)>,
+    ) {
+        // Retrieved on 2023-06-15
+        let now = 1686787200000;
+
+        let mut dummy = Dummy {
+            unixtimes: vec![APRIL, MAY, JUNE],
+            page_order: Order::Asc,
+            term: (0, 0),
+            history_start: None,
+            window: None,
+        };
+        let mut args = args_of_minute(MarketType::Spot, APRIL, now);
+        args.interval = DurationAndUnit(1, TermUnit::Month);
+        args.unclosed = unclosed;
+
+        let expected: Vec<Row> = expected
+            .into_iter()
+            .map(|(t, is_closed)| {
+                vec![
+                    [(Pick::T, KlineNumber::Unixtime(t))].into_iter().collect(),
+                    [(Pick::IsClosed, KlineNumber::Flag(is_closed))]
+                        .into_iter()
+                        .collect(),
+                ]
+            })
+            .collect();
+
+        assert_eq!(dummy.retrieve(&mut args).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(PriceType::Last, KlineNumber::Ohlcv(0.0))]
+    #[case(PriceType::Mark, KlineNumber::Null)]
+    #[case(PriceType::Premium, KlineNumber::Null)]
+    fn test_retrieve_price_type_volume(
+        #[case] price_type: PriceType,
+        #[case] expected: KlineNumber,
+    ) {
+        let mut dummy = Dummy {
+            unixtimes: vec![0],
+            page_order: Order::Asc,
+            term: (0, 0),
+            history_start: None,
+            window: None,
+        };
+        let mut args = args_of_minute(MarketType::Perpetual, 0, MINUTE);
+        args.price_type = price_type;
+        args.pick = vec![Pick::C, Pick::V];
+
+        let expected: Vec<Row> = vec![vec![
+            [(Pick::C, KlineNumber::Ohlcv(0.0))].into_iter().collect(),
+            [(Pick::V, expected)].into_iter().collect(),
+        ]];
+
+        assert_eq!(dummy.retrieve(&mut args).unwrap(), expected);
+    }
+
+    #[rstest]
+    // Delisted, but still served
+    #[case(Ok(vec![vec![[(Pick::C, KlineNumber::Ohlcv(0.0))].into_iter().collect()]]), true)]
+    // Not served either, like a typo
+    #[case(Ok(vec![]), false)]
+    #[case(Err(ExchangeResponseError::symbol()), false)]
+    fn test_settle_unlisted(#[case] result: Result<Vec<Row>, Error>, #[case] expected: bool) {
+        let unlisted = ExchangeResponseError::symbol_of(&["BTC/USDT".to_string()]);
+
+        match settle_unlisted(result, unlisted, "BTC/USDX") {
+            Ok(rows) => assert!(expected && !rows.is_empty()),
+            Err(e) => assert!(!expected && e.to_string().contains("Did you mean BTC/USDT?")),
+        }
+    }
+
+    #[rstest]
+    #[case(None, None, true)]
+    #[case(Some("btc"), None, true)]
+    #[case(Some("BTC"), Some("USDT"), false)]
+    #[case(None, Some("USD"), true)]
+    fn test_listing(
+        #[case] base: Option<&str>,
+        #[case] quote: Option<&str>,
+        #[case] expected: bool,
+    ) {
+        // Named after ISO 4217 on the exchange
+        let listing = Listing::new("XBT", "usd", "XBTUSD", BTC_AS_XBT);
+        assert_eq!(listing.symbol(), "BTC/USD");
+
+        let (base, quote) = (base.map(String::from), quote.map(String::from));
+        assert_eq!(listing.is_of(&base, &quote), expected);
+    }
+
+    #[rstest]
+    #[case(Exchange::Bitmex(Bitmex::new()), "BTC/USD", true)]
+    #[case(Exchange::Bitmex(Bitmex::new()), "XBT/USD", true)]
+    #[case(Exchange::Bitmex(Bitmex::new()), "btc/usd", true)]
+    #[case(Exchange::Bitmex(Bitmex::new()), "BTC/USDT", false)]
+    #[case(Exchange::Hyperliquid(Hyperliquid::new()), "ETH/USD", true)]
+    #[case(Exchange::Kraken(Kraken::new()), "LUNA/USD", true)]
+    #[case(Exchange::Kraken(Kraken::new()), "LUNC/USD", false)]
+    #[case(Exchange::Binance(Binance::new()), "XBT/USD", false)]
+    #[case(Exchange::Binance(Binance::new()), "BTCUSD", false)]
+    fn test_is_listed(#[case] exchange: Exchange, #[case] symbol: &str, #[case] expected: bool) {
+        let listings = vec![
+            Listing::new("XBT", "USD", "XBTUSD", BTC_AS_XBT),
+            Listing::new("ETH", "USDC", "ETH", &[]),
+            Listing::new("LUNA2", "USD", "LUNA2USD", ALIASES),
+        ];
+
+        assert_eq!(exchange.is_listed(symbol, &listings), expected);
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::{anyhow, Error};
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
 use rand::Rng;
 use regex::Regex;
 use reqwest::blocking::Client;
@@ -7,15 +8,41 @@ use serde::Deserialize;
 use crate::{args::*, error::*, exchange::*, unit::*};
 
 const ENDPOINT_SPOT: &str = "https://data-api.binance.vision/api/v3/klines";
-const ENDPOINT_PERPETUAL: &str = "https://fapi.binance.com/fapi/v1/klines";
+/// USD-M (linear) contracts
+const ENDPOINT_BASE_LINEAR: &str = "https://fapi.binance.com/fapi/v1";
+/// COIN-M (inverse) contracts
+const ENDPOINT_BASE_INVERSE: &str = "https://dapi.binance.com/dapi/v1";
+const ENDPOINT_OPEN_INTEREST_LINEAR: &str =
+    "https://fapi.binance.com/futures/data/openInterestHist";
+const ENDPOINT_OPEN_INTEREST_INVERSE: &str =
+    "https://dapi.binance.com/futures/data/openInterestHist";
+const ENDPOINT_TIME_SPOT: &str = "https://data-api.binance.vision/api/v3/time";
+const ENDPOINT_EXCHANGE_INFO_SPOT: &str = "https://data-api.binance.vision/api/v3/exchangeInfo";
+const ENDPOINT_TIME_PERPETUAL: &str = "https://fapi.binance.com/fapi/v1/time";
 const LIMIT_SPOT: i32 = 1000;
 const LIMIT_PERPETUAL: i32 = 1500;
+const LIMIT_FUNDING: i32 = 1000;
+const LIMIT_OPEN_INTEREST: i32 = 500;
+/// Older open interest is not kept
+const LOOK_BACK_OPEN_INTEREST_DAYS: i64 = 30;
+/// COIN-M rejects `startTime` and `endTime` which are more than this apart
+const MAX_TERM_INVERSE_DAYS: i64 = 200;
+/// Quarterly contracts are delivered at this time (UTC) on the last Friday of March, June, September and December
+const DELIVERY_HOUR: i64 = 8;
 
 #[derive(Debug, Clone)]
 pub struct Binance {
     params: Vec<(String, String)>,
     market_type: MarketType,
     endpoint: String,
+    contract: Contract,
+    price_type: PriceType,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnTime {
+    #[serde(alias = "serverTime")]
+    server_time: i64,
 }
 
 #[derive(Deserialize)]
@@ -24,15 +51,174 @@ struct ResponseOnError {
     msg: String,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FundingRate {
+    funding_time: i64,
+    funding_rate: String,
+    /// Empty for old records, and not returned by COIN-M
+    #[serde(default)]
+    mark_price: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenInterestHist {
+    /// In contracts on COIN-M
+    sum_open_interest: String,
+    /// In the quote currency, or in the base currency on COIN-M
+    sum_open_interest_value: String,
+    /// A number, but documented as a string on USD-M
+    timestamp: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+// Generic over the fields of `symbols` to read
+struct ExchangeInfo<T> {
+    symbols: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SymbolInExchangeInfo {
+    pair: String,
+    /// In USD, only on COIN-M
+    contract_size: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SymbolToList {
+    symbol: String,
+    /// Futures only, like `BTCUSDT` of `BTCUSDT_231229`
+    #[serde(default)]
+    pair: String,
+    /// Futures only, `PERPETUAL`, `CURRENT_QUARTER` and so on
+    #[serde(default)]
+    contract_type: String,
+    #[serde(default)]
+    delivery_date: i64,
+    /// Named `contractStatus` on COIN-M
+    #[serde(alias = "contractStatus")]
+    status: String,
+    base_asset: String,
+    quote_asset: String,
+}
+
 impl Binance {
     pub fn new() -> Self {
         Binance {
             params: Vec::new(),
             market_type: MarketType::Spot,
             endpoint: String::new(),
+            contract: Contract::default(),
+            price_type: PriceType::Last,
+        }
+    }
+
+    fn is_inverse(&self) -> bool {
+        matches!(self.contract.margin, Some(Margin::Inverse))
+    }
+
+    /// Delivery time of the quarterly contract that expires in the month of `date`.
+    fn delivery_time(date: &NaiveDate) -> Option<i64> {
+        if !date.month().is_multiple_of(3) {
+            return None;
+        }
+
+        let (year, month) = match date.month() {
+            12 => (date.year() + 1, 1),
+            month => (date.year(), month + 1),
+        };
+        let mut last_friday = NaiveDate::from_ymd_opt(year, month, 1)? - Duration::days(1);
+        while last_friday.weekday() != Weekday::Fri {
+            last_friday -= Duration::days(1);
+        }
+
+        Some(last_friday.and_hms_opt(0, 0, 0)?.timestamp_millis() + DELIVERY_HOUR * 60 * 60 * 1000)
+    }
+
+    /// Quarterly contracts cannot be specified by the symbol in `continuousKlines`,
+    /// but only as `CURRENT_QUARTER` or `NEXT_QUARTER` at the time of each candle.
+    /// So the period is cut at the previous delivery, before which the contract was the next quarter.
+    fn quarter_window(
+        expiry: &NaiveDate,
+        term_start: i64,
+        term_end: i64,
+    ) -> Result<(&'static str, i64, i64), Error> {
+        let expiry_time = expiry.and_hms_opt(DELIVERY_HOUR as u32, 0, 0).unwrap();
+        let delivery = Self::delivery_time(expiry)
+            .filter(|delivery| *delivery == expiry_time.timestamp_millis())
+            .ok_or(anyhow!(
+                "Binance quarterly futures expire on the last Friday of March, June, September and December."
+            ))?;
+
+        let first_day = expiry.with_day(1).unwrap();
+        let previous = Self::delivery_time(&(first_day - Months::new(3))).unwrap();
+        let before_previous = Self::delivery_time(&(first_day - Months::new(6))).unwrap();
+
+        // Before it was listed and after it was delivered, the candles are of other contracts
+        let (term_start, term_end) = (term_start.max(before_previous), term_end.min(delivery - 1));
+
+        if term_end < term_start {
+            // Out of the period of the contract, request a point where no candle opens
+            // so that the response is empty
+            return Ok(("CURRENT_QUARTER", delivery - 1, delivery - 1));
+        }
+
+        match term_start < previous {
+            true => Ok(("NEXT_QUARTER", term_start, term_end.min(previous - 1))),
+            false => Ok(("CURRENT_QUARTER", term_start, term_end)),
         }
     }
 
+    /// Numbers in klines are strings, `None` if the column is missing.
+    fn number_at(raw: &[serde_json::Value], index: usize) -> Option<f64> {
+        raw.get(index)
+            .and_then(|value| value.as_str())
+            .and_then(|value| value.parse::<f64>().ok())
+    }
+
+    /// Contracts of the same pair (perpetual and quarterly) have the same size on COIN-M.
+    fn contract_size(data: &str, pair: &str) -> Result<f64, Error> {
+        serde_json::from_str::<ExchangeInfo<SymbolInExchangeInfo>>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?
+            .symbols
+            .iter()
+            .find(|symbol| symbol.pair == pair)
+            .map(|symbol| symbol.contract_size)
+            .ok_or(ExchangeResponseError::symbol())
+    }
+
+    /// Symbols in `exchangeInfo` which are trading, `self` is of the market type and the margin to list.
+    fn parse_as_listings(&self, data: &str) -> Result<Vec<Listing>, Error> {
+        let symbols = serde_json::from_str::<ExchangeInfo<SymbolToList>>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?
+            .symbols;
+
+        Ok(symbols
+            .iter()
+            .filter(|symbol| symbol.status == "TRADING")
+            .filter_map(|symbol| {
+                let listing =
+                    Listing::new(&symbol.base_asset, &symbol.quote_asset, &symbol.symbol, &[]);
+
+                // Futures are requested with the pair and the expiry instead of the symbol
+                let (name, listing) = match (&self.market_type, symbol.contract_type.as_str()) {
+                    (MarketType::Spot, _) | (MarketType::Perpetual, "PERPETUAL") => {
+                        (&symbol.symbol, listing)
+                    }
+                    (MarketType::Futures, "CURRENT_QUARTER" | "NEXT_QUARTER") => {
+                        (&symbol.pair, listing.expiring_at(symbol.delivery_date))
+                    }
+                    _ => return None,
+                };
+
+                (self.fit_symbol_to_req(&listing.symbol()).ok()? == *name).then_some(listing)
+            })
+            .collect())
+    }
+
     #[allow(dead_code)]
     fn load_balancing(&self) -> Self {
         // No test written
@@ -61,38 +247,168 @@ impl Binance {
 
 impl Retrieve for Binance {
     fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        if let MarketType::Option = args.type_ {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        self.market_type = args.type_.clone();
+        self.contract = args.contract.clone();
+        self.price_type = args.price_type.clone();
+
+        let (mut term_start, mut term_end) = (args.term_start.unwrap(), args.term_end.unwrap());
+        if self.is_inverse() {
+            term_end = term_end.min(term_start + MAX_TERM_INVERSE_DAYS * 24 * 60 * 60 * 1000);
+        }
+
+        let symbol = self.fit_symbol_to_req(&args.symbol)?;
         self.params = [
-            ("symbol".to_string(), self.fit_symbol_to_req(&args.symbol)?),
             (
                 "interval".to_string(),
                 self.fit_interval_to_req(&args.interval)?,
             ),
-            (
-                "startTime".to_string(),
-                args.term_start.unwrap().to_string(),
-            ),
-            ("endTime".to_string(), args.term_end.unwrap().to_string()),
             (
                 "limit".to_string(),
                 match args.type_ {
                     MarketType::Spot => LIMIT_SPOT.to_string(),
-                    MarketType::Perpetual => LIMIT_PERPETUAL.to_string(),
+                    _ => LIMIT_PERPETUAL.to_string(),
                 },
             ),
         ]
         .to_vec();
 
+        if args.price_type != PriceType::Last && !matches!(args.type_, MarketType::Perpetual) {
+            return Err(anyhow!(
+                "Binance supports `--price-type` other than `last` only with `--type perpetual`."
+            ));
+        }
+
+        let base = match self.is_inverse() {
+            true => ENDPOINT_BASE_INVERSE,
+            false => ENDPOINT_BASE_LINEAR,
+        };
+
         match args.type_ {
             MarketType::Spot => {
-                self.market_type = MarketType::Spot;
+                self.params.push(("symbol".to_string(), symbol));
                 self.endpoint = ENDPOINT_SPOT.to_string()
             }
             MarketType::Perpetual => {
-                self.market_type = MarketType::Perpetual;
-                self.endpoint = ENDPOINT_PERPETUAL.to_string()
+                let path = match args.price_type {
+                    PriceType::Last => "klines",
+                    PriceType::Mark => "markPriceKlines",
+                    PriceType::Index => "indexPriceKlines",
+                    PriceType::Premium => "premiumIndexKlines",
+                };
+
+                match args.price_type {
+                    // The index is of the pair, not of the contract (`BTCUSD`, not `BTCUSD_PERP`)
+                    PriceType::Index => self.params.push((
+                        "pair".to_string(),
+                        symbol.trim_end_matches("_PERP").to_string(),
+                    )),
+                    _ => self.params.push(("symbol".to_string(), symbol)),
+                }
+                self.endpoint = format!("{}/{}", base, path);
+            }
+            MarketType::Futures => {
+                let contract_type;
+                (contract_type, term_start, term_end) =
+                    Self::quarter_window(&args.contract.expiry.unwrap(), term_start, term_end)?;
+
+                self.params.push(("pair".to_string(), symbol));
+                self.params
+                    .push(("contractType".to_string(), contract_type.to_string()));
+                self.endpoint = format!("{}/continuousKlines", base);
             }
+            _ => unreachable!(/* Checked above */),
         }
 
+        self.params
+            .push(("startTime".to_string(), term_start.to_string()));
+        self.params
+            .push(("endTime".to_string(), term_end.to_string()));
+
+        Ok(())
+    }
+
+    fn prepare_funding(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        self.market_type = args.type_.clone();
+        self.contract = args.contract.clone();
+
+        self.endpoint = match self.is_inverse() {
+            true => format!("{}/fundingRate", ENDPOINT_BASE_INVERSE),
+            false => format!("{}/fundingRate", ENDPOINT_BASE_LINEAR),
+        };
+        self.params = [
+            ("symbol".to_string(), self.fit_symbol_to_req(&args.symbol)?),
+            (
+                "startTime".to_string(),
+                args.term_start.unwrap().to_string(),
+            ),
+            ("endTime".to_string(), args.term_end.unwrap().to_string()),
+            ("limit".to_string(), LIMIT_FUNDING.to_string()),
+        ]
+        .to_vec();
+
+        Ok(())
+    }
+
+    fn prepare_open_interest(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        self.market_type = args.type_.clone();
+        self.contract = args.contract.clone();
+
+        let period = match (args.interval.0, &args.interval.1) {
+            (5 | 15 | 30, TermUnit::Min)
+            | (1 | 2 | 4 | 6 | 12, TermUnit::Hour)
+            | (1, TermUnit::Day) => self.fit_interval_to_req(&args.interval)?,
+            _ => {
+                return Err(ExchangeResponseError::interval_of(
+                    &[
+                        "5min", "15min", "30min", "1hour", "2hour", "4hour", "6hour", "12hour",
+                        "1day",
+                    ]
+                    .map(String::from),
+                ))
+            }
+        };
+
+        // One interval of tolerance for `--past --range 30day`, whose start is fixed a moment before
+        let oldest = args.now().timestamp_millis()
+            - Duration::days(LOOK_BACK_OPEN_INTEREST_DAYS).num_milliseconds();
+        if args.term_start.unwrap() + args.interval.to_msec() < oldest {
+            return Err(ExchangeResponseError::look_back(
+                LOOK_BACK_OPEN_INTEREST_DAYS,
+            ));
+        }
+
+        let symbol = self.fit_symbol_to_req(&args.symbol)?;
+        self.params = match self.is_inverse() {
+            true => {
+                self.endpoint = ENDPOINT_OPEN_INTEREST_INVERSE.to_string();
+                [
+                    (
+                        "pair".to_string(),
+                        symbol.trim_end_matches("_PERP").to_string(),
+                    ),
+                    ("contractType".to_string(), "PERPETUAL".to_string()),
+                ]
+                .to_vec()
+            }
+            false => {
+                self.endpoint = ENDPOINT_OPEN_INTEREST_LINEAR.to_string();
+                [("symbol".to_string(), symbol)].to_vec()
+            }
+        };
+        self.params.extend([
+            ("period".to_string(), period),
+            (
+                "startTime".to_string(),
+                args.term_start.unwrap().max(oldest).to_string(),
+            ),
+            ("endTime".to_string(), args.term_end.unwrap().to_string()),
+            ("limit".to_string(), LIMIT_OPEN_INTEREST.to_string()),
+        ]);
+
         Ok(())
     }
 
@@ -120,12 +436,75 @@ impl Retrieve for Binance {
         Ok(res)
     }
 
+    fn server_time(&self, client: &Client, market_type: &MarketType) -> Result<Option<i64>, Error> {
+        let endpoint = match market_type {
+            MarketType::Spot => ENDPOINT_TIME_SPOT,
+            // Not supported in `prepare()` other than spot and perpetual, but the time is the same
+            _ => ENDPOINT_TIME_PERPETUAL,
+        };
+
+        let res = client.get(endpoint).send()?.json::<ResponseOnTime>()?;
+
+        Ok(Some(res.server_time))
+    }
+
+    fn native_volume(&self, client: &Client, args: &ParsedArgs) -> Result<NativeVolume, Error> {
+        match (&args.type_, self.is_inverse()) {
+            (MarketType::Spot, _) => Ok(NativeVolume::Base),
+            // Quantities of USD-M are in the base currency, as if a contract were one coin
+            (_, false) => Ok(NativeVolume::Contracts {
+                size: 1.0,
+                inverse: false,
+            }),
+            (_, true) => {
+                let res = client
+                    .get(format!("{}/exchangeInfo", ENDPOINT_BASE_INVERSE))
+                    .send()?
+                    .text()?;
+                let symbol = self.fit_symbol_to_req(&args.symbol)?;
+
+                Ok(NativeVolume::Contracts {
+                    size: Self::contract_size(&res, symbol.trim_end_matches("_PERP"))?,
+                    inverse: true,
+                })
+            }
+        }
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        let binance = Binance {
+            market_type: market_type.clone(),
+            contract: contract.clone(),
+            ..Binance::new()
+        };
+
+        let endpoint = match (market_type, binance.is_inverse()) {
+            (MarketType::Spot, _) => ENDPOINT_EXCHANGE_INFO_SPOT.to_string(),
+            (MarketType::Option, _) => return Err(ExchangeResponseError::no_support_type()),
+            (_, true) => format!("{}/exchangeInfo", ENDPOINT_BASE_INVERSE),
+            (_, false) => format!("{}/exchangeInfo", ENDPOINT_BASE_LINEAR),
+        };
+        let res = client.get(endpoint).send()?.text()?;
+
+        binance.parse_as_listings(&res)
+    }
+
     fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
         let re = Regex::new(r"^(.*?)/(.*?)$").unwrap();
         let matches = re.captures(symbol).ok_or(anyhow!(
             "The symbol pair provided is incorrectly formatted."
         ))?;
-        Ok(format!("{}{}", &matches[1], &matches[2]))
+
+        // COIN-M perpetuals have the suffix like `BTCUSD_PERP`, while the pairs of futures do not
+        match (&self.market_type, self.is_inverse()) {
+            (MarketType::Perpetual, true) => Ok(format!("{}{}_PERP", &matches[1], &matches[2])),
+            _ => Ok(format!("{}{}", &matches[1], &matches[2])),
+        }
     }
 
     fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
@@ -151,6 +530,44 @@ impl Retrieve for Binance {
                 l: raw[3].as_str().unwrap().to_owned().parse::<f64>().unwrap(),
                 c: raw[4].as_str().unwrap().to_owned().parse::<f64>().unwrap(),
                 v: raw[5].as_str().unwrap().to_owned().parse::<f64>().unwrap(),
+                // Filled with zeros other than the last price
+                extra: match self.price_type {
+                    PriceType::Last => KlineExtra {
+                        quote_volume: Self::number_at(raw, 7),
+                        trades: raw.get(8).and_then(|trades| trades.as_u64()),
+                        taker_buy_volume: Self::number_at(raw, 9),
+                        taker_buy_quote_volume: Self::number_at(raw, 10),
+                        vwap: None,
+                    },
+                    _ => KlineExtra::default(),
+                },
+            })
+            .collect()
+    }
+
+    fn parse_as_funding(&self, data: String) -> Vec<Funding> {
+        serde_json::from_str::<Vec<FundingRate>>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .iter()
+            .map(|funding| Funding {
+                unixtime_msec: funding.funding_time,
+                rate: funding.funding_rate.parse::<f64>().unwrap(),
+                mark_price: funding.mark_price.parse::<f64>().ok(),
+            })
+            .collect()
+    }
+
+    fn parse_as_open_interest(&self, data: String) -> Vec<OpenInterest> {
+        serde_json::from_str::<Vec<OpenInterestHist>>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .iter()
+            .map(|hist| OpenInterest {
+                unixtime_msec: match &hist.timestamp {
+                    serde_json::Value::String(timestamp) => timestamp.parse::<i64>().unwrap(),
+                    timestamp => timestamp.as_i64().unwrap(),
+                },
+                amount: hist.sum_open_interest.parse::<f64>().unwrap(),
+                value: hist.sum_open_interest_value.parse::<f64>().ok(),
             })
             .collect()
     }
@@ -164,6 +581,7 @@ mod tests {
     use serde_json::json;
 
     use super::*;
+    use crate::{clock::*, pick::*};
 
     #[rstest]
     #[case("ETH/BNB", "ETHBNB".to_string())]
@@ -174,6 +592,60 @@ mod tests {
         assert_eq!(binance.fit_symbol_to_req(input).unwrap(), expected)
     }
 
+    #[rstest]
+    #[case(MarketType::Perpetual, None, "BTCUSDT".to_string())]
+    #[case(MarketType::Perpetual, Some(Margin::Inverse), "BTCUSD_PERP".to_string())]
+    #[case(MarketType::Futures, Some(Margin::Inverse), "BTCUSD".to_string())]
+    fn test_fit_symbol_to_req_derivatives(
+        #[case] market_type: MarketType,
+        #[case] margin: Option<Margin>,
+        #[case] expected: String,
+    ) {
+        let mut binance = Binance::new();
+        binance.market_type = market_type;
+        binance.contract.margin = margin;
+        let symbol = match binance.is_inverse() {
+            true => "BTC/USD",
+            false => "BTC/USDT",
+        };
+        assert_eq!(binance.fit_symbol_to_req(symbol).unwrap(), expected)
+    }
+
+    #[rstest]
+    #[case(NaiveDate::from_ymd_opt(2023, 12, 29).unwrap(), Some(1703836800000))]
+    #[case(NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(), Some(1711699200000))]
+    #[case(NaiveDate::from_ymd_opt(2024, 2, 23).unwrap(), None)]
+    fn test_delivery_time(#[case] input: NaiveDate, #[case] expected: Option<i64>) {
+        assert_eq!(Binance::delivery_time(&input), expected);
+    }
+
+    #[rstest]
+    // Whole of the period as the current quarter (2023-09-29 08:00 ~ 2023-12-29 08:00)
+    #[case(1698796800000, 1701388800000, Some(("CURRENT_QUARTER", 1698796800000, 1701388800000)))]
+    // Cut at the previous delivery, before which it was the next quarter
+    #[case(1693526400000, 1698796800000, Some(("NEXT_QUARTER", 1693526400000, 1695974399999)))]
+    // Not before it was listed (2023-06-30 08:00) nor after it was delivered
+    #[case(1685577600000, 1704067200000, Some(("NEXT_QUARTER", 1688112000000, 1695974399999)))]
+    #[case(1701388800000, 1704067200000, Some(("CURRENT_QUARTER", 1701388800000, 1703836799999)))]
+    #[case(1703840400000, 1704067200000, Some(("CURRENT_QUARTER", 1703836799999, 1703836799999)))]
+    fn test_quarter_window(
+        #[case] term_start: i64,
+        #[case] term_end: i64,
+        #[case] expected: Option<(&str, i64, i64)>,
+    ) {
+        let expiry = NaiveDate::from_ymd_opt(2023, 12, 29).unwrap();
+        assert_eq!(
+            Binance::quarter_window(&expiry, term_start, term_end).ok(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_quarter_window_not_delivery_date() {
+        let expiry = NaiveDate::from_ymd_opt(2023, 12, 22).unwrap();
+        assert!(Binance::quarter_window(&expiry, 1698796800000, 1701388800000).is_err());
+    }
+
     #[rstest]
     #[case("15min", "15m".to_string())]
     #[case("1month", "1M".to_string())]
@@ -224,6 +696,7 @@ mod tests {
                 l: 0.00001363,
                 c: 0.00001420,
                 v: 592238.0,
+                extra: KlineExtra::default(),
             },
             Kline {
                 unixtime_msec: 1619563260000,
@@ -232,9 +705,315 @@ mod tests {
                 l: 0.00001394,
                 c: 0.00001410,
                 v: 428141.0,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_funding() {
+        let binance = Binance::new();
+
+        let input = r#"
+        [
+            {
+                "symbol": "BTCUSDT",
+                "fundingTime": 1698768000000,
+                "fundingRate": "0.00010000",
+                "markPrice": "34639.12000000"
+            },
+            {
+                "symbol": "BTCUSDT",
+                "fundingTime": 1698796800000,
+                "fundingRate": "0.00007343",
+                "markPrice": ""
+            }
+        ]"#
+        .to_string();
+        let result = binance.parse_as_funding(input);
+        let expected = vec![
+            Funding {
+                unixtime_msec: 1698768000000,
+                rate: 0.0001,
+                mark_price: Some(34639.12),
+            },
+            Funding {
+                unixtime_msec: 1698796800000,
+                rate: 0.00007343,
+                mark_price: None,
             },
         ];
 
         assert_eq!(result, expected);
     }
+
+    fn args_open_interest(term_start: i64, term_end: i64) -> ParsedArgs {
+        ParsedArgs {
+            type_: MarketType::Perpetual,
+            kind: DataKind::OpenInterest,
+            term_start: Some(term_start),
+            term_end: Some(term_end),
+            interval: DurationAndUnit::from_str("1hour").unwrap(),
+            pick: vec![Pick::T, Pick::Oi],
+            // 2023-11-01T00:30:00Z
+            clock: Clock::Fixed(Utc.timestamp_millis_opt(1698798600000).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    // 2023-10-02T00:00:00Z, within 30 days
+    #[case(1696204800000, true)]
+    // 2023-09-01T00:00:00Z
+    #[case(1693526400000, false)]
+    fn test_prepare_open_interest_look_back(#[case] term_start: i64, #[case] expected: bool) {
+        let mut binance = Binance::new();
+        let args = args_open_interest(term_start, 1698796800000);
+        assert_eq!(binance.prepare_open_interest(&args).is_ok(), expected);
+    }
+
+    #[test]
+    fn test_parse_as_open_interest() {
+        let binance = Binance::new();
+
+        let input = r#"
+        [
+            {
+                "symbol": "BTCUSDT",
+                "sumOpenInterest": "87123.45600000",
+                "sumOpenInterestValue": "3018362841.21000000",
+                "timestamp": 1698793200000
+            },
+            {
+                "symbol": "BTCUSDT",
+                "sumOpenInterest": "87290.11100000",
+                "sumOpenInterestValue": "3024136725.99000000",
+                "timestamp": "1698796800000"
+            }
+        ]"#
+        .to_string();
+        let result = binance.parse_as_open_interest(input);
+        let expected = vec![
+            OpenInterest {
+                unixtime_msec: 1698793200000,
+                amount: 87123.456,
+                value: Some(3018362841.21),
+            },
+            OpenInterest {
+                unixtime_msec: 1698796800000,
+                amount: 87290.111,
+                value: Some(3024136725.99),
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline_extra() {
+        let binance = Binance::new();
+
+        let input = r#"
+        [
+            [
+                1681516800000,
+                "30295.09000000",
+                "30311.00000000",
+                "30290.55000000",
+                "30301.44000000",
+                "96.91281000",
+                1681517699999,
+                "2936555.94052660",
+                2773,
+                "45.36018000",
+                "1374505.17262130",
+                "0"
+            ]
+        ]"#
+        .to_string();
+        let expected = vec![Kline {
+            unixtime_msec: 1681516800000,
+            o: 30295.09,
+            h: 30311.0,
+            l: 30290.55,
+            c: 30301.44,
+            v: 96.91281,
+            extra: KlineExtra {
+                quote_volume: Some(2936555.9405266),
+                trades: Some(2773),
+                taker_buy_volume: Some(45.36018),
+                taker_buy_quote_volume: Some(1374505.1726213),
+                vwap: None,
+            },
+        }];
+
+        assert_eq!(binance.parse_as_kline(input), expected);
+    }
+
+    #[rstest]
+    #[case("BTCUSD", Some(100.0))]
+    #[case("ETHUSD", Some(10.0))]
+    #[case("XRPUSD", None)]
+    fn test_contract_size(#[case] pair: &str, #[case] expected: Option<f64>) {
+        let input = r#"
+        {
+            "timezone": "UTC",
+            "serverTime": 1698796800000,
+            "symbols": [
+                {
+                    "symbol": "BTCUSD_PERP",
+                    "pair": "BTCUSD",
+                    "contractType": "PERPETUAL",
+                    "contractSize": 100,
+                    "baseAsset": "BTC",
+                    "quoteAsset": "USD"
+                },
+                {
+                    "symbol": "BTCUSD_231229",
+                    "pair": "BTCUSD",
+                    "contractType": "CURRENT_QUARTER",
+                    "contractSize": 100,
+                    "baseAsset": "BTC",
+                    "quoteAsset": "USD"
+                },
+                {
+                    "symbol": "ETHUSD_PERP",
+                    "pair": "ETHUSD",
+                    "contractType": "PERPETUAL",
+                    "contractSize": 10,
+                    "baseAsset": "ETH",
+                    "quoteAsset": "USD"
+                }
+            ]
+        }"#;
+
+        assert_eq!(Binance::contract_size(input, pair).ok(), expected);
+    }
+
+    #[rstest]
+    #[case(MarketType::Spot, vec![("BTC/USDT", "BTCUSDT", None)])]
+    #[case(MarketType::Perpetual, vec![("BTC/USDT", "BTCUSDT", None)])]
+    #[case(
+        MarketType::Futures,
+        vec![("BTC/USDT", "BTCUSDT_231229", NaiveDate::from_ymd_opt(2023, 12, 29))]
+    )]
+    fn test_parse_as_listings(
+        #[case] market_type: MarketType,
+        #[case] expected: Vec<(&str, &str, Option<NaiveDate>)>,
+    ) {
+        let spot = r#"
+        {
+            "timezone": "UTC",
+            "symbols": [
+                {
+                    "symbol": "BTCUSDT",
+                    "status": "TRADING",
+                    "baseAsset": "BTC",
+                    "quoteAsset": "USDT"
+                },
+                {
+                    "symbol": "LUNABTC",
+                    "status": "BREAK",
+                    "baseAsset": "LUNA",
+                    "quoteAsset": "BTC"
+                }
+            ]
+        }"#;
+        // USD-M, where the settlement currency is in the name of TradFi perpetuals
+        let derivatives = r#"
+        {
+            "timezone": "UTC",
+            "symbols": [
+                {
+                    "symbol": "BTCUSDT",
+                    "pair": "BTCUSDT",
+                    "contractType": "PERPETUAL",
+                    "deliveryDate": 4133404800000,
+                    "status": "TRADING",
+                    "baseAsset": "BTC",
+                    "quoteAsset": "USDT"
+                },
+                {
+                    "symbol": "BTCUSDT_231229",
+                    "pair": "BTCUSDT",
+                    "contractType": "CURRENT_QUARTER",
+                    "deliveryDate": 1703836800000,
+                    "status": "TRADING",
+                    "baseAsset": "BTC",
+                    "quoteAsset": "USDT"
+                },
+                {
+                    "symbol": "XAUUSDT",
+                    "pair": "XAUUSDT",
+                    "contractType": "TRADIFI_PERPETUAL",
+                    "deliveryDate": 4133404800000,
+                    "status": "TRADING",
+                    "baseAsset": "XAU",
+                    "quoteAsset": "USDT"
+                },
+                {
+                    "symbol": "ETHUSDT",
+                    "pair": "ETHUSDT",
+                    "contractType": "PERPETUAL",
+                    "deliveryDate": 4133404800000,
+                    "status": "SETTLING",
+                    "baseAsset": "ETH",
+                    "quoteAsset": "USDT"
+                }
+            ]
+        }"#;
+
+        let binance = Binance {
+            market_type: market_type.clone(),
+            ..Binance::new()
+        };
+        let input = match market_type {
+            MarketType::Spot => spot,
+            _ => derivatives,
+        };
+        let result: Vec<(String, String, Option<NaiveDate>)> = binance
+            .parse_as_listings(input)
+            .unwrap()
+            .into_iter()
+            .map(|listing| (listing.symbol(), listing.native, listing.expiry))
+            .collect();
+        let expected: Vec<(String, String, Option<NaiveDate>)> = expected
+            .into_iter()
+            .map(|(symbol, native, expiry)| (symbol.to_string(), native.to_string(), expiry))
+            .collect();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_listings_inverse() {
+        // COIN-M has `contractStatus` instead of `status`
+        let input = r#"
+        {
+            "timezone": "UTC",
+            "symbols": [
+                {
+                    "symbol": "BTCUSD_PERP",
+                    "pair": "BTCUSD",
+                    "contractType": "PERPETUAL",
+                    "deliveryDate": 4133404800000,
+                    "contractStatus": "TRADING",
+                    "contractSize": 100,
+                    "baseAsset": "BTC",
+                    "quoteAsset": "USD"
+                }
+            ]
+        }"#;
+
+        let mut binance = Binance::new();
+        binance.market_type = MarketType::Perpetual;
+        binance.contract.margin = Some(Margin::Inverse);
+        let result = binance.parse_as_listings(input).unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].symbol(), "BTC/USD");
+        assert_eq!(result[0].native, "BTCUSD_PERP");
+    }
 }
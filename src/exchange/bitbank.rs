@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
 use anyhow::{anyhow, Error};
-use chrono::{Datelike, TimeZone, Utc};
 use regex::Regex;
 use reqwest::blocking::Client;
 use serde::Deserialize;
@@ -9,6 +8,7 @@ use serde::Deserialize;
 use crate::{args::*, error::*, exchange::*, unit::*};
 
 const ENDPOINT_FRAME: &str = "https://public.bitbank.cc/{pair}/candlestick/{candle_type}/{date}";
+const ENDPOINT_PAIRS: &str = "https://api.bitbank.cc/v1/spot/pairs";
 
 #[derive(Debug, Clone)]
 pub struct Bitbank {
@@ -29,6 +29,17 @@ struct DataInResponse {
     code: Option<i32>,
 }
 
+#[derive(Deserialize)]
+// There is no endpoint for time only, so use the timestamp of ticker
+struct ResponseOnTime {
+    data: TimeInResponse,
+}
+
+#[derive(Deserialize)]
+struct TimeInResponse {
+    timestamp: i64,
+}
+
 #[derive(Deserialize)]
 struct Candlestick {
     #[serde(alias = "type")]
@@ -36,6 +47,25 @@ struct Candlestick {
     ohlcv: Vec<Vec<serde_json::Value>>,
 }
 
+#[derive(Deserialize)]
+struct ResponseOnPairs {
+    data: PairsInResponse,
+}
+
+#[derive(Deserialize)]
+struct PairsInResponse {
+    pairs: Vec<PairToList>,
+}
+
+#[derive(Deserialize)]
+struct PairToList {
+    /// Like `btc_jpy`
+    name: String,
+    base_asset: String,
+    quote_asset: String,
+    is_enabled: bool,
+}
+
 impl Bitbank {
     pub fn new() -> Self {
         Bitbank {
@@ -44,24 +74,14 @@ impl Bitbank {
         }
     }
 
-    /// If it crosses days or years (according to `interval`),
-    /// only the first one is returned (then retrieve will repeat itself).
     fn calculate_date(term_start: i64, interval: &str) -> String {
-        // On all exchanges, there is no problem to do -1/+1 when actually fetching,
-        // but only when determining if the day has passed on bitbank, otherwise you will get the wrong result
-        let term_start = term_start + 1;
-
-        let start = Utc.timestamp_millis_opt(term_start).unwrap();
-
-        match interval {
-            "1min" | "5min" | "15min" | "30min" | "1hour" => {
-                format!("{}{:02}{:02}", start.year(), start.month(), start.day())
-            }
-            "4hour" | "8hour" | "12hour" | "1day" | "1week" | "1month" => {
-                format!("{}", start.year())
-            }
+        let partition = match interval {
+            "1min" | "5min" | "15min" | "30min" | "1hour" => Partition::Day,
+            "4hour" | "8hour" | "12hour" | "1day" | "1week" | "1month" => Partition::Year,
             _ => unreachable!(/* Validation with enume is done to get to this point */),
-        }
+        };
+
+        partition.date(term_start, 0)
     }
 
     fn make_url(&self, symbol: String, interval: &str, date: String) -> String {
@@ -70,11 +90,24 @@ impl Bitbank {
             .replace("{candle_type}", interval)
             .replace("{date}", &date)
     }
+
+    fn parse_as_listings(data: &str) -> Result<Vec<Listing>, Error> {
+        let response = serde_json::from_str::<ResponseOnPairs>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+
+        Ok(response
+            .data
+            .pairs
+            .iter()
+            .filter(|pair| pair.is_enabled)
+            .map(|pair| Listing::new(&pair.base_asset, &pair.quote_asset, &pair.name, &[]))
+            .collect())
+    }
 }
 
 impl Retrieve for Bitbank {
     fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
-        if let MarketType::Perpetual = args.type_ {
+        if !matches!(args.type_, MarketType::Spot) {
             return Err(ExchangeResponseError::no_support_type());
         }
 
@@ -106,6 +139,34 @@ impl Retrieve for Bitbank {
         Ok(res)
     }
 
+    fn server_time(
+        &self,
+        client: &Client,
+        _market_type: &MarketType,
+    ) -> Result<Option<i64>, Error> {
+        let res = client
+            .get("https://public.bitbank.cc/btc_jpy/ticker")
+            .send()?
+            .json::<ResponseOnTime>()?;
+
+        Ok(Some(res.data.timestamp))
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        if !matches!(market_type, MarketType::Spot) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        let res = client.get(ENDPOINT_PAIRS).send()?.text()?;
+
+        Self::parse_as_listings(&res)
+    }
+
     fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
         // Almost same code as Binance, so the test already exists
 
@@ -158,28 +219,13 @@ impl Retrieve for Bitbank {
                 l: raw[2].as_str().unwrap().parse::<f64>().unwrap(),
                 c: raw[3].as_str().unwrap().parse::<f64>().unwrap(),
                 v: raw[4].as_str().unwrap().parse::<f64>().unwrap(),
+                extra: KlineExtra::default(),
             })
             .collect()
     }
 
     fn remove_unnecessary_raws(raws: Vec<Kline>, term_start: i64, term_end: i64) -> Vec<Kline> {
-        if raws.is_empty() {
-            return raws.to_vec();
-        }
-
-        let first_ts = raws[0].unixtime_msec;
-        let latest_ts = raws[raws.len() - 1].unixtime_msec;
-        let mut result = raws;
-
-        if first_ts <= term_start {
-            result.retain(|raw| term_start < raw.unixtime_msec);
-        }
-
-        if term_end <= latest_ts {
-            result.retain(|raw| raw.unixtime_msec < term_end);
-        }
-
-        result
+        retain_in_term(raws, term_start, term_end)
     }
 }
 
@@ -263,6 +309,7 @@ mod tests {
                 l: 2168036.0,
                 c: 3014463.0,
                 v: 8628.4802,
+                extra: KlineExtra::default(),
             },
             Kline {
                 unixtime_msec: 1675209600000,
@@ -271,6 +318,7 @@ mod tests {
                 l: 2830100.0,
                 c: 3158202.0,
                 v: 7460.3227,
+                extra: KlineExtra::default(),
             },
         ];
 
@@ -288,6 +336,7 @@ mod tests {
             l: 0.0,
             c: 0.0,
             v: 0.0,
+            extra: KlineExtra::default(),
         },
         Kline {
             unixtime_msec: 1672531200000,
@@ -296,6 +345,7 @@ mod tests {
             l: 0.0,
             c: 0.0,
             v: 0.0,
+            extra: KlineExtra::default(),
         },
         Kline {
             unixtime_msec: 1672545600000,
@@ -304,6 +354,7 @@ mod tests {
             l: 0.0,
             c: 0.0,
             v: 0.0,
+            extra: KlineExtra::default(),
         },
     ], 1672516799999, 1672545600001, vec![
         Kline {
@@ -313,6 +364,7 @@ mod tests {
             l: 0.0,
             c: 0.0,
             v: 0.0,
+            extra: KlineExtra::default(),
         },
         Kline {
             unixtime_msec: 1672545600000,
@@ -321,6 +373,7 @@ mod tests {
             l: 0.0,
             c: 0.0,
             v: 0.0,
+            extra: KlineExtra::default(),
         },
     ])]
     // Expect latest to be removed
@@ -332,6 +385,7 @@ mod tests {
             l: 0.0,
             c: 0.0,
             v: 0.0,
+            extra: KlineExtra::default(),
         },
         Kline {
             unixtime_msec: 1672531200000,
@@ -340,6 +394,7 @@ mod tests {
             l: 0.0,
             c: 0.0,
             v: 0.0,
+            extra: KlineExtra::default(),
         },
         Kline {
             unixtime_msec: 1672545600001,
@@ -348,6 +403,7 @@ mod tests {
             l: 0.0,
             c: 0.0,
             v: 0.0,
+            extra: KlineExtra::default(),
         },
     ], 1672516799999, 1672545600001, vec![
         Kline {
@@ -357,6 +413,7 @@ mod tests {
             l: 0.0,
             c: 0.0,
             v: 0.0,
+            extra: KlineExtra::default(),
         },
         Kline {
             unixtime_msec: 1672531200000,
@@ -365,6 +422,7 @@ mod tests {
             l: 0.0,
             c: 0.0,
             v: 0.0,
+            extra: KlineExtra::default(),
         },
     ])]
     fn test_remove_unnecessary_raws(
@@ -378,4 +436,35 @@ mod tests {
             expected
         )
     }
+
+    #[test]
+    fn test_parse_as_listings() {
+        let input = r#"
+        {
+            "success": 1,
+            "data": {
+                "pairs": [
+                    {
+                        "name": "btc_jpy",
+                        "base_asset": "btc",
+                        "quote_asset": "jpy",
+                        "is_enabled": true,
+                        "is_suspended": false
+                    },
+                    {
+                        "name": "bcc_jpy",
+                        "base_asset": "bcc",
+                        "quote_asset": "jpy",
+                        "is_enabled": false,
+                        "is_suspended": false
+                    }
+                ]
+            }
+        }"#;
+
+        let result = Bitbank::parse_as_listings(input).unwrap();
+
+        assert_eq!(result, vec![Listing::new("BTC", "JPY", "btc_jpy", &[])]);
+        assert_eq!(result[0].symbol(), "BTC/JPY");
+    }
 }
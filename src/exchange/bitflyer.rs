@@ -0,0 +1,508 @@
+use std::{thread, time};
+
+use anyhow::{anyhow, Error};
+use chrono::NaiveDateTime;
+use regex::Regex;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use crate::{args::*, error::*, exchange::*, unit::*};
+
+/// There is no public OHLC endpoint, so candles are built from executions.
+/// Executions can only be retrieved back to about 31 days ago.
+const ENDPOINT: &str = "https://api.bitflyer.com/v1/executions";
+const ENDPOINT_MARKETS: &str = "https://api.bitflyer.com/v1/getmarkets";
+const LIMIT: i32 = 500;
+/// Executions older than this are not served at all
+const LOOK_BACK_DAYS: i64 = 31;
+/// Requests to seek the execution id to start from, see `Bitflyer::seek_before()`
+const MAX_SEEKS: u32 = 16;
+/// Public endpoints allow 500 requests per 5 minutes per IP address
+const REQUEST_INTERVAL_MSEC: u64 = 700;
+
+#[derive(Debug, Clone)]
+pub struct Bitflyer {
+    params: Vec<(String, String)>,
+    endpoint: String,
+    interval: DurationAndUnit,
+    /// Execution id cursor, the oldest id in the previous response (`None` for the latest)
+    before: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnError {
+    error_message: String,
+}
+
+#[derive(Deserialize)]
+struct Execution {
+    id: i64,
+    price: f64,
+    size: f64,
+    exec_date: String,
+}
+
+impl Execution {
+    fn unixtime_msec(&self) -> i64 {
+        // `exec_date` is in UTC but without any offset
+        NaiveDateTime::parse_from_str(&self.exec_date, "%Y-%m-%dT%H:%M:%S%.f")
+            .unwrap()
+            .timestamp_millis()
+    }
+}
+
+#[derive(Deserialize)]
+struct MarketToList {
+    /// Like `BTC_JPY` and `FX_BTC_JPY`
+    product_code: String,
+    /// `Spot`, `FX` (perpetual) or `Futures`
+    market_type: String,
+}
+
+impl Bitflyer {
+    pub fn new() -> Self {
+        Bitflyer {
+            params: Vec::new(),
+            endpoint: ENDPOINT.to_string(),
+            interval: DurationAndUnit(1, TermUnit::Min),
+            before: None,
+        }
+    }
+
+    /// Returns trades in the page (newest first) and the cursor for the next page.
+    fn parse_as_trades(&self, data: String) -> (Vec<Trade>, Option<i64>) {
+        let executions = serde_json::from_str::<Vec<Execution>>(&data)
+            .expect("Unexpected error! Failed to parse response to json.");
+
+        let oldest = executions.last().map(|execution| execution.id);
+        let trades = executions
+            .iter()
+            .map(|execution| Trade {
+                unixtime_msec: execution.unixtime_msec(),
+                price: execution.price,
+                size: execution.size,
+            })
+            .collect();
+
+        (trades, oldest)
+    }
+
+    /// Returns ids and times of executions in the page (newest first).
+    fn parse_as_ids(data: &str) -> Vec<(i64, i64)> {
+        serde_json::from_str::<Vec<Execution>>(data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .iter()
+            .map(|execution| (execution.id, execution.unixtime_msec()))
+            .collect()
+    }
+
+    /// Ids and times of up to `count` executions before the cursor (newest first).
+    fn fetch_ids(
+        &mut self,
+        client: &Client,
+        args: &ParsedArgs,
+        before: Option<i64>,
+        count: i32,
+    ) -> Result<Vec<(i64, i64)>, Error> {
+        self.before = before;
+        self.prepare(args)?;
+        self.params.retain(|(key, _)| key != "count");
+        self.params.push(("count".to_string(), count.to_string()));
+
+        Ok(Self::parse_as_ids(&self.fetch(client)?))
+    }
+
+    /// Cursor to walk executions backwards from, so that the walk starts around `until`
+    /// rather than from the latest execution (`None` if the latest page already reaches it).
+    fn seek(
+        &mut self,
+        client: &Client,
+        args: &ParsedArgs,
+        until: i64,
+    ) -> Result<Option<i64>, Error> {
+        let latest = self.fetch_ids(client, args, None, LIMIT)?;
+        let (Some(newest), Some(oldest)) = (latest.first(), latest.last()) else {
+            return Ok(None);
+        };
+        if oldest.1 <= until {
+            return Ok(None);
+        }
+        let ids_per_msec = (newest.0 - oldest.0) as f64 / (newest.1 - oldest.1).max(1) as f64;
+
+        let before = Self::seek_before(until, *oldest, ids_per_msec, |before| {
+            thread::sleep(time::Duration::from_millis(REQUEST_INTERVAL_MSEC));
+            Ok(self
+                .fetch_ids(client, args, Some(before), 1)?
+                .first()
+                .copied())
+        })?;
+
+        Ok(Some(before))
+    }
+
+    /// Looks up the id of an execution after `until` and close to it, starting from `later`
+    /// (the id and time of such an execution) and the rate ids are numbered at.
+    /// Ids increase with time, so probes (the execution just before an id) step back until one
+    /// is before `until`, then are interpolated between the closest ones before and after it.
+    fn seek_before<F>(
+        until: i64,
+        later: (i64, i64),
+        ids_per_msec: f64,
+        mut probe: F,
+    ) -> Result<i64, Error>
+    where
+        F: FnMut(i64) -> Result<Option<(i64, i64)>, Error>,
+    {
+        let mut after = later;
+        let mut before: Option<(i64, i64)> = None;
+        // Reaches further back while the rate at the latest page underestimates the distance
+        let mut reach = 1.0;
+        // Whether the last probe was after `until`, to aim the next one at the other side
+        let mut last_after = true;
+
+        for _ in 0..MAX_SEEKS {
+            let guess = match before {
+                // Close enough, the rest is walked within a page
+                Some(before) if after.0 - before.0 <= LIMIT as i64 => break,
+                // Half a page off the interpolation, so that two probes around it are enough
+                Some(before) => {
+                    let ratio = (until - before.1) as f64 / (after.1 - before.1).max(1) as f64;
+                    let interpolated = before.0 + ((after.0 - before.0) as f64 * ratio) as i64;
+                    let margin = match last_after {
+                        true => -(LIMIT as i64) / 2,
+                        false => LIMIT as i64 / 2,
+                    };
+                    (interpolated + margin).clamp(before.0 + 1, after.0 - 1)
+                }
+                None => {
+                    let distance = ((after.1 - until) as f64 * ids_per_msec * reach) as i64;
+                    reach *= 2.0;
+                    (after.0 - distance.max(1)).max(1)
+                }
+            };
+
+            // The execution with the largest id up to the guess (ids are shared by all products,
+            // so the ones between it and the guess belong to others and do not matter)
+            match probe(guess + 1)? {
+                Some(execution) if until < execution.1 => {
+                    after = execution;
+                    last_after = true;
+                }
+                Some(execution) => {
+                    before = Some((guess, execution.1));
+                    last_after = false;
+                }
+                // Nothing is served up to the guess
+                None => {
+                    before = Some((guess, 0));
+                    last_after = false;
+                }
+            }
+        }
+
+        Ok(after.0)
+    }
+
+    fn parse_as_listings(data: &str, market_type: &MarketType) -> Result<Vec<Listing>, Error> {
+        let markets = serde_json::from_str::<Vec<MarketToList>>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+
+        Ok(markets
+            .iter()
+            .filter_map(|market| {
+                let pair = match (market_type, market.market_type.as_str()) {
+                    (MarketType::Spot, "Spot") => market.product_code.as_str(),
+                    (MarketType::Perpetual, "FX") => market.product_code.strip_prefix("FX_")?,
+                    _ => return None,
+                };
+                let (base, quote) = pair.split_once('_')?;
+
+                Some(Listing::new(base, quote, &market.product_code, &[]))
+            })
+            .collect())
+    }
+}
+
+impl Retrieve for Bitflyer {
+    fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        let product_code = match args.type_ {
+            MarketType::Spot => self.fit_symbol_to_req(&args.symbol)?,
+            MarketType::Perpetual => format!("FX_{}", self.fit_symbol_to_req(&args.symbol)?),
+            _ => return Err(ExchangeResponseError::no_support_type()),
+        };
+
+        self.interval = args.interval.clone();
+        self.params = [
+            ("product_code".to_string(), product_code),
+            ("count".to_string(), LIMIT.to_string()),
+        ]
+        .to_vec();
+        if let Some(before) = self.before {
+            self.params.push(("before".to_string(), before.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Walk executions backwards with the cursor from around the end of the period and build candles
+    /// at the end, because ids cannot be looked up from time.
+    fn collect_klines(&mut self, args: &mut ParsedArgs) -> Result<Vec<Kline>, Error> {
+        let client = Client::new();
+        // Only whole candles, a partial one would have wrong prices and volume
+        let (term_start, term_end) = trade_term(
+            args.term_start.unwrap(),
+            args.term_end.unwrap(),
+            &args.interval,
+        );
+        if term_end < term_start {
+            return Ok(Vec::new());
+        }
+
+        // Older executions are not served, which would silently leave the period empty or truncated
+        // (`--count` stops at `history_start()` by itself)
+        if args.count.is_none() && term_start < self.history_start(args).unwrap() {
+            return Err(ExchangeResponseError::look_back(LOOK_BACK_DAYS));
+        }
+
+        let mut trades: Vec<Trade> = Vec::new();
+
+        self.before = self.seek(&client, args, term_end)?;
+        thread::sleep(time::Duration::from_millis(REQUEST_INTERVAL_MSEC));
+
+        loop {
+            self.prepare(args)?;
+
+            let res = self.fetch(&client)?;
+            let (page, oldest) = self.parse_as_trades(res);
+
+            let reached_start = match page.last() {
+                Some(oldest) => oldest.unixtime_msec < term_start,
+                None => true,
+            };
+
+            trades.extend(page.into_iter().filter(|trade| {
+                term_start <= trade.unixtime_msec && trade.unixtime_msec <= term_end
+            }));
+
+            if reached_start || oldest.is_none() {
+                break;
+            }
+            self.before = oldest;
+
+            thread::sleep(time::Duration::from_millis(REQUEST_INTERVAL_MSEC));
+        }
+
+        Ok(aggregate_trades(trades, &args.interval))
+    }
+
+    fn history_start(&self, args: &ParsedArgs) -> Option<i64> {
+        Some(args.now().timestamp_millis() - LOOK_BACK_DAYS * 24 * 60 * 60 * 1000)
+    }
+
+    fn fetch(&self, client: &Client) -> Result<String, Error> {
+        let res = client
+            .get(&self.endpoint)
+            .query(&self.params)
+            .send()?
+            .text()?;
+
+        if let Ok(response) = serde_json::from_str::<ResponseOnError>(&res) {
+            match response.error_message.as_str() {
+                "Invalid product" => return Err(ExchangeResponseError::symbol()),
+                message if message.contains("limit") => {
+                    return Err(ExchangeResponseError::too_many_requests())
+                }
+                _ => return Err(ExchangeResponseError::wrap_error(response.error_message)),
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn native_volume(&self, _client: &Client, args: &ParsedArgs) -> Result<NativeVolume, Error> {
+        // Perpetuals are traded in coins, as if a contract were one coin
+        match args.type_ {
+            MarketType::Spot => Ok(NativeVolume::Base),
+            _ => Ok(NativeVolume::Contracts {
+                size: 1.0,
+                inverse: false,
+            }),
+        }
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        if !matches!(market_type, MarketType::Spot | MarketType::Perpetual) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        let res = client.get(ENDPOINT_MARKETS).send()?.text()?;
+
+        Self::parse_as_listings(&res, market_type)
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        let re = Regex::new(r"^(.*?)/(.*?)$").unwrap();
+        let matches = re.captures(symbol).ok_or(anyhow!(
+            "The symbol pair provided is incorrectly formatted."
+        ))?;
+        Ok(format!("{}_{}", &matches[1], &matches[2]))
+    }
+
+    fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
+        // Not sent to bitFlyer, any interval can be built from executions
+        Ok(format!("{}{:?}", interval.0, interval.1).to_lowercase())
+    }
+
+    fn parse_as_kline(&self, data: String) -> Vec<Kline> {
+        aggregate_trades(self.parse_as_trades(data).0, &self.interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use rstest::*;
+
+    use super::*;
+    use crate::clock::*;
+
+    #[rstest]
+    #[case("BTC/JPY", "BTC_JPY".to_string())]
+    #[case("ETH/BTC", "ETH_BTC".to_string())]
+    fn test_fit_symbol_to_req(#[case] input: &str, #[case] expected: String) {
+        let bitflyer = Bitflyer::new();
+        assert_eq!(bitflyer.fit_symbol_to_req(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    // The rate at the latest page is right
+    #[case(1, 0.01)]
+    // ... or far less than in the past
+    #[case(1, 0.0001)]
+    // ... or far more
+    #[case(1, 1.0)]
+    // Most ids are of other products
+    #[case(7, 0.01)]
+    fn test_seek_before(#[case] step: usize, #[case] ids_per_msec: f64) {
+        // An execution every 100 ms, whose ids are spaced by `step`
+        let executions: Vec<(i64, i64)> = (1..=100_000)
+            .step_by(step)
+            .map(|id| (id, id * 100))
+            .collect();
+        let until = 5_000_000;
+        let mut probes = 0;
+
+        let result =
+            Bitflyer::seek_before(until, *executions.last().unwrap(), ids_per_msec, |before| {
+                probes += 1;
+                Ok(executions
+                    .iter()
+                    .rev()
+                    .find(|execution| execution.0 < before)
+                    .copied())
+            })
+            .unwrap();
+
+        // After `until`, and the ones between are within a page
+        let (id, unixtime_msec) = *executions
+            .iter()
+            .find(|execution| execution.0 == result)
+            .unwrap();
+        assert!(until < unixtime_msec);
+        let between = executions
+            .iter()
+            .filter(|execution| until < execution.1 && execution.0 < id)
+            .count();
+        assert!(between <= LIMIT as usize, "{} executions between", between);
+        assert!(probes <= MAX_SEEKS);
+    }
+
+    #[test]
+    fn test_collect_klines_look_back() {
+        let mut bitflyer = Bitflyer::new();
+        let now = 1688669598410;
+        let mut args = ParsedArgs {
+            exchange: Exchange::Bitflyer(Bitflyer::new()),
+            symbol: "BTC/JPY".to_string(),
+            // 40 days ago, before any execution served
+            term_start: Some(now - 40 * 24 * 60 * 60 * 1000),
+            term_end: Some(now),
+            clock: Clock::Fixed(Utc.timestamp_millis_opt(now).unwrap()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            bitflyer.history_start(&args),
+            Some(now - 31 * 24 * 60 * 60 * 1000)
+        );
+        assert!(bitflyer.collect_klines(&mut args).is_err());
+    }
+
+    #[test]
+    fn test_parse_as_trades() {
+        let bitflyer = Bitflyer::new();
+
+        let input = r#"
+        [
+            {
+                "id": 2431633571,
+                "side": "SELL",
+                "price": 4282570.0,
+                "size": 0.01,
+                "exec_date": "2023-07-06T18:53:18.41",
+                "buy_child_order_acceptance_id": "JRF20230706-185318-046561",
+                "sell_child_order_acceptance_id": "JRF20230706-185318-135009"
+            },
+            {
+                "id": 2431633570,
+                "side": "BUY",
+                "price": 4282600.0,
+                "size": 0.0032,
+                "exec_date": "2023-07-06T18:53:17",
+                "buy_child_order_acceptance_id": "JRF20230706-185317-229144",
+                "sell_child_order_acceptance_id": "JRF20230706-185316-035047"
+            }
+        ]"#
+        .to_string();
+        let result = bitflyer.parse_as_trades(input);
+        let expected = (
+            vec![
+                Trade {
+                    unixtime_msec: 1688669598410,
+                    price: 4282570.0,
+                    size: 0.01,
+                },
+                Trade {
+                    unixtime_msec: 1688669597000,
+                    price: 4282600.0,
+                    size: 0.0032,
+                },
+            ],
+            Some(2431633570),
+        );
+
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(MarketType::Spot, vec![Listing::new("BTC", "JPY", "BTC_JPY", &[])])]
+    #[case(MarketType::Perpetual, vec![Listing::new("BTC", "JPY", "FX_BTC_JPY", &[])])]
+    fn test_parse_as_listings(#[case] market_type: MarketType, #[case] expected: Vec<Listing>) {
+        let input = r#"
+        [
+            { "product_code": "BTC_JPY", "market_type": "Spot" },
+            { "product_code": "FX_BTC_JPY", "market_type": "FX" },
+            { "product_code": "BTCJPY29DEC2023", "alias": "BTCJPY_MAT3M", "market_type": "Futures" }
+        ]"#;
+
+        assert_eq!(
+            Bitflyer::parse_as_listings(input, &market_type).unwrap(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use anyhow::Error;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use crate::{args::*, error::*, exchange::*, unit::*};
+
+const ENDPOINT_SPOT: &str = "https://api.bitget.com/api/v2/spot/market/candles";
+const ENDPOINT_PERPETUAL: &str = "https://api.bitget.com/api/v2/mix/market/candles";
+const ENDPOINT_SYMBOLS_SPOT: &str = "https://api.bitget.com/api/v2/spot/public/symbols";
+const ENDPOINT_CONTRACTS: &str = "https://api.bitget.com/api/v2/mix/market/contracts";
+const LIMIT: i64 = 1000;
+
+#[derive(Debug, Clone)]
+pub struct Bitget {
+    params: Vec<(String, String)>,
+    market_type: MarketType,
+    endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    code: String,
+    msg: String,
+    data: Option<Vec<Vec<String>>>,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnTime {
+    data: TimeInResponse,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TimeInResponse {
+    server_time: String,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnSymbols {
+    code: String,
+    msg: String,
+    data: Option<Vec<SymbolToList>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SymbolToList {
+    symbol: String,
+    base_coin: String,
+    quote_coin: String,
+    /// Spot only, `online`, `halt` and so on
+    status: Option<String>,
+    /// Perpetual only, `normal`, `maintain` and so on
+    symbol_status: Option<String>,
+}
+
+impl Bitget {
+    pub fn new() -> Self {
+        Bitget {
+            params: Vec::new(),
+            market_type: MarketType::Spot,
+            endpoint: String::new(),
+        }
+    }
+
+    fn parse_as_listings(data: &str) -> Result<Vec<Listing>, Error> {
+        let response = serde_json::from_str::<ResponseOnSymbols>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+        if response.code != "00000" {
+            return Err(ExchangeResponseError::wrap_error(response.msg));
+        }
+
+        Ok(response
+            .data
+            .unwrap_or_default()
+            .iter()
+            .filter(|symbol| {
+                matches!(
+                    (symbol.status.as_deref(), symbol.symbol_status.as_deref()),
+                    (Some("online"), _) | (_, Some("normal"))
+                )
+            })
+            .map(|symbol| Listing::new(&symbol.base_coin, &symbol.quote_coin, &symbol.symbol, &[]))
+            .collect())
+    }
+}
+
+impl Retrieve for Bitget {
+    fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        if let MarketType::Futures | MarketType::Option = args.type_ {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        self.market_type = args.type_.clone();
+
+        // Cut the period into windows of `LIMIT` candles, the generic loop moves on to the next one
+        let term_start = args.term_start.unwrap();
+        let term_end = args
+            .term_end
+            .unwrap()
+            .min(term_start + self.window(args).unwrap());
+
+        self.params = [
+            ("symbol".to_string(), self.fit_symbol_to_req(&args.symbol)?),
+            (
+                "granularity".to_string(),
+                self.fit_interval_to_req(&args.interval)?,
+            ),
+            ("startTime".to_string(), term_start.to_string()),
+            ("endTime".to_string(), term_end.to_string()),
+            ("limit".to_string(), LIMIT.to_string()),
+        ]
+        .to_vec();
+
+        match args.type_ {
+            MarketType::Spot => self.endpoint = ENDPOINT_SPOT.to_string(),
+            MarketType::Perpetual => {
+                // Only USDT-margined perpetuals
+                self.params
+                    .push(("productType".to_string(), "USDT-FUTURES".to_string()));
+                self.endpoint = ENDPOINT_PERPETUAL.to_string();
+            }
+            _ => unreachable!(/* Checked above */),
+        }
+
+        Ok(())
+    }
+
+    fn window(&self, args: &ParsedArgs) -> Option<i64> {
+        Some((LIMIT - 1) * args.interval.to_msec())
+    }
+
+    fn fetch(&self, client: &Client) -> Result<String, Error> {
+        let res = client
+            .get(&self.endpoint)
+            .query(&self.params)
+            .send()?
+            .text()?;
+
+        let response = serde_json::from_str::<Response>(&res)
+            .expect("Unexpected error! Failed to parse response (for error code) to json.");
+        match response.code.as_str() {
+            "00000" => {}
+            "429" => return Err(ExchangeResponseError::too_many_requests()),
+            // If the validation of `interval` is complete, then the cause of the error
+            // can be identified as a symbol only
+            "40034" => return Err(ExchangeResponseError::symbol()),
+            _ => return Err(ExchangeResponseError::wrap_error(response.msg)),
+        }
+
+        Ok(res)
+    }
+
+    fn server_time(
+        &self,
+        client: &Client,
+        _market_type: &MarketType,
+    ) -> Result<Option<i64>, Error> {
+        let res = client
+            .get("https://api.bitget.com/api/v2/public/time")
+            .send()?
+            .json::<ResponseOnTime>()?;
+
+        Ok(Some(res.data.server_time.parse::<i64>()?))
+    }
+
+    fn native_volume(&self, _client: &Client, args: &ParsedArgs) -> Result<NativeVolume, Error> {
+        // Perpetuals are traded in coins, as if a contract were one coin
+        match args.type_ {
+            MarketType::Spot => Ok(NativeVolume::Base),
+            _ => Ok(NativeVolume::Contracts {
+                size: 1.0,
+                inverse: false,
+            }),
+        }
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        let res = match market_type {
+            MarketType::Spot => client.get(ENDPOINT_SYMBOLS_SPOT).send()?.text()?,
+            // Only USDT-margined perpetuals
+            MarketType::Perpetual => client
+                .get(ENDPOINT_CONTRACTS)
+                .query(&[("productType", "USDT-FUTURES")])
+                .send()?
+                .text()?,
+            _ => return Err(ExchangeResponseError::no_support_type()),
+        };
+
+        Self::parse_as_listings(&res)
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        let (base, quote) = split_symbol(symbol, &[])?;
+        Ok(format!("{}{}", base, quote))
+    }
+
+    fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
+        // Days or longer without `utc` are based on UTC+8, so only UTC ones are used
+        let result = match (&self.market_type, interval.0, &interval.1) {
+            (MarketType::Spot, 1 | 3 | 5 | 15 | 30, TermUnit::Min) => format!("{}min", interval.0),
+            (MarketType::Spot, 1 | 4, TermUnit::Hour) => format!("{}h", interval.0),
+            (_, 1 | 3 | 5 | 15 | 30, TermUnit::Min) => format!("{}m", interval.0),
+            (_, 1 | 4, TermUnit::Hour) => format!("{}H", interval.0),
+            (_, 6 | 12, TermUnit::Hour) => format!("{}Hutc", interval.0),
+            (_, 1 | 3, TermUnit::Day) => format!("{}Dutc", interval.0),
+            (_, 1, TermUnit::Week) => "1Wutc".to_string(),
+            (_, 1, TermUnit::Month) => "1Mutc".to_string(),
+            _ => {
+                return Err(ExchangeResponseError::interval(
+                    &ExchangeChoices::Bitget,
+                    &self.market_type,
+                ))
+            }
+        };
+        Ok(result)
+    }
+
+    fn parse_as_kline(&self, data: String) -> Vec<Kline> {
+        // Same for spot and perpetual: [time, open, high, low, close, base volume, ...] in strings
+        serde_json::from_str::<Response>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .data
+            .unwrap(/* Error handling has already been completed in `fetch()` */)
+            .iter()
+            .map(|raw| Kline {
+                unixtime_msec: raw[0].parse::<i64>().unwrap(),
+                o: raw[1].parse::<f64>().unwrap(),
+                h: raw[2].parse::<f64>().unwrap(),
+                l: raw[3].parse::<f64>().unwrap(),
+                c: raw[4].parse::<f64>().unwrap(),
+                v: raw[5].parse::<f64>().unwrap(),
+                extra: KlineExtra::default(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case(MarketType::Spot, DurationAndUnit::from_str("15min").unwrap(), "15min".to_string())]
+    #[case(MarketType::Spot, DurationAndUnit::from_str("4hour").unwrap(), "4h".to_string())]
+    #[case(MarketType::Perpetual, DurationAndUnit::from_str("15min").unwrap(), "15m".to_string())]
+    #[case(MarketType::Perpetual, DurationAndUnit::from_str("1hour").unwrap(), "1H".to_string())]
+    #[case(MarketType::Perpetual, DurationAndUnit::from_str("1day").unwrap(), "1Dutc".to_string())]
+    #[should_panic]
+    #[case(MarketType::Spot, DurationAndUnit::from_str("2hour").unwrap(), "panic".to_string())]
+    fn test_fit_interval_to_req(
+        #[case] market_type: MarketType,
+        #[case] input: DurationAndUnit,
+        #[case] expected: String,
+    ) {
+        let mut bitget = Bitget::new();
+        bitget.market_type = market_type;
+        assert_eq!(bitget.fit_interval_to_req(&input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline() {
+        let bitget = Bitget::new();
+
+        let input = r#"
+        {
+            "code": "00000",
+            "msg": "success",
+            "requestTime": 1695865615662,
+            "data": [
+                [
+                    "1695835800000",
+                    "26210.5",
+                    "26210.5",
+                    "26194.5",
+                    "26194.5",
+                    "26.26",
+                    "687897.63",
+                    "687897.63"
+                ],
+                [
+                    "1695836700000",
+                    "26194.5",
+                    "26220",
+                    "26190.1",
+                    "26215",
+                    "31.0091",
+                    "812605.35",
+                    "812605.35"
+                ]
+            ]
+        }"#
+        .to_string();
+        let result = bitget.parse_as_kline(input);
+        let expected = vec![
+            Kline {
+                unixtime_msec: 1695835800000,
+                o: 26210.5,
+                h: 26210.5,
+                l: 26194.5,
+                c: 26194.5,
+                v: 26.26,
+                extra: KlineExtra::default(),
+            },
+            Kline {
+                unixtime_msec: 1695836700000,
+                o: 26194.5,
+                h: 26220.0,
+                l: 26190.1,
+                c: 26215.0,
+                v: 31.0091,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(
+        r#"
+        {
+            "code": "00000",
+            "msg": "success",
+            "data": [
+                { "symbol": "BTCUSDT", "baseCoin": "BTC", "quoteCoin": "USDT", "status": "online" },
+                { "symbol": "LUNAUSDT", "baseCoin": "LUNA", "quoteCoin": "USDT", "status": "halt" }
+            ]
+        }"#
+    )]
+    #[case(
+        r#"
+        {
+            "code": "00000",
+            "msg": "success",
+            "data": [
+                {
+                    "symbol": "BTCUSDT",
+                    "baseCoin": "BTC",
+                    "quoteCoin": "USDT",
+                    "symbolType": "perpetual",
+                    "symbolStatus": "normal"
+                },
+                {
+                    "symbol": "LUNAUSDT",
+                    "baseCoin": "LUNA",
+                    "quoteCoin": "USDT",
+                    "symbolType": "perpetual",
+                    "symbolStatus": "off"
+                }
+            ]
+        }"#
+    )]
+    fn test_parse_as_listings(#[case] input: &str) {
+        assert_eq!(
+            Bitget::parse_as_listings(input).unwrap(),
+            vec![Listing::new("BTC", "USDT", "BTCUSDT", &[])]
+        );
+    }
+}
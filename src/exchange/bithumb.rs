@@ -0,0 +1,52 @@
+use anyhow::Error;
+use reqwest::blocking::Client;
+
+use crate::{args::*, exchange::*, unit::*};
+
+/// The v1 API is compatible with Upbit's one (same paths, parameters and responses).
+/// The legacy `/public/candlestick` was not used because it has no time parameters at all.
+const HOST: &str = "https://api.bithumb.com";
+
+#[derive(Debug, Clone)]
+pub struct Bithumb(Upbit);
+
+impl Bithumb {
+    pub fn new() -> Self {
+        Bithumb(Upbit::compatible(ExchangeChoices::Bithumb, HOST))
+    }
+}
+
+impl Retrieve for Bithumb {
+    fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        self.0.prepare(args)
+    }
+
+    fn fetch(&self, client: &Client) -> Result<String, Error> {
+        self.0.fetch(client)
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        self.0.list_symbols(client, market_type, contract)
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        self.0.fit_symbol_to_req(symbol)
+    }
+
+    fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
+        self.0.fit_interval_to_req(interval)
+    }
+
+    fn parse_as_kline(&self, data: String) -> Vec<Kline> {
+        self.0.parse_as_kline(data)
+    }
+
+    fn remove_unnecessary_raws(raws: Vec<Kline>, term_start: i64, term_end: i64) -> Vec<Kline> {
+        Upbit::remove_unnecessary_raws(raws, term_start, term_end)
+    }
+}
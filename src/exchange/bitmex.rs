@@ -1,19 +1,24 @@
-use std::collections::HashMap;
-
 use anyhow::{anyhow, Error};
 use chrono::{DateTime, TimeZone, Utc};
-use regex::Regex;
 use reqwest::blocking::Client;
 use serde::Deserialize;
 
 use crate::{args::*, error::*, exchange::*, unit::*};
 
+const ENDPOINT: &str = "https://www.bitmex.com/api/v1/trade/bucketed";
+const ENDPOINT_FUNDING: &str = "https://www.bitmex.com/api/v1/funding";
+/// There is no history of open interest, only the current one of the instrument
+const ENDPOINT_INSTRUMENT: &str = "https://www.bitmex.com/api/v1/instrument";
+
 #[derive(Debug, Clone)]
 pub struct Bitmex {
     params: Vec<(String, String)>,
     market_type: MarketType,
     endpoint: String,
     limit: i32,
+    /// Buckets are stamped at their end, so the timestamps are moved back by this to the opening time.
+    /// Also the current open interest is regarded as of the start of the interval, to be aligned with candles.
+    interval_msec: i64,
 }
 
 #[derive(Deserialize)]
@@ -28,13 +33,55 @@ struct ErrorInResponseOnError {
     name: String,
 }
 
+#[derive(Deserialize)]
+// The root of the API returns only its information
+struct ResponseOnTime {
+    timestamp: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FundingRate {
+    timestamp: String,
+    funding_rate: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Instrument {
+    timestamp: String,
+    /// In contracts
+    open_interest: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstrumentSpec {
+    is_quanto: bool,
+    is_inverse: bool,
+    /// Value of a contract in the smallest unit of the settlement currency (negative for inverse contracts),
+    /// per the price for linear contracts
+    multiplier: f64,
+    underlying_to_settle_multiplier: Option<f64>,
+    quote_to_settle_multiplier: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstrumentToList {
+    symbol: String,
+    underlying: String,
+    quote_currency: String,
+}
+
 impl Bitmex {
     pub fn new() -> Self {
         Bitmex {
             params: Vec::new(),
             market_type: MarketType::Perpetual,
-            endpoint: "https://www.bitmex.com/api/v1/trade/bucketed".to_string(),
+            endpoint: ENDPOINT.to_string(),
             limit: 1000,
+            interval_msec: 60 * 1000,
         }
     }
 
@@ -45,14 +92,63 @@ impl Bitmex {
     fn rfc3339_to_unixtime(rfc3339: String) -> i64 {
         rfc3339.parse::<DateTime<Utc>>().unwrap().timestamp_millis()
     }
+
+    /// The multipliers turn the value of a contract in the settlement currency into the quote
+    /// currency (linear, like `XBTUSDT`) or into the base currency (inverse, like `XBTUSD`).
+    fn contract_size(data: &str) -> Result<NativeVolume, Error> {
+        let instruments = serde_json::from_str::<Vec<InstrumentSpec>>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+        let spec = instruments.first().ok_or(ExchangeResponseError::symbol())?;
+
+        if spec.is_quanto {
+            return Err(anyhow!(
+                "BitMEX quanto contracts are worth neither the base nor the quote currency, so their volume cannot be converted."
+            ));
+        }
+
+        let settle_multiplier = match spec.is_inverse {
+            true => spec.underlying_to_settle_multiplier,
+            false => spec.quote_to_settle_multiplier,
+        }
+        .ok_or(ExchangeResponseError::unknown())?;
+
+        Ok(NativeVolume::Contracts {
+            size: (spec.multiplier / settle_multiplier).abs(),
+            inverse: spec.is_inverse,
+        })
+    }
+
+    fn parse_as_listings(&self, data: &str) -> Result<Vec<Listing>, Error> {
+        let instruments = serde_json::from_str::<Vec<InstrumentToList>>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+
+        Ok(instruments
+            .iter()
+            .map(|instrument| {
+                Listing::new(
+                    &instrument.underlying,
+                    &instrument.quote_currency,
+                    &instrument.symbol,
+                    BTC_AS_XBT,
+                )
+            })
+            .filter(|listing| {
+                self.fit_symbol_to_req(&listing.symbol())
+                    .is_ok_and(|symbol| symbol == listing.native)
+            })
+            .collect())
+    }
 }
 
 impl Retrieve for Bitmex {
     fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
-        if let MarketType::Spot = args.type_ {
+        if !matches!(args.type_, MarketType::Perpetual) {
             return Err(ExchangeResponseError::no_support_type());
         }
 
+        self.endpoint = ENDPOINT.to_string();
+        self.interval_msec = args.interval.to_msec();
+        // The period is of the opening times, while `startTime` and `endTime` filter the end of the buckets
         self.params = [
             (
                 "binSize".to_string(),
@@ -61,9 +157,28 @@ impl Retrieve for Bitmex {
             ("symbol".to_string(), self.fit_symbol_to_req(&args.symbol)?),
             (
                 "columns".to_string(),
-                "timestamp,open,high,low,close,volume".to_string(),
+                "timestamp,open,high,low,close,volume,trades,vwap,foreignNotional".to_string(),
             ),
             ("count".to_string(), self.limit.to_string()),
+            (
+                "startTime".to_string(),
+                Self::unixtime_to_rfc3339(args.term_start.unwrap() + self.interval_msec),
+            ),
+            (
+                "endTime".to_string(),
+                Self::unixtime_to_rfc3339(args.term_end.unwrap() + self.interval_msec),
+            ),
+        ]
+        .to_vec();
+
+        Ok(())
+    }
+
+    fn prepare_funding(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        self.endpoint = ENDPOINT_FUNDING.to_string();
+        self.params = [
+            ("symbol".to_string(), self.fit_symbol_to_req(&args.symbol)?),
+            ("count".to_string(), self.limit.to_string()),
             (
                 "startTime".to_string(),
                 Self::unixtime_to_rfc3339(args.term_start.unwrap()),
@@ -78,6 +193,24 @@ impl Retrieve for Bitmex {
         Ok(())
     }
 
+    fn prepare_open_interest(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        self.interval_msec = args.interval.to_msec();
+
+        let now = args.now().timestamp_millis();
+        if args.term_end.unwrap() < now - now % self.interval_msec {
+            return Err(ExchangeResponseError::no_history());
+        }
+
+        self.endpoint = ENDPOINT_INSTRUMENT.to_string();
+        self.params = [
+            ("symbol".to_string(), self.fit_symbol_to_req(&args.symbol)?),
+            ("columns".to_string(), "timestamp,openInterest".to_string()),
+        ]
+        .to_vec();
+
+        Ok(())
+    }
+
     fn fetch(&self, client: &Client) -> Result<String, Error> {
         let res = client
             .get(&self.endpoint)
@@ -99,18 +232,62 @@ impl Retrieve for Bitmex {
         Ok(res)
     }
 
-    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
-        let mut aliases = HashMap::new();
-        aliases.insert("BTC", "XBT");
+    fn server_time(
+        &self,
+        client: &Client,
+        _market_type: &MarketType,
+    ) -> Result<Option<i64>, Error> {
+        let res = client
+            .get("https://www.bitmex.com/api/v1")
+            .send()?
+            .json::<ResponseOnTime>()?;
+
+        Ok(Some(res.timestamp))
+    }
+
+    fn native_volume(&self, client: &Client, args: &ParsedArgs) -> Result<NativeVolume, Error> {
+        let res = client
+            .get(ENDPOINT_INSTRUMENT)
+            .query(&[
+                ("symbol", self.fit_symbol_to_req(&args.symbol)?),
+                (
+                    "columns",
+                    "isQuanto,isInverse,multiplier,underlyingToSettleMultiplier,quoteToSettleMultiplier"
+                        .to_string(),
+                ),
+            ])
+            .send()?
+            .text()?;
 
-        let re = Regex::new(r"^(.*?)/(.*?)$").unwrap();
-        let matches = re.captures(symbol).ok_or(anyhow!(
-            "The symbol pair provided is incorrectly formatted."
-        ))?;
+        Self::contract_size(&res)
+    }
 
-        let base = aliases.get(&matches[1]).unwrap_or(&&matches[1]).to_owned();
-        let quote = aliases.get(&matches[2]).unwrap_or(&&matches[2]).to_owned();
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        if !matches!(market_type, MarketType::Perpetual) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
 
+        let res = client
+            .get(ENDPOINT_INSTRUMENT)
+            .query(&[
+                // Perpetual contracts which are open
+                ("filter", r#"{"typ": "FFWCSX", "state": "Open"}"#),
+                ("columns", "symbol,underlying,quoteCurrency"),
+                ("count", "500"),
+            ])
+            .send()?
+            .text()?;
+
+        self.parse_as_listings(&res)
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        let (base, quote) = split_symbol(symbol, BTC_AS_XBT)?;
         Ok(format!("{}{}", base, quote))
     }
 
@@ -132,12 +309,47 @@ impl Retrieve for Bitmex {
             .map(|raw| Kline {
                 unixtime_msec: Self::rfc3339_to_unixtime(
                     raw.get("timestamp").unwrap().as_str().unwrap().to_owned(),
-                ),
+                ) - self.interval_msec,
                 o: raw.get("open").unwrap().as_f64().unwrap(),
                 h: raw.get("high").unwrap().as_f64().unwrap(),
                 l: raw.get("low").unwrap().as_f64().unwrap(),
                 c: raw.get("close").unwrap().as_f64().unwrap(),
                 v: raw.get("volume").unwrap().as_f64().unwrap(),
+                // `vwap` is `null` when there is no trade
+                extra: KlineExtra {
+                    quote_volume: raw.get("foreignNotional").and_then(|v| v.as_f64()),
+                    trades: raw.get("trades").and_then(|v| v.as_u64()),
+                    vwap: raw.get("vwap").and_then(|v| v.as_f64()),
+                    ..Default::default()
+                },
+            })
+            .collect()
+    }
+
+    fn parse_as_funding(&self, data: String) -> Vec<Funding> {
+        serde_json::from_str::<Vec<FundingRate>>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .into_iter()
+            .map(|funding| Funding {
+                unixtime_msec: Self::rfc3339_to_unixtime(funding.timestamp),
+                rate: funding.funding_rate,
+                mark_price: None,
+            })
+            .collect()
+    }
+
+    fn parse_as_open_interest(&self, data: String) -> Vec<OpenInterest> {
+        serde_json::from_str::<Vec<Instrument>>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .into_iter()
+            .map(|instrument| {
+                let unixtime_msec = Self::rfc3339_to_unixtime(instrument.timestamp);
+                OpenInterest {
+                    unixtime_msec: unixtime_msec - unixtime_msec % self.interval_msec,
+                    amount: instrument.open_interest,
+                    // `openValue` is in the smallest unit of the settlement currency (e.g. satoshi)
+                    value: None,
+                }
             })
             .collect()
     }
@@ -145,10 +357,12 @@ impl Retrieve for Bitmex {
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
 
     use rstest::*;
 
     use super::*;
+    use crate::{clock::*, pick::*};
 
     #[rstest]
     #[case("BTC/USDT", "XBTUSDT".to_string())]
@@ -184,26 +398,238 @@ mod tests {
             }
         ]"#
         .to_string();
+        // Stamped at the end of the buckets
         let result = bitmex.parse_as_kline(input);
         let expected = vec![
             Kline {
-                unixtime_msec: 1683040800000,
+                unixtime_msec: 1683040740000,
                 o: 1860.25,
                 h: 1860.95,
                 l: 1860.0,
                 c: 1860.55,
                 v: 2304.0,
+                extra: KlineExtra::default(),
             },
             Kline {
-                unixtime_msec: 1683040860000,
+                unixtime_msec: 1683040800000,
                 o: 1860.55,
                 h: 1861.95,
                 l: 1860.4,
                 c: 1860.55,
                 v: 1723.0,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_funding() {
+        let bitmex = Bitmex::new();
+
+        let input = r#"
+        [
+            {
+                "timestamp": "2023-11-01T04:00:00.000Z",
+                "symbol": "XBTUSD",
+                "fundingInterval": "2000-01-01T08:00:00.000Z",
+                "fundingRate": 0.0001,
+                "fundingRateDaily": 0.0003
+            },
+            {
+                "timestamp": "2023-11-01T12:00:00.000Z",
+                "symbol": "XBTUSD",
+                "fundingInterval": "2000-01-01T08:00:00.000Z",
+                "fundingRate": -0.000052,
+                "fundingRateDaily": -0.000156
+            }
+        ]"#
+        .to_string();
+        let result = bitmex.parse_as_funding(input);
+        let expected = vec![
+            Funding {
+                unixtime_msec: 1698811200000,
+                rate: 0.0001,
+                mark_price: None,
+            },
+            Funding {
+                unixtime_msec: 1698840000000,
+                rate: -0.000052,
+                mark_price: None,
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    fn args_open_interest(term_start: i64, term_end: i64) -> ParsedArgs {
+        ParsedArgs {
+            exchange: Exchange::Bitmex(Bitmex::new()),
+            symbol: "BTC/USD".to_string(),
+            type_: MarketType::Perpetual,
+            kind: DataKind::OpenInterest,
+            term_start: Some(term_start),
+            term_end: Some(term_end),
+            interval: DurationAndUnit::from_str("1hour").unwrap(),
+            pick: vec![Pick::T, Pick::Oi],
+            // 2023-11-01T00:30:00Z
+            clock: Clock::Fixed(Utc.timestamp_millis_opt(1698798600000).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    // Until 2023-11-01T01:00:00Z, which includes the present
+    #[case(1698800400000, true)]
+    // Until 2023-10-31T00:00:00Z
+    #[case(1698710400000, false)]
+    fn test_prepare_open_interest_no_history(#[case] term_end: i64, #[case] expected: bool) {
+        let mut bitmex = Bitmex::new();
+        let args = args_open_interest(1698710400000 - 24 * 60 * 60 * 1000, term_end);
+        assert_eq!(bitmex.prepare_open_interest(&args).is_ok(), expected);
+    }
+
+    #[test]
+    fn test_parse_as_open_interest() {
+        let mut bitmex = Bitmex::new();
+        bitmex.interval_msec = 60 * 60 * 1000;
+
+        let input = r#"
+        [
+            {
+                "symbol": "XBTUSD",
+                "timestamp": "2023-11-01T00:31:05.123Z",
+                "openInterest": 512345600
+            }
+        ]"#
+        .to_string();
+        let result = bitmex.parse_as_open_interest(input);
+        let expected = vec![OpenInterest {
+            // Aligned with the candle of 2023-11-01T00:00:00Z
+            unixtime_msec: 1698796800000,
+            amount: 512345600.0,
+            value: None,
+        }];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline_extra() {
+        let bitmex = Bitmex::new();
+
+        let input = r#"
+        [
+            {
+                "timestamp": "2023-05-02T15:20:00.000Z",
+                "symbol": "XBTUSD",
+                "open": 28540.5,
+                "high": 28551,
+                "low": 28540.5,
+                "close": 28551,
+                "trades": 12,
+                "volume": 41200,
+                "vwap": 28545.3129,
+                "foreignNotional": 41200
+            },
+            {
+                "timestamp": "2023-05-02T15:21:00.000Z",
+                "symbol": "XBTUSD",
+                "open": 28551,
+                "high": 28551,
+                "low": 28551,
+                "close": 28551,
+                "trades": 0,
+                "volume": 0,
+                "vwap": null,
+                "foreignNotional": 0
+            }
+        ]"#
+        .to_string();
+        // Stamped at the end of the buckets
+        let result = bitmex.parse_as_kline(input);
+        let expected = vec![
+            Kline {
+                unixtime_msec: 1683040740000,
+                o: 28540.5,
+                h: 28551.0,
+                l: 28540.5,
+                c: 28551.0,
+                v: 41200.0,
+                extra: KlineExtra {
+                    quote_volume: Some(41200.0),
+                    trades: Some(12),
+                    vwap: Some(28545.3129),
+                    ..Default::default()
+                },
+            },
+            Kline {
+                unixtime_msec: 1683040800000,
+                o: 28551.0,
+                h: 28551.0,
+                l: 28551.0,
+                c: 28551.0,
+                v: 0.0,
+                extra: KlineExtra {
+                    quote_volume: Some(0.0),
+                    trades: Some(0),
+                    ..Default::default()
+                },
             },
         ];
 
         assert_eq!(result, expected);
     }
+
+    #[rstest]
+    // XBTUSD, a dollar per contract
+    #[case(r#"[{"symbol": "XBTUSD", "isQuanto": false, "isInverse": true, "multiplier": -100000000, "underlyingToSettleMultiplier": -100000000, "quoteToSettleMultiplier": null}]"#, Some(NativeVolume::Contracts { size: 1.0, inverse: true }))]
+    // XBTUSDT, a millionth of a bitcoin per contract
+    #[case(r#"[{"symbol": "XBTUSDT", "isQuanto": false, "isInverse": false, "multiplier": 1, "underlyingToSettleMultiplier": null, "quoteToSettleMultiplier": 1000000}]"#, Some(NativeVolume::Contracts { size: 0.000001, inverse: false }))]
+    // ETHUSD
+    #[case(r#"[{"symbol": "ETHUSD", "isQuanto": true, "isInverse": false, "multiplier": 100, "underlyingToSettleMultiplier": null, "quoteToSettleMultiplier": null}]"#, None)]
+    #[case("[]", None)]
+    fn test_contract_size(#[case] input: &str, #[case] expected: Option<NativeVolume>) {
+        assert_eq!(Bitmex::contract_size(input).ok(), expected);
+    }
+
+    #[test]
+    fn test_parse_as_listings() {
+        // Only the ones named after the pair can be retrieved
+        let input = r#"
+        [
+            {
+                "symbol": "XBTUSD",
+                "underlying": "XBT",
+                "quoteCurrency": "USD"
+            },
+            {
+                "symbol": "ETHUSDT",
+                "underlying": "ETH",
+                "quoteCurrency": "USDT"
+            },
+            {
+                "symbol": "XBTUSDTZ23",
+                "underlying": "XBT",
+                "quoteCurrency": "USDT"
+            }
+        ]"#;
+
+        let bitmex = Bitmex::new();
+        let result: Vec<(String, String)> = bitmex
+            .parse_as_listings(input)
+            .unwrap()
+            .into_iter()
+            .map(|listing| (listing.symbol(), listing.native))
+            .collect();
+
+        assert_eq!(
+            result,
+            vec![
+                ("BTC/USD".to_string(), "XBTUSD".to_string()),
+                ("ETH/USDT".to_string(), "ETHUSDT".to_string()),
+            ]
+        );
+    }
 }
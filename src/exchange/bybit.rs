@@ -1,35 +1,83 @@
 use anyhow::{anyhow, Error};
-use regex::Regex;
+use chrono::Datelike;
 use reqwest::blocking::Client;
 use serde::Deserialize;
 
 use crate::{args::*, error::*, exchange::*, unit::*};
 
+const ENDPOINT_BASE: &str = "https://api.bybit.com/v5/market";
+const LIMIT_FUNDING: i32 = 200;
+const LIMIT_OPEN_INTEREST: i32 = 200;
+
+/// Futures month codes from January to December
+const MONTH_CODES: [char; 12] = ['F', 'G', 'H', 'J', 'K', 'M', 'N', 'Q', 'U', 'V', 'X', 'Z'];
+
 #[derive(Debug, Clone)]
 pub struct Bybit {
     params: Vec<(String, String)>,
     market_type: MarketType,
     endpoint: String,
     limit: i32,
+    contract: Contract,
+    price_type: PriceType,
 }
 
 #[derive(Deserialize)]
-struct Response {
+// Generic over the items of `list`, which are arrays for klines and objects for funding rates
+struct Response<T> {
     #[serde(alias = "retCode")]
     ret_code: serde_json::Number,
     #[serde(alias = "retMsg")]
     ret_msg: String,
-    result: ResultInResponse,
+    result: ResultInResponse<T>,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnTime {
+    time: i64,
 }
 
 #[derive(Deserialize)]
 // In case of error, to be empty `{}` (why all fields are optional)
-struct ResultInResponse {
+struct ResultInResponse<T> {
     #[allow(dead_code)]
     category: Option<String>,
     #[allow(dead_code)]
     symbol: Option<String>,
-    list: Option<Vec<Vec<serde_json::Value>>>,
+    list: Option<Vec<T>>,
+    /// Empty on the last page
+    #[serde(alias = "nextPageCursor")]
+    next_page_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FundingRate {
+    funding_rate: String,
+    funding_rate_timestamp: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OpenInterestInList {
+    /// In the base currency, or in USD (contracts) on inverse contracts
+    open_interest: String,
+    timestamp: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstrumentInList {
+    symbol: String,
+    /// Derivatives only, `LinearPerpetual`, `InverseFutures` and so on
+    #[serde(default)]
+    contract_type: String,
+    status: String,
+    base_coin: String,
+    quote_coin: String,
+    /// Derivatives only, `0` for perpetuals
+    #[serde(default)]
+    delivery_time: String,
 }
 
 impl Bybit {
@@ -37,22 +85,101 @@ impl Bybit {
         Bybit {
             params: Vec::new(),
             market_type: MarketType::Spot,
-            endpoint: "https://api.bybit.com/v5/market/kline".to_string(),
+            endpoint: format!("{}/kline", ENDPOINT_BASE),
             limit: 200,
+            contract: Contract::default(),
+            price_type: PriceType::Last,
+        }
+    }
+
+    fn category(market_type: &MarketType, margin: &Option<Margin>) -> Result<&'static str, Error> {
+        match (market_type, margin) {
+            (MarketType::Spot, _) => Ok("spot"),
+            (MarketType::Perpetual, None | Some(Margin::Linear)) => Ok("linear"),
+            (MarketType::Perpetual | MarketType::Futures, Some(Margin::Inverse)) => Ok("inverse"),
+            (MarketType::Futures, _) => Err(anyhow!(
+                "Bybit supports only inverse futures, use `--margin inverse`."
+            )),
+            (MarketType::Option, _) => Err(ExchangeResponseError::no_support_type()),
         }
     }
+
+    /// A page of `instruments-info` and the cursor of the next page,
+    /// `self` is of the market type and the margin to list.
+    fn parse_as_listings(&self, data: &str) -> Result<(Vec<Listing>, Option<String>), Error> {
+        let response = serde_json::from_str::<Response<InstrumentInList>>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+        if response.ret_code.as_i64() != Some(0) {
+            return Err(ExchangeResponseError::wrap_error(response.ret_msg));
+        }
+
+        let listings = response
+            .result
+            .list
+            .unwrap_or_default()
+            .iter()
+            .filter(|instrument| instrument.status == "Trading")
+            .filter_map(|instrument| {
+                let listing = Listing::new(
+                    &instrument.base_coin,
+                    &instrument.quote_coin,
+                    &instrument.symbol,
+                    &[],
+                );
+                let listing = match (&self.market_type, instrument.contract_type.as_str()) {
+                    (MarketType::Spot, _)
+                    | (MarketType::Perpetual, "LinearPerpetual" | "InversePerpetual") => listing,
+                    (MarketType::Futures, "InverseFutures") => {
+                        listing.expiring_at(instrument.delivery_time.parse::<i64>().ok()?)
+                    }
+                    _ => return None,
+                };
+
+                // Names of futures depend on the expiry
+                let bybit = Bybit {
+                    contract: Contract {
+                        expiry: listing.expiry,
+                        ..self.contract.clone()
+                    },
+                    ..self.clone()
+                };
+                (bybit.fit_symbol_to_req(&listing.symbol()).ok()? == instrument.symbol)
+                    .then_some(listing)
+            })
+            .collect();
+
+        let cursor = response
+            .result
+            .next_page_cursor
+            .filter(|cursor| !cursor.is_empty());
+
+        Ok((listings, cursor))
+    }
 }
 
 impl Retrieve for Bybit {
     fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        let category = Self::category(&args.type_, &args.contract.margin)?;
+
+        let path = match (&args.type_, &args.price_type) {
+            (_, PriceType::Last) => "kline",
+            (MarketType::Spot, _) => {
+                return Err(anyhow!(
+                    "Bybit does not support `--price-type` other than `last` with `--type spot`."
+                ))
+            }
+            (_, PriceType::Mark) => "mark-price-kline",
+            (_, PriceType::Index) => "index-price-kline",
+            (_, PriceType::Premium) => "premium-index-price-kline",
+        };
+
+        self.contract = args.contract.clone();
+        self.market_type = args.type_.clone();
+        self.price_type = args.price_type.clone();
+        self.endpoint = format!("{}/{}", ENDPOINT_BASE, path);
+
         self.params = [
-            (
-                "category".to_string(),
-                match args.type_ {
-                    MarketType::Spot => "spot".to_string(),
-                    MarketType::Perpetual => "linear".to_string(),
-                },
-            ),
+            ("category".to_string(), category.to_string()),
             ("symbol".to_string(), self.fit_symbol_to_req(&args.symbol)?),
             (
                 "interval".to_string(),
@@ -64,11 +191,64 @@ impl Retrieve for Bybit {
         ]
         .to_vec();
 
-        match args.type_ {
-            MarketType::Spot => self.market_type = MarketType::Spot,
-            MarketType::Perpetual => self.market_type = MarketType::Perpetual,
+        Ok(())
+    }
+
+    fn prepare_funding(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        let category = match args.contract.margin {
+            Some(Margin::Inverse) => "inverse",
+            _ => "linear",
         };
 
+        self.market_type = args.type_.clone();
+        self.endpoint = format!("{}/funding/history", ENDPOINT_BASE);
+        self.params = [
+            ("category".to_string(), category.to_string()),
+            ("symbol".to_string(), self.fit_symbol_to_req(&args.symbol)?),
+            (
+                "startTime".to_string(),
+                args.term_start.unwrap().to_string(),
+            ),
+            ("endTime".to_string(), args.term_end.unwrap().to_string()),
+            ("limit".to_string(), LIMIT_FUNDING.to_string()),
+        ]
+        .to_vec();
+
+        Ok(())
+    }
+
+    fn prepare_open_interest(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        let category = match args.contract.margin {
+            Some(Margin::Inverse) => "inverse",
+            _ => "linear",
+        };
+
+        let interval_time = match (args.interval.0, &args.interval.1) {
+            (5 | 15 | 30, TermUnit::Min) => format!("{}min", args.interval.0),
+            (1 | 4, TermUnit::Hour) => format!("{}h", args.interval.0),
+            (1, TermUnit::Day) => "1d".to_string(),
+            _ => {
+                return Err(ExchangeResponseError::interval_of(
+                    &["5min", "15min", "30min", "1hour", "4hour", "1day"].map(String::from),
+                ))
+            }
+        };
+
+        self.market_type = args.type_.clone();
+        self.endpoint = format!("{}/open-interest", ENDPOINT_BASE);
+        self.params = [
+            ("category".to_string(), category.to_string()),
+            ("symbol".to_string(), self.fit_symbol_to_req(&args.symbol)?),
+            ("intervalTime".to_string(), interval_time),
+            (
+                "startTime".to_string(),
+                args.term_start.unwrap().to_string(),
+            ),
+            ("endTime".to_string(), args.term_end.unwrap().to_string()),
+            ("limit".to_string(), LIMIT_OPEN_INTEREST.to_string()),
+        ]
+        .to_vec();
+
         Ok(())
     }
 
@@ -79,7 +259,7 @@ impl Retrieve for Bybit {
             .send()?
             .text()?;
 
-        let response = serde_json::from_str::<Response>(&res)
+        let response = serde_json::from_str::<Response<serde_json::Value>>(&res)
             .expect("Unexpected error! Failed to parse response (for error code) to json.");
         match response
             .ret_code
@@ -104,14 +284,90 @@ impl Retrieve for Bybit {
         Ok(res)
     }
 
+    fn server_time(
+        &self,
+        client: &Client,
+        _market_type: &MarketType,
+    ) -> Result<Option<i64>, Error> {
+        let res = client
+            .get("https://api.bybit.com/v5/market/time")
+            .send()?
+            .json::<ResponseOnTime>()?;
+
+        Ok(Some(res.time))
+    }
+
+    fn native_volume(&self, _client: &Client, args: &ParsedArgs) -> Result<NativeVolume, Error> {
+        // The size is not in the instruments info, but fixed by the category:
+        // a coin for linear contracts and a dollar for inverse ones
+        match (&args.type_, &args.contract.margin) {
+            (MarketType::Spot, _) => Ok(NativeVolume::Base),
+            (_, Some(Margin::Inverse)) => Ok(NativeVolume::Contracts {
+                size: 1.0,
+                inverse: true,
+            }),
+            _ => Ok(NativeVolume::Contracts {
+                size: 1.0,
+                inverse: false,
+            }),
+        }
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        let bybit = Bybit {
+            market_type: market_type.clone(),
+            contract: contract.clone(),
+            ..Bybit::new()
+        };
+        let category = Self::category(market_type, &contract.margin)?;
+
+        let mut listings = Vec::new();
+        let mut cursor = None;
+        loop {
+            let mut params = vec![
+                ("category", category.to_string()),
+                ("limit", "1000".to_string()),
+            ];
+            if let Some(cursor) = cursor {
+                params.push(("cursor", cursor));
+            }
+
+            let res = client
+                .get(format!("{}/instruments-info", ENDPOINT_BASE))
+                .query(&params)
+                .send()?
+                .text()?;
+            let (page, next) = bybit.parse_as_listings(&res)?;
+            listings.extend(page);
+
+            match next {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(listings)
+    }
+
     fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
-        // Same code as Binance, so the test already exists
+        let (base, quote) = split_symbol(symbol, &[])?;
 
-        let re = Regex::new(r"^(.*?)/(.*?)$").unwrap();
-        let matches = re.captures(symbol).ok_or(anyhow!(
-            "The symbol pair provided is incorrectly formatted."
-        ))?;
-        Ok(format!("{}{}", &matches[1], &matches[2]))
+        match (&self.market_type, &self.contract.expiry) {
+            // Inverse futures are named with the month code and the year like `BTCUSDZ23`
+            (MarketType::Futures, Some(expiry)) => Ok(format!(
+                "{}{}{}{}",
+                base,
+                quote,
+                MONTH_CODES[expiry.month0() as usize],
+                expiry.format("%y")
+            )),
+            _ => Ok(format!("{}{}", base, quote)),
+        }
     }
 
     fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
@@ -142,7 +398,7 @@ impl Retrieve for Bybit {
     }
 
     fn parse_as_kline(&self, data: String) -> Vec<Kline> {
-        serde_json::from_str::<Response>(&data)
+        serde_json::from_str::<Response<Vec<serde_json::Value>>>(&data)
             .expect("Unexpected error! Failed to parse response to json.")
             .result
             .list
@@ -154,7 +410,53 @@ impl Retrieve for Bybit {
                 h: raw[2].as_str().unwrap().to_owned().parse::<f64>().unwrap(),
                 l: raw[3].as_str().unwrap().to_owned().parse::<f64>().unwrap(),
                 c: raw[4].as_str().unwrap().to_owned().parse::<f64>().unwrap(),
-                v: raw[5].as_str().unwrap().to_owned().parse::<f64>().unwrap(),
+                // Other than the last price, there is no volume (output as null by `retrieve()`)
+                v: match self.price_type {
+                    PriceType::Last => raw[5].as_str().unwrap().to_owned().parse::<f64>().unwrap(),
+                    _ => 0.0,
+                },
+                extra: KlineExtra {
+                    quote_volume: match self.price_type {
+                        PriceType::Last => raw
+                            .get(6)
+                            .and_then(|turnover| turnover.as_str())
+                            .and_then(|turnover| turnover.parse::<f64>().ok()),
+                        _ => None,
+                    },
+                    ..Default::default()
+                },
+            })
+            .collect()
+    }
+
+    fn parse_as_funding(&self, data: String) -> Vec<Funding> {
+        // Newest first
+        serde_json::from_str::<Response<FundingRate>>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .result
+            .list
+            .unwrap(/* Error handling has already been completed in `fetch()` */)
+            .iter()
+            .map(|funding| Funding {
+                unixtime_msec: funding.funding_rate_timestamp.parse::<i64>().unwrap(),
+                rate: funding.funding_rate.parse::<f64>().unwrap(),
+                mark_price: None,
+            })
+            .collect()
+    }
+
+    fn parse_as_open_interest(&self, data: String) -> Vec<OpenInterest> {
+        // Newest first
+        serde_json::from_str::<Response<OpenInterestInList>>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .result
+            .list
+            .unwrap(/* Error handling has already been completed in `fetch()` */)
+            .iter()
+            .map(|open_interest| OpenInterest {
+                unixtime_msec: open_interest.timestamp.parse::<i64>().unwrap(),
+                amount: open_interest.open_interest.parse::<f64>().unwrap(),
+                value: None,
             })
             .collect()
     }
@@ -164,10 +466,28 @@ impl Retrieve for Bybit {
 mod tests {
     use std::str::FromStr;
 
+    use chrono::NaiveDate;
     use rstest::*;
 
     use super::*;
 
+    #[rstest]
+    #[case(MarketType::Spot, None, "BTC/USDT", "BTCUSDT".to_string())]
+    #[case(MarketType::Perpetual, None, "BTC/USD", "BTCUSD".to_string())]
+    #[case(MarketType::Futures, NaiveDate::from_ymd_opt(2023, 12, 29), "BTC/USD", "BTCUSDZ23".to_string())]
+    #[case(MarketType::Futures, NaiveDate::from_ymd_opt(2024, 3, 29), "ETH/USD", "ETHUSDH24".to_string())]
+    fn test_fit_symbol_to_req(
+        #[case] market_type: MarketType,
+        #[case] expiry: Option<NaiveDate>,
+        #[case] input: &str,
+        #[case] expected: String,
+    ) {
+        let mut bybit = Bybit::new();
+        bybit.market_type = market_type;
+        bybit.contract.expiry = expiry;
+        assert_eq!(bybit.fit_symbol_to_req(input).unwrap(), expected);
+    }
+
     #[rstest]
     #[case(DurationAndUnit::from_str("1min").unwrap(), "1".to_string())]
     #[case(DurationAndUnit::from_str("15min").unwrap(), "15".to_string())]
@@ -230,6 +550,10 @@ mod tests {
                 l: 28548.75,
                 c: 28548.76,
                 v: 13.05842,
+                extra: KlineExtra {
+                    quote_volume: Some(372910.44913563),
+                    ..Default::default()
+                },
             },
             Kline {
                 unixtime_msec: 1682921700000,
@@ -238,9 +562,269 @@ mod tests {
                 l: 28487.31,
                 c: 28555.21,
                 v: 31.123026,
+                extra: KlineExtra {
+                    quote_volume: Some(887365.25928333),
+                    ..Default::default()
+                },
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline_mark_price() {
+        let mut bybit = Bybit::new();
+        bybit.price_type = PriceType::Mark;
+
+        let input = r#"
+        {
+            "retCode": 0,
+            "retMsg": "OK",
+            "result": {
+                "symbol": "BTCUSDT",
+                "category": "linear",
+                "list": [
+                    [
+                        "1682922600000",
+                        "28560.12",
+                        "28566.3",
+                        "28551.47",
+                        "28552.9"
+                    ]
+                ]
+            },
+            "retExtInfo": {},
+            "time": 1682922881591
+        }"#
+        .to_string();
+        let result = bybit.parse_as_kline(input);
+        let expected = vec![Kline {
+            unixtime_msec: 1682922600000,
+            o: 28560.12,
+            h: 28566.3,
+            l: 28551.47,
+            c: 28552.9,
+            v: 0.0,
+            extra: KlineExtra::default(),
+        }];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_funding() {
+        let bybit = Bybit::new();
+
+        let input = r#"
+        {
+            "retCode": 0,
+            "retMsg": "OK",
+            "result": {
+                "category": "linear",
+                "list": [
+                    {
+                        "symbol": "BTCUSDT",
+                        "fundingRate": "0.0001",
+                        "fundingRateTimestamp": "1698796800000"
+                    },
+                    {
+                        "symbol": "BTCUSDT",
+                        "fundingRate": "-0.00002477",
+                        "fundingRateTimestamp": "1698768000000"
+                    }
+                ]
+            },
+            "retExtInfo": {},
+            "time": 1698800000000
+        }"#
+        .to_string();
+        let result = bybit.parse_as_funding(input);
+        let expected = vec![
+            Funding {
+                unixtime_msec: 1698796800000,
+                rate: 0.0001,
+                mark_price: None,
+            },
+            Funding {
+                unixtime_msec: 1698768000000,
+                rate: -0.00002477,
+                mark_price: None,
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_open_interest() {
+        let bybit = Bybit::new();
+
+        let input = r#"
+        {
+            "retCode": 0,
+            "retMsg": "OK",
+            "result": {
+                "symbol": "BTCUSDT",
+                "category": "linear",
+                "list": [
+                    {
+                        "openInterest": "52143.27100000",
+                        "timestamp": "1698796800000"
+                    },
+                    {
+                        "openInterest": "52010.88200000",
+                        "timestamp": "1698793200000"
+                    }
+                ],
+                "nextPageCursor": "lastid%3D123456"
+            },
+            "retExtInfo": {},
+            "time": 1698798600000
+        }"#
+        .to_string();
+        let result = bybit.parse_as_open_interest(input);
+        let expected = vec![
+            OpenInterest {
+                unixtime_msec: 1698796800000,
+                amount: 52143.271,
+                value: None,
+            },
+            OpenInterest {
+                unixtime_msec: 1698793200000,
+                amount: 52010.882,
+                value: None,
             },
         ];
 
         assert_eq!(result, expected);
     }
+
+    #[rstest]
+    #[case(MarketType::Spot, None, vec![("BTC/USDT", "BTCUSDT")])]
+    #[case(MarketType::Perpetual, None, vec![("BTC/USDT", "BTCUSDT")])]
+    #[case(MarketType::Perpetual, Some(Margin::Inverse), vec![("BTC/USD", "BTCUSD")])]
+    #[case(MarketType::Futures, Some(Margin::Inverse), vec![("BTC/USD", "BTCUSDZ23")])]
+    fn test_parse_as_listings(
+        #[case] market_type: MarketType,
+        #[case] margin: Option<Margin>,
+        #[case] expected: Vec<(&str, &str)>,
+    ) {
+        let input = match (&market_type, &margin) {
+            (MarketType::Spot, _) => {
+                r#"
+                {
+                    "retCode": 0,
+                    "retMsg": "OK",
+                    "result": {
+                        "category": "spot",
+                        "list": [
+                            {
+                                "symbol": "BTCUSDT",
+                                "baseCoin": "BTC",
+                                "quoteCoin": "USDT",
+                                "status": "Trading"
+                            }
+                        ]
+                    }
+                }"#
+            }
+            // USDC perpetuals are named like `BTCPERP`, which cannot be retrieved
+            (_, None) => {
+                r#"
+                {
+                    "retCode": 0,
+                    "retMsg": "OK",
+                    "result": {
+                        "category": "linear",
+                        "list": [
+                            {
+                                "symbol": "BTCUSDT",
+                                "contractType": "LinearPerpetual",
+                                "status": "Trading",
+                                "baseCoin": "BTC",
+                                "quoteCoin": "USDT",
+                                "deliveryTime": "0"
+                            },
+                            {
+                                "symbol": "BTCPERP",
+                                "contractType": "LinearPerpetual",
+                                "status": "Trading",
+                                "baseCoin": "BTC",
+                                "quoteCoin": "USDC",
+                                "deliveryTime": "0"
+                            },
+                            {
+                                "symbol": "BTC-29DEC23",
+                                "contractType": "LinearFutures",
+                                "status": "Trading",
+                                "baseCoin": "BTC",
+                                "quoteCoin": "USDC",
+                                "deliveryTime": "1703836800000"
+                            }
+                        ],
+                        "nextPageCursor": ""
+                    }
+                }"#
+            }
+            _ => {
+                r#"
+                {
+                    "retCode": 0,
+                    "retMsg": "OK",
+                    "result": {
+                        "category": "inverse",
+                        "list": [
+                            {
+                                "symbol": "BTCUSD",
+                                "contractType": "InversePerpetual",
+                                "status": "Trading",
+                                "baseCoin": "BTC",
+                                "quoteCoin": "USD",
+                                "deliveryTime": "0"
+                            },
+                            {
+                                "symbol": "BTCUSDZ23",
+                                "contractType": "InverseFutures",
+                                "status": "Trading",
+                                "baseCoin": "BTC",
+                                "quoteCoin": "USD",
+                                "deliveryTime": "1703836800000"
+                            },
+                            {
+                                "symbol": "BTCUSDU23",
+                                "contractType": "InverseFutures",
+                                "status": "Closed",
+                                "baseCoin": "BTC",
+                                "quoteCoin": "USD",
+                                "deliveryTime": "1695974400000"
+                            }
+                        ],
+                        "nextPageCursor": ""
+                    }
+                }"#
+            }
+        };
+
+        let bybit = Bybit {
+            market_type,
+            contract: Contract {
+                margin,
+                ..Default::default()
+            },
+            ..Bybit::new()
+        };
+        let (result, cursor) = bybit.parse_as_listings(input).unwrap();
+        let result: Vec<(String, String)> = result
+            .into_iter()
+            .map(|listing| (listing.symbol(), listing.native))
+            .collect();
+        let expected: Vec<(String, String)> = expected
+            .into_iter()
+            .map(|(symbol, native)| (symbol.to_string(), native.to_string()))
+            .collect();
+
+        assert_eq!(result, expected);
+        assert_eq!(cursor, None);
+    }
 }
@@ -0,0 +1,315 @@
+use anyhow::{anyhow, Error};
+use chrono::{TimeZone, Utc};
+use regex::Regex;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use crate::{args::*, error::*, exchange::*, unit::*};
+
+const ENDPOINT_FRAME: &str = "https://api.exchange.coinbase.com/products/{product_id}/candles";
+const ENDPOINT_PRODUCTS: &str = "https://api.exchange.coinbase.com/products";
+const LIMIT: i64 = 300;
+
+#[derive(Debug, Clone)]
+pub struct Coinbase {
+    params: Vec<(String, String)>,
+    market_type: MarketType,
+    endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnTime {
+    epoch: f64,
+}
+
+#[derive(Deserialize)]
+struct ProductToList {
+    /// Like `BTC-USD`
+    id: String,
+    base_currency: String,
+    quote_currency: String,
+    /// `online`, `delisted` and so on
+    status: String,
+    trading_disabled: bool,
+}
+
+impl Coinbase {
+    pub fn new() -> Self {
+        Coinbase {
+            params: Vec::new(),
+            market_type: MarketType::Spot,
+            endpoint: String::new(),
+        }
+    }
+
+    fn unixtime_to_rfc3339(unixtime: i64) -> String {
+        Utc.timestamp_millis_opt(unixtime).unwrap().to_rfc3339()
+    }
+
+    fn parse_as_listings(data: &str) -> Result<Vec<Listing>, Error> {
+        if let Ok(response) = serde_json::from_str::<ResponseOnError>(data) {
+            return Err(ExchangeResponseError::wrap_error(response.message));
+        }
+
+        let products = serde_json::from_str::<Vec<ProductToList>>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+
+        Ok(products
+            .iter()
+            .filter(|product| product.status == "online" && !product.trading_disabled)
+            .map(|product| {
+                Listing::new(
+                    &product.base_currency,
+                    &product.quote_currency,
+                    &product.id,
+                    &[],
+                )
+            })
+            .collect())
+    }
+}
+
+impl Retrieve for Coinbase {
+    // Coinbase's timestamps are all non-millisec
+
+    fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        if !matches!(args.type_, MarketType::Spot) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        // Coinbase rejects the request itself when the period contains more than `LIMIT` candles
+        // (instead of returning only some of them), and returns data in the newest first order,
+        // so the period is cut from `term_end`
+        let term_end = args.term_end.unwrap();
+        let term_start = args
+            .term_start
+            .unwrap()
+            .max(term_end - (LIMIT - 1) * args.interval.to_msec());
+
+        self.params = [
+            (
+                "granularity".to_string(),
+                self.fit_interval_to_req(&args.interval)?,
+            ),
+            ("start".to_string(), Self::unixtime_to_rfc3339(term_start)),
+            ("end".to_string(), Self::unixtime_to_rfc3339(term_end)),
+        ]
+        .to_vec();
+
+        self.endpoint =
+            ENDPOINT_FRAME.replace("{product_id}", &self.fit_symbol_to_req(&args.symbol)?);
+
+        Ok(())
+    }
+
+    fn fetch(&self, client: &Client) -> Result<String, Error> {
+        let res = client
+            .get(&self.endpoint)
+            .query(&self.params)
+            // Requests without `User-Agent` are rejected
+            .header("User-Agent", "ro-soku")
+            .send()?
+            .text()?;
+
+        if let Ok(response) = serde_json::from_str::<ResponseOnError>(&res) {
+            match response.message.as_str() {
+                "NotFound" => return Err(ExchangeResponseError::symbol()),
+                "Unsupported granularity" => {
+                    return Err(ExchangeResponseError::interval(
+                        &ExchangeChoices::Coinbase,
+                        &self.market_type,
+                    ))
+                }
+                message if message.contains("rate limit") => {
+                    return Err(ExchangeResponseError::too_many_requests())
+                }
+                _ => return Err(ExchangeResponseError::wrap_error(response.message)),
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn server_time(
+        &self,
+        client: &Client,
+        _market_type: &MarketType,
+    ) -> Result<Option<i64>, Error> {
+        let res = client
+            .get("https://api.exchange.coinbase.com/time")
+            .header("User-Agent", "ro-soku")
+            .send()?
+            .json::<ResponseOnTime>()?;
+
+        Ok(Some((res.epoch * 1000.0) as i64))
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        if !matches!(market_type, MarketType::Spot) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        let res = client
+            .get(ENDPOINT_PRODUCTS)
+            // Requests without `User-Agent` are rejected
+            .header("User-Agent", "ro-soku")
+            .send()?
+            .text()?;
+
+        Self::parse_as_listings(&res)
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        // Almost same code as Binance, so the test already exists
+
+        let re = Regex::new(r"^(.*?)/(.*?)$").unwrap();
+        let matches = re.captures(symbol).ok_or(anyhow!(
+            "The symbol pair provided is incorrectly formatted."
+        ))?;
+        Ok(format!("{}-{}", &matches[1], &matches[2]))
+    }
+
+    fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
+        // Only these six are allowed in seconds
+        let result = match (interval.0, &interval.1) {
+            (1, TermUnit::Min) => "60",
+            (5, TermUnit::Min) => "300",
+            (15, TermUnit::Min) => "900",
+            (1, TermUnit::Hour) => "3600",
+            (6, TermUnit::Hour) => "21600",
+            (1, TermUnit::Day) => "86400",
+            _ => {
+                return Err(ExchangeResponseError::interval(
+                    &ExchangeChoices::Coinbase,
+                    &self.market_type,
+                ))
+            }
+        };
+        Ok(result.to_string())
+    }
+
+    fn parse_as_kline(&self, data: String) -> Vec<Kline> {
+        // The order is different from other exchanges: [time, low, high, open, close, volume]
+        serde_json::from_str::<Vec<Vec<serde_json::Value>>>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .iter()
+            .map(|raw| Kline {
+                unixtime_msec: raw[0].as_i64().unwrap() * 1000,
+                o: raw[3].as_f64().unwrap(),
+                h: raw[2].as_f64().unwrap(),
+                l: raw[1].as_f64().unwrap(),
+                c: raw[4].as_f64().unwrap(),
+                v: raw[5].as_f64().unwrap(),
+                extra: KlineExtra::default(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case(DurationAndUnit::from_str("1min").unwrap(), "60".to_string())]
+    #[case(DurationAndUnit::from_str("6hour").unwrap(), "21600".to_string())]
+    #[case(DurationAndUnit::from_str("1day").unwrap(), "86400".to_string())]
+    #[should_panic]
+    #[case(DurationAndUnit::from_str("3min").unwrap(), "panic".to_string())]
+    #[should_panic]
+    #[case(DurationAndUnit::from_str("1week").unwrap(), "panic".to_string())]
+    fn test_fit_interval_to_req(#[case] input: DurationAndUnit, #[case] expected: String) {
+        let coinbase = Coinbase::new();
+        assert_eq!(coinbase.fit_interval_to_req(&input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline() {
+        let coinbase = Coinbase::new();
+
+        let input = r#"
+        [
+            [
+                1683727200,
+                27521.01,
+                27589.99,
+                27545.21,
+                27580.75,
+                95.60938173
+            ],
+            [
+                1683726300,
+                27503.53,
+                27556.93,
+                27514.2,
+                27545.22,
+                131.85226542
+            ]
+        ]"#
+        .to_string();
+        let result = coinbase.parse_as_kline(input);
+        let expected = vec![
+            Kline {
+                unixtime_msec: 1683727200000,
+                o: 27545.21,
+                h: 27589.99,
+                l: 27521.01,
+                c: 27580.75,
+                v: 95.60938173,
+                extra: KlineExtra::default(),
+            },
+            Kline {
+                unixtime_msec: 1683726300000,
+                o: 27514.2,
+                h: 27556.93,
+                l: 27503.53,
+                c: 27545.22,
+                v: 131.85226542,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_listings() {
+        let input = r#"
+        [
+            {
+                "id": "BTC-USD",
+                "base_currency": "BTC",
+                "quote_currency": "USD",
+                "status": "online",
+                "trading_disabled": false
+            },
+            {
+                "id": "UST-USD",
+                "base_currency": "UST",
+                "quote_currency": "USD",
+                "status": "delisted",
+                "trading_disabled": true
+            }
+        ]"#;
+
+        assert_eq!(
+            Coinbase::parse_as_listings(input).unwrap(),
+            vec![Listing::new("BTC", "USD", "BTC-USD", &[])]
+        );
+        assert!(Coinbase::parse_as_listings(r#"{"message": "Unauthorized."}"#).is_err());
+    }
+}
@@ -0,0 +1,534 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Error};
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{args::*, error::*, exchange::*, unit::*};
+
+/// Declarative description of a simple exchange, loaded with `--definition` from a JSON file.
+/// It covers exchanges that take GET with query parameters and return candles as rows
+/// (see `definitions/` for examples of the built-in exchanges written in it).
+#[derive(Debug, Clone, Deserialize)]
+pub struct Definition {
+    /// Display name used in error messages
+    name: String,
+    /// The only market type of the endpoint (`spot` or `perpetual`, etc.)
+    market_type: String,
+    /// URL, which can contain the placeholders same as `params`
+    endpoint: String,
+    /// Query parameters, the values can contain `{symbol}`, `{interval}`, `{term_start}`, `{term_end}` and `{limit}`
+    params: BTreeMap<String, String>,
+    /// Unit of the timestamps in both the request and the response
+    time_unit: TimeUnit,
+    /// Template with `{base}` and `{quote}` like `{base}-{quote}`
+    symbol: String,
+    #[serde(default)]
+    lowercase_symbol: bool,
+    /// Currencies named differently on the exchange like `{"BTC": "XBT"}`
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+    /// Intervals of ro-soku (like `15min`) to the ones of the exchange
+    intervals: BTreeMap<String, String>,
+    /// Maximum number of candles in a response
+    limit: i64,
+    /// Which end of the period to cut the window of `limit` candles from
+    pagination: Pagination,
+    /// JSON pointer to the array of candles (empty for the root)
+    #[serde(default)]
+    rows: String,
+    /// JSON pointers to each value in a candle, relative to the candle (like `/0` or `/open`)
+    columns: Columns,
+    #[serde(default)]
+    errors: Option<Errors>,
+    #[serde(default)]
+    server_time: Option<ServerTime>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TimeUnit {
+    Msec,
+    Sec,
+    Rfc3339,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Pagination {
+    /// Windows from `term_start` (for responses that are oldest first)
+    Forward,
+    /// Windows from `term_end` (for responses that are newest first)
+    Backward,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Columns {
+    t: String,
+    o: String,
+    h: String,
+    l: String,
+    c: String,
+    v: String,
+    /// Optional, the extra columns that the exchange provides
+    #[serde(default)]
+    qv: Option<String>,
+    #[serde(default)]
+    trades: Option<String>,
+    #[serde(default)]
+    tbv: Option<String>,
+    #[serde(default)]
+    tbqv: Option<String>,
+    #[serde(default)]
+    vwap: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Errors {
+    /// JSON pointer to the error code, the response is an error if it exists and is not in `ok`
+    code: String,
+    /// JSON pointer to the error message
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    ok: Vec<String>,
+    #[serde(default)]
+    symbol: Vec<String>,
+    #[serde(default)]
+    interval: Vec<String>,
+    #[serde(default)]
+    too_many_requests: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ServerTime {
+    endpoint: String,
+    /// JSON pointer to the time in `time_unit`
+    pointer: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Custom {
+    // Boxed, as it is much larger than the other exchanges
+    definition: Box<Definition>,
+    params: Vec<(String, String)>,
+    endpoint: String,
+}
+
+impl Definition {
+    pub fn from_file(path: &str) -> Result<Self, Error> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read the definition file `{}`: {}", path, e))?;
+        Self::from_json(&data)
+    }
+
+    pub fn from_json(data: &str) -> Result<Self, Error> {
+        serde_json::from_str::<Definition>(data)
+            .map_err(|e| anyhow!("Failed to parse the definition: {}", e))
+    }
+}
+
+impl Custom {
+    pub fn new(definition: Definition) -> Self {
+        Custom {
+            definition: Box::new(definition),
+            params: Vec::new(),
+            endpoint: String::new(),
+        }
+    }
+
+    fn format_time(&self, unixtime: i64, round_up: bool) -> String {
+        match self.definition.time_unit {
+            TimeUnit::Msec => unixtime.to_string(),
+            TimeUnit::Sec if round_up => ((unixtime + 999) / 1000).to_string(),
+            TimeUnit::Sec => (unixtime / 1000).to_string(),
+            TimeUnit::Rfc3339 => Utc.timestamp_millis_opt(unixtime).unwrap().to_rfc3339(),
+        }
+    }
+
+    fn parse_time(&self, value: &Value) -> Option<i64> {
+        let time = match value {
+            Value::String(s) => match s.parse::<i64>() {
+                Ok(n) => n,
+                Err(_) => {
+                    return s
+                        .parse::<DateTime<Utc>>()
+                        .ok()
+                        .map(|t| t.timestamp_millis())
+                }
+            },
+            _ => value.as_i64()?,
+        };
+
+        match self.definition.time_unit {
+            TimeUnit::Sec => Some(time * 1000),
+            _ => Some(time),
+        }
+    }
+
+    /// Numbers are given either as numbers or as strings.
+    fn parse_number(value: &Value) -> Option<f64> {
+        match value {
+            Value::String(s) => s.parse::<f64>().ok(),
+            _ => value.as_f64(),
+        }
+    }
+
+    /// Supported intervals in ascending order (not in the alphabetical order of the keys).
+    fn intervals(&self) -> Vec<String> {
+        let mut intervals: Vec<String> = self.definition.intervals.keys().cloned().collect();
+        intervals.sort_by_key(|interval| {
+            DurationAndUnit::from_str(interval).map_or(i64::MAX, |interval| interval.to_msec())
+        });
+        intervals
+    }
+
+    fn fill(template: &str, placeholders: &[(&str, &str)]) -> String {
+        placeholders
+            .iter()
+            .fold(template.to_string(), |filled, (key, value)| {
+                filled.replace(&format!("{{{}}}", key), value)
+            })
+    }
+}
+
+impl Retrieve for Custom {
+    fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        if args.type_.to_string().to_lowercase() != self.definition.market_type.to_lowercase() {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        let interval = args.interval.to_msec();
+        let span = (self.definition.limit - 1) * interval;
+        let (mut term_start, mut term_end) = (args.term_start.unwrap(), args.term_end.unwrap());
+        match self.definition.pagination {
+            Pagination::Forward => term_end = term_end.min(term_start + span),
+            Pagination::Backward => term_start = term_start.max(term_end - span),
+        }
+
+        let symbol = self.fit_symbol_to_req(&args.symbol)?;
+        let interval = self.fit_interval_to_req(&args.interval)?;
+        let (term_start, term_end) = (
+            self.format_time(term_start, true),
+            self.format_time(term_end, false),
+        );
+        let limit = self.definition.limit.to_string();
+        let placeholders = [
+            ("symbol", symbol.as_str()),
+            ("interval", interval.as_str()),
+            ("term_start", term_start.as_str()),
+            ("term_end", term_end.as_str()),
+            ("limit", limit.as_str()),
+        ];
+
+        self.endpoint = Self::fill(&self.definition.endpoint, &placeholders);
+        self.params = self
+            .definition
+            .params
+            .iter()
+            .map(|(key, value)| (key.clone(), Self::fill(value, &placeholders)))
+            .collect();
+
+        Ok(())
+    }
+
+    fn fetch(&self, client: &Client) -> Result<String, Error> {
+        let res = client
+            .get(&self.endpoint)
+            .query(&self.params)
+            .send()?
+            .text()?;
+
+        let Some(errors) = &self.definition.errors else {
+            return Ok(res);
+        };
+
+        let response = serde_json::from_str::<Value>(&res)
+            .map_err(|_| ExchangeResponseError::wrap_error(res.clone()))?;
+        if let Some(code) = response.pointer(&errors.code) {
+            // Codes can be numbers or strings
+            let code = match code {
+                Value::String(s) => s.clone(),
+                _ => code.to_string(),
+            };
+
+            if errors.ok.contains(&code) {
+                return Ok(res);
+            } else if errors.symbol.contains(&code) {
+                return Err(ExchangeResponseError::symbol());
+            } else if errors.interval.contains(&code) {
+                return Err(ExchangeResponseError::interval_of(&self.intervals()));
+            } else if errors.too_many_requests.contains(&code) {
+                return Err(ExchangeResponseError::too_many_requests());
+            }
+
+            let message = match errors
+                .message
+                .as_ref()
+                .and_then(|pointer| response.pointer(pointer))
+            {
+                Some(Value::String(message)) => message.clone(),
+                Some(message) => message.to_string(),
+                None => format!("Error code {}", code),
+            };
+            let message = format!("{}: {}", self.definition.name, message);
+            return Err(ExchangeResponseError::wrap_error(message));
+        }
+
+        Ok(res)
+    }
+
+    fn server_time(
+        &self,
+        client: &Client,
+        _market_type: &MarketType,
+    ) -> Result<Option<i64>, Error> {
+        let Some(server_time) = &self.definition.server_time else {
+            return Ok(None);
+        };
+
+        let res = client.get(&server_time.endpoint).send()?.json::<Value>()?;
+
+        Ok(res
+            .pointer(&server_time.pointer)
+            .and_then(|time| self.parse_time(time)))
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        let aliases: Vec<(&str, &str)> = self
+            .definition
+            .aliases
+            .iter()
+            .map(|(from, to)| (from.as_str(), to.as_str()))
+            .collect();
+        let (base, quote) = split_symbol(symbol, &aliases)?;
+
+        let symbol = Self::fill(
+            &self.definition.symbol,
+            &[("base", &base), ("quote", &quote)],
+        );
+        match self.definition.lowercase_symbol {
+            true => Ok(symbol.to_lowercase()),
+            false => Ok(symbol),
+        }
+    }
+
+    fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
+        let interval = format!("{}{:?}", interval.0, interval.1).to_lowercase();
+
+        self.definition
+            .intervals
+            .get(&interval)
+            .cloned()
+            .ok_or_else(|| ExchangeResponseError::interval_of(&self.intervals()))
+    }
+
+    fn parse_as_kline(&self, data: String) -> Vec<Kline> {
+        let response = serde_json::from_str::<Value>(&data)
+            .expect("Unexpected error! Failed to parse response to json.");
+        let columns = &self.definition.columns;
+
+        response
+            .pointer(&self.definition.rows)
+            .and_then(|rows| rows.as_array())
+            .expect("Unexpected error! The `rows` of the definition is not an array.")
+            .iter()
+            .map(|raw| {
+                let number = |pointer: &str| {
+                    raw.pointer(pointer)
+                        .and_then(Self::parse_number)
+                        .expect("Unexpected error! A column of the definition is not a number.")
+                };
+                // Missing or `null` in the response is also regarded as not provided
+                let optional_number = |pointer: &Option<String>| {
+                    pointer
+                        .as_ref()
+                        .and_then(|pointer| raw.pointer(pointer))
+                        .and_then(Self::parse_number)
+                };
+
+                Kline {
+                    unixtime_msec: raw
+                        .pointer(&columns.t)
+                        .and_then(|time| self.parse_time(time))
+                        .expect(
+                            "Unexpected error! The `t` column of the definition is not a time.",
+                        ),
+                    o: number(&columns.o),
+                    h: number(&columns.h),
+                    l: number(&columns.l),
+                    c: number(&columns.c),
+                    v: number(&columns.v),
+                    extra: KlineExtra {
+                        quote_volume: optional_number(&columns.qv),
+                        trades: optional_number(&columns.trades).map(|trades| trades as u64),
+                        taker_buy_volume: optional_number(&columns.tbv),
+                        taker_buy_quote_volume: optional_number(&columns.tbqv),
+                        vwap: optional_number(&columns.vwap),
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+    use crate::pick::*;
+
+    fn binance_spot() -> Custom {
+        Custom::new(
+            Definition::from_json(include_str!("../../definitions/binance-spot.json")).unwrap(),
+        )
+    }
+
+    fn bybit_spot() -> Custom {
+        Custom::new(
+            Definition::from_json(include_str!("../../definitions/bybit-spot.json")).unwrap(),
+        )
+    }
+
+    fn args(exchange: Exchange) -> ParsedArgs {
+        ParsedArgs {
+            exchange,
+            term_start: Some(1681516800000),
+            term_end: Some(1681603200000),
+            interval: DurationAndUnit::from_str("1hour").unwrap(),
+            pick: vec![Pick::T],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_prepare() {
+        let mut binance = binance_spot();
+        let args = args(Exchange::Binance(Binance::new()));
+
+        binance.prepare(&args).unwrap();
+
+        // Same request as the built-in Binance adapter
+        assert_eq!(
+            binance.endpoint,
+            "https://data-api.binance.vision/api/v3/klines"
+        );
+        assert_eq!(
+            binance.params,
+            [
+                ("endTime", "1681603200000"),
+                ("interval", "1h"),
+                ("limit", "1000"),
+                ("startTime", "1681516800000"),
+                ("symbol", "BTCUSDT"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .to_vec()
+        );
+    }
+
+    #[test]
+    fn test_prepare_window() {
+        let mut bybit = bybit_spot();
+        let mut args = args(Exchange::Bybit(Bybit::new()));
+        args.interval = DurationAndUnit::from_str("1min").unwrap();
+
+        bybit.prepare(&args).unwrap();
+
+        // Only `limit` candles from `term_end`, because Bybit returns newest first
+        let start = bybit.params.iter().find(|(key, _)| key == "start").unwrap();
+        assert_eq!(start.1, (1681603200000_i64 - 999 * 60 * 1000).to_string());
+    }
+
+    #[rstest]
+    #[case("BTC/USDT", "BTCUSDT".to_string())]
+    #[case("ETH/BTC", "ETHBTC".to_string())]
+    fn test_fit_symbol_to_req(#[case] input: &str, #[case] expected: String) {
+        assert_eq!(binance_spot().fit_symbol_to_req(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(DurationAndUnit::from_str("1min").unwrap(), "1m".to_string())]
+    #[case(DurationAndUnit::from_str("1month").unwrap(), "1M".to_string())]
+    #[should_panic]
+    #[case(DurationAndUnit::from_str("2min").unwrap(), "panic".to_string())]
+    fn test_fit_interval_to_req(#[case] input: DurationAndUnit, #[case] expected: String) {
+        assert_eq!(
+            binance_spot().fit_interval_to_req(&input).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_parse_as_kline_same_as_binance() {
+        let input = r#"
+        [
+            [
+                1681516800000,
+                "30295.09000000",
+                "30311.00000000",
+                "30290.55000000",
+                "30301.44000000",
+                "96.91281000",
+                1681517699999,
+                "2936555.94052660",
+                2773,
+                "45.36018000",
+                "1374505.17262130",
+                "0"
+            ]
+        ]"#
+        .to_string();
+
+        assert_eq!(
+            binance_spot().parse_as_kline(input.clone()),
+            Binance::new().parse_as_kline(input)
+        );
+    }
+
+    #[test]
+    fn test_parse_as_kline_nested() {
+        let input = r#"
+        {
+            "retCode": 0,
+            "retMsg": "OK",
+            "result": {
+                "category": "spot",
+                "symbol": "BTCUSDT",
+                "list": [
+                    [
+                        "1681517700000",
+                        "30301.44",
+                        "30320.5",
+                        "30300.01",
+                        "30311.43",
+                        "12.412811",
+                        "376251.88"
+                    ]
+                ]
+            }
+        }"#
+        .to_string();
+        let expected = vec![Kline {
+            unixtime_msec: 1681517700000,
+            o: 30301.44,
+            h: 30320.5,
+            l: 30300.01,
+            c: 30311.43,
+            v: 12.412811,
+            extra: KlineExtra {
+                quote_volume: Some(376251.88),
+                ..Default::default()
+            },
+        }];
+
+        assert_eq!(bybit_spot().parse_as_kline(input), expected);
+    }
+}
@@ -0,0 +1,443 @@
+use anyhow::{anyhow, Error};
+use chrono::{Datelike, NaiveDate};
+use regex::Regex;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use crate::{args::*, error::*, exchange::*, unit::*};
+
+const ENDPOINT: &str = "https://www.deribit.com/api/v2/public/get_tradingview_chart_data";
+const ENDPOINT_INSTRUMENTS: &str = "https://www.deribit.com/api/v2/public/get_instruments";
+/// Not documented, but more than this is not returned at once
+const LIMIT: i64 = 1000;
+
+#[derive(Debug, Clone)]
+pub struct Deribit {
+    params: Vec<(String, String)>,
+    market_type: MarketType,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    result: Option<Chart>,
+    error: Option<ErrorInResponse>,
+}
+
+#[derive(Deserialize)]
+struct ErrorInResponse {
+    code: i64,
+    message: String,
+    data: Option<ErrorData>,
+}
+
+#[derive(Deserialize)]
+struct ErrorData {
+    param: Option<String>,
+}
+
+/// Each field is a column, not a row
+#[derive(Deserialize)]
+struct Chart {
+    ticks: Vec<i64>,
+    open: Vec<f64>,
+    high: Vec<f64>,
+    low: Vec<f64>,
+    close: Vec<f64>,
+    volume: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnTime {
+    result: i64,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnInstruments {
+    result: Option<Vec<InstrumentToList>>,
+    error: Option<ErrorInResponse>,
+}
+
+#[derive(Deserialize)]
+struct InstrumentToList {
+    instrument_name: String,
+    base_currency: String,
+    counter_currency: String,
+    /// `perpetual`, or `day`, `week` and `month` for futures
+    settlement_period: String,
+    expiration_timestamp: i64,
+}
+
+impl Deribit {
+    pub fn new() -> Self {
+        Deribit {
+            params: Vec::new(),
+            market_type: MarketType::Perpetual,
+        }
+    }
+
+    /// Expiry in instrument names like `29DEC23` (the day is not zero-padded like `5JAN24`)
+    fn format_expiry(expiry: &NaiveDate) -> String {
+        format!(
+            "{}{}",
+            expiry.day(),
+            expiry.format("%b%y").to_string().to_uppercase()
+        )
+    }
+
+    /// Strike in instrument names like `40000` for `40000.0`, and `0d625` for `0.625`
+    /// (`--strike` is already validated as a positive number)
+    fn format_strike(strike: &str) -> String {
+        strike.parse::<f64>().unwrap().to_string().replace('.', "d")
+    }
+
+    /// Futures (including perpetuals) which are active, `market_type` is which ones to list.
+    fn parse_as_listings(
+        &self,
+        data: &str,
+        market_type: &MarketType,
+    ) -> Result<Vec<Listing>, Error> {
+        let response = serde_json::from_str::<ResponseOnInstruments>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+        if let Some(error) = response.error {
+            return Err(ExchangeResponseError::wrap_error(error.message));
+        }
+
+        Ok(response
+            .result
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|instrument| {
+                let listing = Listing::new(
+                    &instrument.base_currency,
+                    &instrument.counter_currency,
+                    &instrument.instrument_name,
+                    &[],
+                );
+                let currency = self.fit_symbol_to_req(&listing.symbol()).ok()?;
+
+                let (name, listing) = match (market_type, instrument.settlement_period.as_str()) {
+                    (MarketType::Perpetual, "perpetual") => {
+                        (format!("{}-PERPETUAL", currency), listing)
+                    }
+                    (MarketType::Futures, period) if period != "perpetual" => {
+                        let listing = listing.expiring_at(instrument.expiration_timestamp);
+                        (
+                            format!("{}-{}", currency, Self::format_expiry(&listing.expiry?)),
+                            listing,
+                        )
+                    }
+                    _ => return None,
+                };
+
+                (name == instrument.instrument_name).then_some(listing)
+            })
+            .collect())
+    }
+
+    /// Instrument names are like `BTC-PERPETUAL`, `BTC-29DEC23` and `BTC-29DEC23-40000-C`.
+    fn instrument_name(&self, args: &ParsedArgs) -> Result<String, Error> {
+        let currency = self.fit_symbol_to_req(&args.symbol)?;
+
+        let name = match args.type_ {
+            MarketType::Perpetual => format!("{}-PERPETUAL", currency),
+            MarketType::Futures => format!(
+                "{}-{}",
+                currency,
+                Self::format_expiry(&args.contract.expiry.unwrap())
+            ),
+            MarketType::Option => format!(
+                "{}-{}-{}-{}",
+                currency,
+                Self::format_expiry(&args.contract.expiry.unwrap()),
+                Self::format_strike(args.contract.strike.as_ref().unwrap()),
+                match args.contract.right.clone().unwrap() {
+                    OptionRight::Call => "C",
+                    OptionRight::Put => "P",
+                }
+            ),
+            MarketType::Spot => return Err(ExchangeResponseError::no_support_type()),
+        };
+
+        Ok(name)
+    }
+}
+
+impl Retrieve for Deribit {
+    fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        self.market_type = args.type_.clone();
+
+        // Cut the period into windows of `LIMIT` candles, the generic loop moves on to the next one
+        let term_start = args.term_start.unwrap();
+        let term_end = args
+            .term_end
+            .unwrap()
+            .min(term_start + self.window(args).unwrap());
+
+        self.params = [
+            ("instrument_name".to_string(), self.instrument_name(args)?),
+            ("start_timestamp".to_string(), term_start.to_string()),
+            ("end_timestamp".to_string(), term_end.to_string()),
+            (
+                "resolution".to_string(),
+                self.fit_interval_to_req(&args.interval)?,
+            ),
+        ]
+        .to_vec();
+
+        Ok(())
+    }
+
+    fn window(&self, args: &ParsedArgs) -> Option<i64> {
+        Some((LIMIT - 1) * args.interval.to_msec())
+    }
+
+    fn fetch(&self, client: &Client) -> Result<String, Error> {
+        let res = client.get(ENDPOINT).query(&self.params).send()?.text()?;
+
+        let response = serde_json::from_str::<Response>(&res)
+            .expect("Unexpected error! Failed to parse response (for error code) to json.");
+        if let Some(error) = response.error {
+            let param = error.data.and_then(|data| data.param);
+            match (error.code, param.as_deref()) {
+                (-32602, Some("instrument_name")) => return Err(ExchangeResponseError::symbol()),
+                (-32602, Some("resolution")) => {
+                    return Err(ExchangeResponseError::interval(
+                        &ExchangeChoices::Deribit,
+                        &self.market_type,
+                    ))
+                }
+                (10028, _) => return Err(ExchangeResponseError::too_many_requests()),
+                _ => return Err(ExchangeResponseError::wrap_error(error.message)),
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn server_time(
+        &self,
+        client: &Client,
+        _market_type: &MarketType,
+    ) -> Result<Option<i64>, Error> {
+        let res = client
+            .get("https://www.deribit.com/api/v2/public/get_time")
+            .send()?
+            .json::<ResponseOnTime>()?;
+
+        Ok(Some(res.result))
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        if !matches!(market_type, MarketType::Perpetual | MarketType::Futures) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        let res = client
+            .get(ENDPOINT_INSTRUMENTS)
+            .query(&[
+                ("currency", "any"),
+                ("kind", "future"),
+                ("expired", "false"),
+            ])
+            .send()?
+            .text()?;
+
+        self.parse_as_listings(&res, market_type)
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        let re = Regex::new(r"^(.*?)/(.*?)$").unwrap();
+        let matches = re.captures(symbol).ok_or(anyhow!(
+            "The symbol pair provided is incorrectly formatted."
+        ))?;
+
+        // Inverse contracts (quoted in USD) have only the base currency in their names,
+        // while linear ones have both like `BTC_USDC-PERPETUAL`
+        match &matches[2] {
+            "USD" => Ok(matches[1].to_string()),
+            quote => Ok(format!("{}_{}", &matches[1], quote)),
+        }
+    }
+
+    fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
+        let result = match (interval.0, &interval.1) {
+            (1 | 3 | 5 | 10 | 15 | 30, TermUnit::Min) => interval.0.to_string(),
+            (1 | 2 | 3 | 6 | 12, TermUnit::Hour) => (interval.0 as u32 * 60).to_string(),
+            (1, TermUnit::Day) => "1D".to_string(),
+            _ => {
+                return Err(ExchangeResponseError::interval(
+                    &ExchangeChoices::Deribit,
+                    &self.market_type,
+                ))
+            }
+        };
+        Ok(result)
+    }
+
+    fn parse_as_kline(&self, data: String) -> Vec<Kline> {
+        let chart = serde_json::from_str::<Response>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .result
+            .unwrap(/* Error handling has already been completed in `fetch()` */);
+
+        (0..chart.ticks.len())
+            .map(|i| Kline {
+                unixtime_msec: chart.ticks[i],
+                o: chart.open[i],
+                h: chart.high[i],
+                l: chart.low[i],
+                c: chart.close[i],
+                v: chart.volume[i],
+                extra: KlineExtra::default(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case("BTC/USD", "BTC".to_string())]
+    #[case("ETH/USDC", "ETH_USDC".to_string())]
+    fn test_fit_symbol_to_req(#[case] input: &str, #[case] expected: String) {
+        let deribit = Deribit::new();
+        assert_eq!(deribit.fit_symbol_to_req(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(DurationAndUnit::from_str("3min").unwrap(), "3".to_string())]
+    #[case(DurationAndUnit::from_str("6hour").unwrap(), "360".to_string())]
+    #[case(DurationAndUnit::from_str("1day").unwrap(), "1D".to_string())]
+    #[should_panic]
+    #[case(DurationAndUnit::from_str("4hour").unwrap(), "panic".to_string())]
+    fn test_fit_interval_to_req(#[case] input: DurationAndUnit, #[case] expected: String) {
+        let deribit = Deribit::new();
+        assert_eq!(deribit.fit_interval_to_req(&input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(NaiveDate::from_ymd_opt(2023, 12, 29).unwrap(), "29DEC23".to_string())]
+    #[case(NaiveDate::from_ymd_opt(2024, 1, 5).unwrap(), "5JAN24".to_string())]
+    fn test_format_expiry(#[case] input: NaiveDate, #[case] expected: String) {
+        assert_eq!(Deribit::format_expiry(&input), expected);
+    }
+
+    #[rstest]
+    #[case("40000", "40000".to_string())]
+    #[case("40000.0", "40000".to_string())]
+    #[case("040000.00", "40000".to_string())]
+    #[case("0.625", "0d625".to_string())]
+    fn test_format_strike(#[case] input: &str, #[case] expected: String) {
+        assert_eq!(Deribit::format_strike(input), expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline() {
+        let deribit = Deribit::new();
+
+        let input = r#"
+        {
+            "jsonrpc": "2.0",
+            "result": {
+                "volume": [19.0, 8.5],
+                "ticks": [1554373800000, 1554375600000],
+                "status": "ok",
+                "open": [4963.42, 4986.29],
+                "low": [4951.36, 4980.23],
+                "high": [5011.65, 5018.24],
+                "cost": [94950.0, 42500.0],
+                "close": [4986.29, 4998.73]
+            },
+            "usIn": 1554381680742493,
+            "usOut": 1554381680742698,
+            "usDiff": 205,
+            "testnet": false
+        }"#
+        .to_string();
+        let result = deribit.parse_as_kline(input);
+        let expected = vec![
+            Kline {
+                unixtime_msec: 1554373800000,
+                o: 4963.42,
+                h: 5011.65,
+                l: 4951.36,
+                c: 4986.29,
+                v: 19.0,
+                extra: KlineExtra::default(),
+            },
+            Kline {
+                unixtime_msec: 1554375600000,
+                o: 4986.29,
+                h: 5018.24,
+                l: 4980.23,
+                c: 4998.73,
+                v: 8.5,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(
+        MarketType::Perpetual,
+        vec![
+            Listing::new("BTC", "USD", "BTC-PERPETUAL", &[]),
+            Listing::new("BTC", "USDC", "BTC_USDC-PERPETUAL", &[]),
+        ]
+    )]
+    #[case(
+        MarketType::Futures,
+        vec![Listing::new("BTC", "USD", "BTC-29DEC23", &[]).expiring_at(1703836800000)]
+    )]
+    fn test_parse_as_listings(#[case] market_type: MarketType, #[case] expected: Vec<Listing>) {
+        let input = r#"
+        {
+            "jsonrpc": "2.0",
+            "result": [
+                {
+                    "instrument_name": "BTC-PERPETUAL",
+                    "base_currency": "BTC",
+                    "counter_currency": "USD",
+                    "settlement_period": "perpetual",
+                    "expiration_timestamp": 32503708800000,
+                    "kind": "future"
+                },
+                {
+                    "instrument_name": "BTC_USDC-PERPETUAL",
+                    "base_currency": "BTC",
+                    "counter_currency": "USDC",
+                    "settlement_period": "perpetual",
+                    "expiration_timestamp": 32503708800000,
+                    "kind": "future"
+                },
+                {
+                    "instrument_name": "BTC-29DEC23",
+                    "base_currency": "BTC",
+                    "counter_currency": "USD",
+                    "settlement_period": "month",
+                    "expiration_timestamp": 1703836800000,
+                    "kind": "future"
+                }
+            ]
+        }"#;
+
+        let result = Deribit::new()
+            .parse_as_listings(input, &market_type)
+            .unwrap();
+
+        assert_eq!(result, expected);
+    }
+}
@@ -0,0 +1,467 @@
+use anyhow::Error;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use crate::{args::*, error::*, exchange::*, unit::*};
+
+const ENDPOINT_SPOT: &str = "https://api.gateio.ws/api/v4/spot/candlesticks";
+const ENDPOINT_PERPETUAL: &str = "https://api.gateio.ws/api/v4/futures/usdt/candlesticks";
+const ENDPOINT_CONTRACTS: &str = "https://api.gateio.ws/api/v4/futures/usdt/contracts";
+const ENDPOINT_CURRENCY_PAIRS: &str = "https://api.gateio.ws/api/v4/spot/currency_pairs";
+/// A query with `from` and `to` that contains more points than this is rejected
+const LIMIT: i64 = 1000;
+
+#[derive(Debug, Clone)]
+pub struct Gate {
+    params: Vec<(String, String)>,
+    market_type: MarketType,
+    endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnError {
+    label: String,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnTime {
+    server_time: i64,
+}
+
+#[derive(Deserialize)]
+struct Candlestick {
+    t: i64,
+    v: serde_json::Value,
+    c: String,
+    h: String,
+    l: String,
+    o: String,
+}
+
+#[derive(Deserialize)]
+struct ContractInResponse {
+    /// Size of a contract in the base currency
+    quanto_multiplier: String,
+}
+
+#[derive(Deserialize)]
+struct PairToList {
+    /// Like `BTC_USDT`, named `name` in contracts
+    #[serde(alias = "name")]
+    id: String,
+    /// Spot only, `tradable`, `untradable` and so on
+    trade_status: Option<String>,
+    /// Perpetual only
+    #[serde(default)]
+    in_delisting: bool,
+}
+
+impl Gate {
+    pub fn new() -> Self {
+        Gate {
+            params: Vec::new(),
+            market_type: MarketType::Spot,
+            endpoint: String::new(),
+        }
+    }
+
+    fn contract_size(data: &str) -> Result<NativeVolume, Error> {
+        if let Ok(response) = serde_json::from_str::<ResponseOnError>(data) {
+            return match response.label.as_str() {
+                "CONTRACT_NOT_FOUND" => Err(ExchangeResponseError::symbol()),
+                _ => Err(ExchangeResponseError::wrap_error(response.message)),
+            };
+        }
+
+        let contract = serde_json::from_str::<ContractInResponse>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+
+        Ok(NativeVolume::Contracts {
+            size: contract
+                .quanto_multiplier
+                .parse::<f64>()
+                .map_err(|_| ExchangeResponseError::unknown())?,
+            inverse: false,
+        })
+    }
+
+    /// Currency pairs of spot and contracts of perpetual which are tradable.
+    fn parse_as_listings(data: &str) -> Result<Vec<Listing>, Error> {
+        if let Ok(response) = serde_json::from_str::<ResponseOnError>(data) {
+            return Err(ExchangeResponseError::wrap_error(response.message));
+        }
+
+        let pairs = serde_json::from_str::<Vec<PairToList>>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+
+        Ok(pairs
+            .iter()
+            .filter(|pair| {
+                pair.trade_status
+                    .as_deref()
+                    .is_none_or(|status| status == "tradable")
+                    && !pair.in_delisting
+            })
+            .filter_map(|pair| {
+                let (base, quote) = pair.id.split_once('_')?;
+                Some(Listing::new(base, quote, &pair.id, &[]))
+            })
+            .collect())
+    }
+}
+
+impl Retrieve for Gate {
+    // Gate.io's timestamps are all non-millisec
+
+    fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        if let MarketType::Futures | MarketType::Option = args.type_ {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        self.market_type = args.type_.clone();
+
+        // Cut the period into windows of `LIMIT` candles, the generic loop moves on to the next one
+        let term_start = args.term_start.unwrap();
+        let term_end = args
+            .term_end
+            .unwrap()
+            .min(term_start + self.window(args).unwrap());
+
+        let symbol = self.fit_symbol_to_req(&args.symbol)?;
+        self.params = [
+            (
+                "interval".to_string(),
+                self.fit_interval_to_req(&args.interval)?,
+            ),
+            // Round up so as not to include the candle before `term_start`
+            ("from".to_string(), ((term_start + 999) / 1000).to_string()),
+            ("to".to_string(), (term_end / 1000).to_string()),
+        ]
+        .to_vec();
+
+        match args.type_ {
+            MarketType::Spot => {
+                self.params.push(("currency_pair".to_string(), symbol));
+                self.endpoint = ENDPOINT_SPOT.to_string();
+            }
+            MarketType::Perpetual => {
+                self.params.push(("contract".to_string(), symbol));
+                self.endpoint = ENDPOINT_PERPETUAL.to_string();
+            }
+            _ => unreachable!(/* Checked above */),
+        }
+
+        Ok(())
+    }
+
+    fn window(&self, args: &ParsedArgs) -> Option<i64> {
+        Some((LIMIT - 1) * args.interval.to_msec())
+    }
+
+    fn fetch(&self, client: &Client) -> Result<String, Error> {
+        let res = client
+            .get(&self.endpoint)
+            .query(&self.params)
+            .send()?
+            .text()?;
+
+        if let Ok(response) = serde_json::from_str::<ResponseOnError>(&res) {
+            match response.label.as_str() {
+                "INVALID_CURRENCY_PAIR" | "INVALID_CURRENCY" | "CONTRACT_NOT_FOUND" => {
+                    return Err(ExchangeResponseError::symbol())
+                }
+                "TOO_MANY_REQUESTS" => return Err(ExchangeResponseError::too_many_requests()),
+                _ => return Err(ExchangeResponseError::wrap_error(response.message)),
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn server_time(
+        &self,
+        client: &Client,
+        _market_type: &MarketType,
+    ) -> Result<Option<i64>, Error> {
+        let res = client
+            .get("https://api.gateio.ws/api/v4/spot/time")
+            .send()?
+            .json::<ResponseOnTime>()?;
+
+        Ok(Some(res.server_time))
+    }
+
+    fn native_volume(&self, client: &Client, args: &ParsedArgs) -> Result<NativeVolume, Error> {
+        if let MarketType::Spot = args.type_ {
+            return Ok(NativeVolume::Base);
+        }
+
+        let res = client
+            .get(format!(
+                "{}/{}",
+                ENDPOINT_CONTRACTS,
+                self.fit_symbol_to_req(&args.symbol)?
+            ))
+            .send()?
+            .text()?;
+
+        Self::contract_size(&res)
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        let endpoint = match market_type {
+            MarketType::Spot => ENDPOINT_CURRENCY_PAIRS,
+            MarketType::Perpetual => ENDPOINT_CONTRACTS,
+            _ => return Err(ExchangeResponseError::no_support_type()),
+        };
+        let res = client.get(endpoint).send()?.text()?;
+
+        Self::parse_as_listings(&res)
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        let (base, quote) = split_symbol(symbol, &[])?;
+        Ok(format!("{}_{}", base, quote))
+    }
+
+    fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
+        let result = match (&self.market_type, interval.0, &interval.1) {
+            (_, 10, TermUnit::Sec) => "10s".to_string(),
+            (MarketType::Perpetual, 30, TermUnit::Sec) => "30s".to_string(),
+            (_, 1 | 5 | 15 | 30, TermUnit::Min) => format!("{}m", interval.0),
+            (_, 1 | 4 | 8, TermUnit::Hour) => format!("{}h", interval.0),
+            (MarketType::Perpetual, 2 | 6 | 12, TermUnit::Hour) => format!("{}h", interval.0),
+            (_, 1, TermUnit::Day) => "1d".to_string(),
+            (_, 1, TermUnit::Week) => "7d".to_string(),
+            _ => {
+                return Err(ExchangeResponseError::interval(
+                    &ExchangeChoices::Gate,
+                    &self.market_type,
+                ))
+            }
+        };
+        Ok(result)
+    }
+
+    fn parse_as_kline(&self, data: String) -> Vec<Kline> {
+        match self.market_type {
+            // [time, quote volume, close, high, low, open, base volume, closed or not] in strings
+            MarketType::Spot => serde_json::from_str::<Vec<Vec<serde_json::Value>>>(&data)
+                .expect("Unexpected error! Failed to parse response to json.")
+                .iter()
+                .map(|raw| Kline {
+                    unixtime_msec: raw[0].as_str().unwrap().parse::<i64>().unwrap() * 1000,
+                    o: raw[5].as_str().unwrap().parse::<f64>().unwrap(),
+                    h: raw[3].as_str().unwrap().parse::<f64>().unwrap(),
+                    l: raw[4].as_str().unwrap().parse::<f64>().unwrap(),
+                    c: raw[2].as_str().unwrap().parse::<f64>().unwrap(),
+                    v: raw[6].as_str().unwrap().parse::<f64>().unwrap(),
+                    extra: KlineExtra::default(),
+                })
+                .collect(),
+            // Volume is the number of contracts
+            _ => serde_json::from_str::<Vec<Candlestick>>(&data)
+                .expect("Unexpected error! Failed to parse response to json.")
+                .iter()
+                .map(|candle| Kline {
+                    unixtime_msec: candle.t * 1000,
+                    o: candle.o.parse::<f64>().unwrap(),
+                    h: candle.h.parse::<f64>().unwrap(),
+                    l: candle.l.parse::<f64>().unwrap(),
+                    c: candle.c.parse::<f64>().unwrap(),
+                    v: candle.v.as_f64().unwrap(),
+                    extra: KlineExtra::default(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case("BTC/USDT", "BTC_USDT".to_string())]
+    fn test_fit_symbol_to_req(#[case] input: &str, #[case] expected: String) {
+        let gate = Gate::new();
+        assert_eq!(gate.fit_symbol_to_req(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(MarketType::Spot, DurationAndUnit::from_str("10sec").unwrap(), "10s".to_string())]
+    #[case(MarketType::Spot, DurationAndUnit::from_str("1week").unwrap(), "7d".to_string())]
+    #[case(MarketType::Perpetual, DurationAndUnit::from_str("6hour").unwrap(), "6h".to_string())]
+    #[should_panic]
+    #[case(MarketType::Spot, DurationAndUnit::from_str("6hour").unwrap(), "panic".to_string())]
+    fn test_fit_interval_to_req(
+        #[case] market_type: MarketType,
+        #[case] input: DurationAndUnit,
+        #[case] expected: String,
+    ) {
+        let mut gate = Gate::new();
+        gate.market_type = market_type;
+        assert_eq!(gate.fit_interval_to_req(&input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline_spot() {
+        let gate = Gate::new();
+
+        let input = r#"
+        [
+            [
+                "1695835800",
+                "1529463.8524",
+                "26200.1",
+                "26215.3",
+                "26190",
+                "26210.4",
+                "58.3611",
+                "true"
+            ],
+            [
+                "1695836700",
+                "950722.0161",
+                "26214.9",
+                "26221.7",
+                "26196.2",
+                "26200.1",
+                "36.2744",
+                "true"
+            ]
+        ]"#
+        .to_string();
+        let result = gate.parse_as_kline(input);
+        let expected = vec![
+            Kline {
+                unixtime_msec: 1695835800000,
+                o: 26210.4,
+                h: 26215.3,
+                l: 26190.0,
+                c: 26200.1,
+                v: 58.3611,
+                extra: KlineExtra::default(),
+            },
+            Kline {
+                unixtime_msec: 1695836700000,
+                o: 26200.1,
+                h: 26221.7,
+                l: 26196.2,
+                c: 26214.9,
+                v: 36.2744,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline_perpetual() {
+        let mut gate = Gate::new();
+        gate.market_type = MarketType::Perpetual;
+
+        let input = r#"
+        [
+            {
+                "t": 1695835800,
+                "v": 311120,
+                "c": "26199.9",
+                "h": "26215",
+                "l": "26189.8",
+                "o": "26210.2",
+                "sum": "815130.73"
+            }
+        ]"#
+        .to_string();
+        let result = gate.parse_as_kline(input);
+        let expected = vec![Kline {
+            unixtime_msec: 1695835800000,
+            o: 26210.2,
+            h: 26215.0,
+            l: 26189.8,
+            c: 26199.9,
+            v: 311120.0,
+            extra: KlineExtra::default(),
+        }];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_contract_size() {
+        let input = r#"
+        {
+            "name": "BTC_USDT",
+            "type": "direct",
+            "quanto_multiplier": "0.0001",
+            "leverage_min": "1",
+            "leverage_max": "125",
+            "mark_price": "34512.3"
+        }"#;
+
+        assert_eq!(
+            Gate::contract_size(input).unwrap(),
+            NativeVolume::Contracts {
+                size: 0.0001,
+                inverse: false,
+            }
+        );
+        assert!(Gate::contract_size(
+            r#"{"label": "CONTRACT_NOT_FOUND", "message": "Contract not found"}"#
+        )
+        .is_err());
+    }
+
+    #[rstest]
+    #[case(
+        r#"
+        [
+            {
+                "id": "BTC_USDT",
+                "base": "BTC",
+                "quote": "USDT",
+                "trade_status": "tradable"
+            },
+            {
+                "id": "LUNA_USDT",
+                "base": "LUNA",
+                "quote": "USDT",
+                "trade_status": "untradable"
+            }
+        ]"#
+    )]
+    #[case(
+        r#"
+        [
+            {
+                "name": "BTC_USDT",
+                "type": "direct",
+                "quanto_multiplier": "0.0001",
+                "in_delisting": false
+            },
+            {
+                "name": "LUNA_USDT",
+                "type": "direct",
+                "quanto_multiplier": "1",
+                "in_delisting": true
+            }
+        ]"#
+    )]
+    fn test_parse_as_listings(#[case] input: &str) {
+        let result = Gate::parse_as_listings(input).unwrap();
+
+        assert_eq!(result, vec![Listing::new("BTC", "USDT", "BTC_USDT", &[])]);
+    }
+}
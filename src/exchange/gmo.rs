@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Error};
+use chrono::DateTime;
+use regex::Regex;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use crate::{args::*, error::*, exchange::*, unit::*};
+
+const ENDPOINT: &str = "https://api.coin.z.com/public/v1/klines";
+const ENDPOINT_SYMBOLS: &str = "https://api.coin.z.com/public/v1/symbols";
+/// Days are switched at 6:00 JST (21:00 UTC of the previous day)
+const PARTITION_OFFSET_MSEC: i64 = 3 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone)]
+pub struct Gmo {
+    params: Vec<(String, String)>,
+    market_type: MarketType,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    data: Option<Vec<Candle>>,
+    messages: Option<Vec<Message>>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    message_code: String,
+    message_string: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Candle {
+    open_time: String,
+    open: String,
+    high: String,
+    low: String,
+    close: String,
+    volume: String,
+}
+
+#[derive(Deserialize)]
+// There is no endpoint for time only, so use the response time of status
+struct ResponseOnTime {
+    responsetime: String,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnSymbols {
+    data: Option<Vec<SymbolToList>>,
+    messages: Option<Vec<Message>>,
+}
+
+#[derive(Deserialize)]
+struct SymbolToList {
+    /// Like `BTC` for spot and `BTC_JPY` for leverage trading
+    symbol: String,
+}
+
+impl Gmo {
+    pub fn new() -> Self {
+        Gmo {
+            params: Vec::new(),
+            market_type: MarketType::Spot,
+        }
+    }
+
+    fn calculate_date(term_start: i64, interval: &str) -> String {
+        let partition = match interval {
+            "1min" | "5min" | "10min" | "15min" | "30min" | "1hour" => Partition::Day,
+            "4hour" | "8hour" | "12hour" | "1day" | "1week" | "1month" => Partition::Year,
+            _ => unreachable!(/* Validation with enume is done to get to this point */),
+        };
+
+        partition.date(term_start, PARTITION_OFFSET_MSEC)
+    }
+
+    fn parse_as_listings(data: &str, market_type: &MarketType) -> Result<Vec<Listing>, Error> {
+        let response = serde_json::from_str::<ResponseOnSymbols>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+        if let Some(message) = response
+            .messages
+            .and_then(|messages| messages.into_iter().next())
+        {
+            return Err(ExchangeResponseError::wrap_error(message.message_string));
+        }
+
+        Ok(response
+            .data
+            .unwrap_or_default()
+            .iter()
+            .filter_map(
+                |symbol| match (market_type, symbol.symbol.split_once('_')) {
+                    // Spot symbols have only the base currency (quoted in JPY)
+                    (MarketType::Spot, None) => {
+                        Some(Listing::new(&symbol.symbol, "JPY", &symbol.symbol, &[]))
+                    }
+                    (MarketType::Perpetual, Some((base, quote))) => {
+                        Some(Listing::new(base, quote, &symbol.symbol, &[]))
+                    }
+                    _ => None,
+                },
+            )
+            .collect())
+    }
+}
+
+impl Retrieve for Gmo {
+    fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        self.market_type = args.type_.clone();
+
+        let symbol = self.fit_symbol_to_req(&args.symbol)?;
+        let symbol = match args.type_ {
+            // Spot symbols have only the base currency (quoted in JPY)
+            MarketType::Spot => match symbol.strip_suffix("_JPY") {
+                Some(base) => base.to_string(),
+                None => return Err(ExchangeResponseError::symbol()),
+            },
+            // Leverage trading
+            MarketType::Perpetual => symbol,
+            _ => return Err(ExchangeResponseError::no_support_type()),
+        };
+
+        let interval = self.fit_interval_to_req(&args.interval)?;
+        self.params = [
+            ("symbol".to_string(), symbol),
+            (
+                "date".to_string(),
+                Self::calculate_date(args.term_start.unwrap(), &interval),
+            ),
+            ("interval".to_string(), interval),
+        ]
+        .to_vec();
+
+        Ok(())
+    }
+
+    fn fetch(&self, client: &Client) -> Result<String, Error> {
+        let res = client.get(ENDPOINT).query(&self.params).send()?.text()?;
+
+        let response = serde_json::from_str::<Response>(&res)
+            .expect("Unexpected error! Failed to parse response (for error code) to json.");
+        if let Some(message) = response
+            .messages
+            .and_then(|messages| messages.into_iter().next())
+        {
+            match message.message_code.as_str() {
+                "ERR-5003" => return Err(ExchangeResponseError::too_many_requests()),
+                // If the validation of `interval` is complete, then the cause of the error
+                // can be identified as a symbol only
+                "ERR-5106" => return Err(ExchangeResponseError::symbol()),
+                _ => return Err(ExchangeResponseError::wrap_error(message.message_string)),
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn server_time(
+        &self,
+        client: &Client,
+        _market_type: &MarketType,
+    ) -> Result<Option<i64>, Error> {
+        let res = client
+            .get("https://api.coin.z.com/public/v1/status")
+            .send()?
+            .json::<ResponseOnTime>()?;
+
+        Ok(Some(
+            DateTime::parse_from_rfc3339(&res.responsetime)?.timestamp_millis(),
+        ))
+    }
+
+    fn native_volume(&self, _client: &Client, args: &ParsedArgs) -> Result<NativeVolume, Error> {
+        // Perpetuals are traded in coins, as if a contract were one coin
+        match args.type_ {
+            MarketType::Spot => Ok(NativeVolume::Base),
+            _ => Ok(NativeVolume::Contracts {
+                size: 1.0,
+                inverse: false,
+            }),
+        }
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        if !matches!(market_type, MarketType::Spot | MarketType::Perpetual) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        let res = client.get(ENDPOINT_SYMBOLS).send()?.text()?;
+
+        Self::parse_as_listings(&res, market_type)
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        // Almost same code as Binance, so the test already exists
+
+        let re = Regex::new(r"^(.*?)/(.*?)$").unwrap();
+        let matches = re.captures(symbol).ok_or(anyhow!(
+            "The symbol pair provided is incorrectly formatted."
+        ))?;
+        Ok(format!("{}_{}", &matches[1], &matches[2]))
+    }
+
+    fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
+        let data = include_str!("../data/intervals.json");
+
+        let intervals_map: HashMap<String, HashMap<String, Vec<String>>> =
+            serde_json::from_str(data)?;
+        let intervals = intervals_map
+            .get("GMO Coin")
+            .and_then(|market_type_map| market_type_map.get(&self.market_type.to_string()))
+            .unwrap();
+
+        let unit = format!("{:?}", interval.1);
+        let result = format!("{}{}", interval.0, unit.to_lowercase());
+
+        if !intervals.contains(&result) {
+            return Err(ExchangeResponseError::interval(
+                &ExchangeChoices::Gmo,
+                &self.market_type,
+            ));
+        }
+
+        Ok(result)
+    }
+
+    fn parse_as_kline(&self, data: String) -> Vec<Kline> {
+        // All fields including the timestamp are string-encoded
+        serde_json::from_str::<Response>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .data
+            .unwrap(/* Error handling has already been completed in `fetch()` */)
+            .iter()
+            .map(|candle| Kline {
+                unixtime_msec: candle.open_time.parse::<i64>().unwrap(),
+                o: candle.open.parse::<f64>().unwrap(),
+                h: candle.high.parse::<f64>().unwrap(),
+                l: candle.low.parse::<f64>().unwrap(),
+                c: candle.close.parse::<f64>().unwrap(),
+                v: candle.volume.parse::<f64>().unwrap(),
+                extra: KlineExtra::default(),
+            })
+            .collect()
+    }
+
+    fn remove_unnecessary_raws(raws: Vec<Kline>, term_start: i64, term_end: i64) -> Vec<Kline> {
+        retain_in_term(raws, term_start, term_end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    // 2023-01-01T00:00:00Z is 2023-01-01T09:00:00+09:00, after the switch at 6:00 JST
+    #[case(1672531200000, "1min", "20230101".to_string())]
+    // 2022-12-31T21:00:00Z is 2023-01-01T06:00:00+09:00, the first candle of the day
+    #[case(1672520400000, "1min", "20230101".to_string())]
+    #[case(1672520399999, "1hour", "20221231".to_string())]
+    #[case(1672520400000, "4hour", "2023".to_string())]
+    fn test_calculate_date(
+        #[case] term_start: i64,
+        #[case] interval: &str,
+        #[case] expected: String,
+    ) {
+        // `collect_klines` passes `term_start - 1`
+        assert_eq!(Gmo::calculate_date(term_start - 1, interval), expected)
+    }
+
+    #[rstest]
+    #[case(DurationAndUnit::from_str("10min").unwrap(), "10min".to_string())]
+    #[case(DurationAndUnit::from_str("1month").unwrap(), "1month".to_string())]
+    #[should_panic]
+    #[case(DurationAndUnit::from_str("1sec").unwrap(), "panic".to_string())]
+    fn test_fit_interval_to_req(#[case] input: DurationAndUnit, #[case] expected: String) {
+        let gmo = Gmo::new();
+        assert_eq!(gmo.fit_interval_to_req(&input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline() {
+        let gmo = Gmo::new();
+
+        let input = r#"
+        {
+            "status": 0,
+            "data": [
+                {
+                    "openTime": "1618588800000",
+                    "open": "6376000",
+                    "high": "6640000",
+                    "low": "6374000",
+                    "close": "6532000",
+                    "volume": "331.312"
+                },
+                {
+                    "openTime": "1618592400000",
+                    "open": "6546000",
+                    "high": "6561000",
+                    "low": "6441000",
+                    "close": "6479000",
+                    "volume": "203.4"
+                }
+            ],
+            "responsetime": "2021-04-17T02:01:38.912Z"
+        }"#
+        .to_string();
+        let result = gmo.parse_as_kline(input);
+        let expected = vec![
+            Kline {
+                unixtime_msec: 1618588800000,
+                o: 6376000.0,
+                h: 6640000.0,
+                l: 6374000.0,
+                c: 6532000.0,
+                v: 331.312,
+                extra: KlineExtra::default(),
+            },
+            Kline {
+                unixtime_msec: 1618592400000,
+                o: 6546000.0,
+                h: 6561000.0,
+                l: 6441000.0,
+                c: 6479000.0,
+                v: 203.4,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(MarketType::Spot, vec![Listing::new("BTC", "JPY", "BTC", &[])])]
+    #[case(MarketType::Perpetual, vec![Listing::new("BTC", "JPY", "BTC_JPY", &[])])]
+    fn test_parse_as_listings(#[case] market_type: MarketType, #[case] expected: Vec<Listing>) {
+        let input = r#"
+        {
+            "status": 0,
+            "data": [
+                { "symbol": "BTC", "minOrderSize": "0.0001", "sizeStep": "0.0001" },
+                { "symbol": "BTC_JPY", "minOrderSize": "0.01", "sizeStep": "0.01" }
+            ],
+            "responsetime": "2023-11-01T00:00:00.000Z"
+        }"#;
+
+        assert_eq!(
+            Gmo::parse_as_listings(input, &market_type).unwrap(),
+            expected
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use anyhow::{anyhow, Error};
+use regex::Regex;
+use reqwest::{blocking::Client, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::{args::*, error::*, exchange::*, unit::*};
+
+/// All public data is requested by POST with a JSON body to the same endpoint
+const ENDPOINT: &str = "https://api.hyperliquid.xyz/info";
+/// Only the most recent candles are served (older ones are not returned at all)
+const LOOK_BACK_LIMIT: i64 = 5000;
+
+#[derive(Debug, Clone)]
+pub struct Hyperliquid {
+    body: RequestBody,
+    market_type: MarketType,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RequestBody {
+    #[serde(rename = "type")]
+    type_: String,
+    req: CandleSnapshotRequest,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CandleSnapshotRequest {
+    coin: String,
+    interval: String,
+    start_time: i64,
+    end_time: i64,
+}
+
+#[derive(Deserialize)]
+struct Candle {
+    t: i64,
+    o: String,
+    h: String,
+    l: String,
+    c: String,
+    v: String,
+}
+
+#[derive(Deserialize)]
+struct Meta {
+    universe: Vec<CoinInMeta>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CoinInMeta {
+    name: String,
+    #[serde(default)]
+    is_delisted: bool,
+}
+
+impl Hyperliquid {
+    pub fn new() -> Self {
+        Hyperliquid {
+            body: RequestBody {
+                type_: "candleSnapshot".to_string(),
+                req: CandleSnapshotRequest::default(),
+            },
+            market_type: MarketType::Perpetual,
+        }
+    }
+
+    /// Coins of perpetuals, which are all settled in USDC.
+    fn parse_as_listings(data: &str) -> Result<Vec<Listing>, Error> {
+        let meta = serde_json::from_str::<Meta>(data)
+            .map_err(|_| ExchangeResponseError::wrap_error(data.to_string()))?;
+
+        Ok(meta
+            .universe
+            .iter()
+            .filter(|coin| !coin.is_delisted)
+            .map(|coin| Listing::new(&coin.name, "USDC", &coin.name, &[]))
+            .collect())
+    }
+}
+
+impl Retrieve for Hyperliquid {
+    fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        if !matches!(args.type_, MarketType::Perpetual) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        // Requests before the look-back window return nothing, so it is better to tell
+        // than to silently output nothing or a truncated period (`--count` stops at it without asking)
+        let oldest = self.history_start(args).unwrap();
+        let (term_start, term_end) = (args.term_start.unwrap(), args.term_end.unwrap());
+        if term_end < oldest || (args.count.is_none() && term_start < oldest) {
+            return Err(anyhow!(
+                "Hyperliquid only serves the latest {} candles, which start from {} (unixtime) for this interval.",
+                LOOK_BACK_LIMIT,
+                oldest
+            ));
+        }
+
+        self.body.req = CandleSnapshotRequest {
+            coin: self.fit_symbol_to_req(&args.symbol)?,
+            interval: self.fit_interval_to_req(&args.interval)?,
+            start_time: term_start.max(oldest),
+            end_time: term_end,
+        };
+
+        Ok(())
+    }
+
+    fn history_start(&self, args: &ParsedArgs) -> Option<i64> {
+        Some(args.now().timestamp_millis() - LOOK_BACK_LIMIT * args.interval.to_msec())
+    }
+
+    fn fetch(&self, client: &Client) -> Result<String, Error> {
+        let res = client.post(ENDPOINT).json(&self.body).send()?;
+
+        if res.status() == StatusCode::TOO_MANY_REQUESTS {
+            return Err(ExchangeResponseError::too_many_requests());
+        }
+
+        let res = res.text()?;
+
+        // Errors are returned as plain text (or `null` for unknown coins), not as JSON
+        if serde_json::from_str::<Vec<serde_json::Value>>(&res).is_err() {
+            match res.as_str() {
+                "null" => return Err(ExchangeResponseError::symbol()),
+                _ => return Err(ExchangeResponseError::wrap_error(res)),
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn native_volume(&self, _client: &Client, args: &ParsedArgs) -> Result<NativeVolume, Error> {
+        // Perpetuals are traded in coins, as if a contract were one coin
+        match args.type_ {
+            MarketType::Spot => Ok(NativeVolume::Base),
+            _ => Ok(NativeVolume::Contracts {
+                size: 1.0,
+                inverse: false,
+            }),
+        }
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        if !matches!(market_type, MarketType::Perpetual) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        let res = client
+            .post(ENDPOINT)
+            .json(&serde_json::json!({ "type": "meta" }))
+            .send()?
+            .text()?;
+
+        Self::parse_as_listings(&res)
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        let re = Regex::new(r"^(.*?)/(.*?)$").unwrap();
+        let matches = re.captures(symbol).ok_or(anyhow!(
+            "The symbol pair provided is incorrectly formatted."
+        ))?;
+
+        // Coins have no quote currency, all perpetuals are settled in USDC
+        match &matches[2] {
+            "USD" | "USDC" => Ok(matches[1].to_string()),
+            _ => Err(ExchangeResponseError::symbol()),
+        }
+    }
+
+    fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
+        let result = match (interval.0, &interval.1) {
+            (1 | 3 | 5 | 15 | 30, TermUnit::Min) => format!("{}m", interval.0),
+            (1 | 2 | 4 | 8 | 12, TermUnit::Hour) => format!("{}h", interval.0),
+            (1 | 3, TermUnit::Day) => format!("{}d", interval.0),
+            (1, TermUnit::Week) => "1w".to_string(),
+            (1, TermUnit::Month) => "1M".to_string(),
+            _ => {
+                return Err(ExchangeResponseError::interval(
+                    &ExchangeChoices::Hyperliquid,
+                    &self.market_type,
+                ))
+            }
+        };
+        Ok(result)
+    }
+
+    fn parse_as_kline(&self, data: String) -> Vec<Kline> {
+        serde_json::from_str::<Vec<Candle>>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .iter()
+            .map(|candle| Kline {
+                unixtime_msec: candle.t,
+                o: candle.o.parse::<f64>().unwrap(),
+                h: candle.h.parse::<f64>().unwrap(),
+                l: candle.l.parse::<f64>().unwrap(),
+                c: candle.c.parse::<f64>().unwrap(),
+                v: candle.v.parse::<f64>().unwrap(),
+                extra: KlineExtra::default(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use chrono::{TimeZone, Utc};
+    use rstest::*;
+
+    use super::*;
+    use crate::clock::*;
+
+    #[rstest]
+    #[case("BTC/USD", "BTC".to_string())]
+    #[case("ETH/USDC", "ETH".to_string())]
+    #[should_panic]
+    #[case("BTC/JPY", "panic".to_string())]
+    fn test_fit_symbol_to_req(#[case] input: &str, #[case] expected: String) {
+        let hyperliquid = Hyperliquid::new();
+        assert_eq!(hyperliquid.fit_symbol_to_req(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(DurationAndUnit::from_str("15min").unwrap(), "15m".to_string())]
+    #[case(DurationAndUnit::from_str("8hour").unwrap(), "8h".to_string())]
+    #[case(DurationAndUnit::from_str("1month").unwrap(), "1M".to_string())]
+    #[should_panic]
+    #[case(DurationAndUnit::from_str("6hour").unwrap(), "panic".to_string())]
+    fn test_fit_interval_to_req(#[case] input: DurationAndUnit, #[case] expected: String) {
+        let hyperliquid = Hyperliquid::new();
+        assert_eq!(hyperliquid.fit_interval_to_req(&input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_request_body() {
+        let mut hyperliquid = Hyperliquid::new();
+        hyperliquid.body.req = CandleSnapshotRequest {
+            coin: "BTC".to_string(),
+            interval: "15m".to_string(),
+            start_time: 1681923600000,
+            end_time: 1681924499999,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&hyperliquid.body).unwrap(),
+            r#"{"type":"candleSnapshot","req":{"coin":"BTC","interval":"15m","startTime":1681923600000,"endTime":1681924499999}}"#
+        );
+    }
+
+    #[rstest]
+    // Starting before the look-back, the window of `--count` is clamped to it
+    #[case(0, 1681920000000, Some(10), true)]
+    // ... while the period is not truncated silently
+    #[case(0, 1681920000000, None, false)]
+    #[case(1681900000000, 1681920000000, None, true)]
+    // Ending before the look-back, which a window of `--count` never does
+    #[case(0, 1681600000000, Some(10), false)]
+    fn test_prepare_look_back(
+        #[case] term_start: i64,
+        #[case] term_end: i64,
+        #[case] count: Option<u32>,
+        #[case] expected: bool,
+    ) {
+        let mut hyperliquid = Hyperliquid::new();
+        let now = 1681920000000;
+        let args = ParsedArgs {
+            exchange: Exchange::Hyperliquid(Hyperliquid::new()),
+            symbol: "BTC/USD".to_string(),
+            type_: MarketType::Perpetual,
+            term_start: Some(term_start),
+            term_end: Some(term_end),
+            count,
+            interval: DurationAndUnit(1, TermUnit::Min),
+            clock: Clock::Fixed(Utc.timestamp_millis_opt(now).unwrap()),
+            ..Default::default()
+        };
+
+        // The latest 5000 minutes
+        let oldest = now - 5000 * 60 * 1000;
+        assert_eq!(hyperliquid.history_start(&args), Some(oldest));
+
+        assert_eq!(hyperliquid.prepare(&args).is_ok(), expected);
+        if expected {
+            assert_eq!(hyperliquid.body.req.start_time, term_start.max(oldest));
+        }
+    }
+
+    #[test]
+    fn test_parse_as_kline() {
+        let hyperliquid = Hyperliquid::new();
+
+        let input = r#"
+        [
+            {
+                "T": 1681924499999,
+                "c": "29258.0",
+                "h": "29309.0",
+                "i": "15m",
+                "l": "29250.0",
+                "n": 189,
+                "o": "29295.0",
+                "s": "BTC",
+                "t": 1681923600000,
+                "v": "0.98639"
+            },
+            {
+                "T": 1681925399999,
+                "c": "29260.0",
+                "h": "29275.0",
+                "i": "15m",
+                "l": "29241.0",
+                "n": 144,
+                "o": "29258.0",
+                "s": "BTC",
+                "t": 1681924500000,
+                "v": "1.2011"
+            }
+        ]"#
+        .to_string();
+        let result = hyperliquid.parse_as_kline(input);
+        let expected = vec![
+            Kline {
+                unixtime_msec: 1681923600000,
+                o: 29295.0,
+                h: 29309.0,
+                l: 29250.0,
+                c: 29258.0,
+                v: 0.98639,
+                extra: KlineExtra::default(),
+            },
+            Kline {
+                unixtime_msec: 1681924500000,
+                o: 29258.0,
+                h: 29275.0,
+                l: 29241.0,
+                c: 29260.0,
+                v: 1.2011,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_listings() {
+        let input = r#"
+        {
+            "universe": [
+                { "name": "BTC", "szDecimals": 5, "maxLeverage": 40 },
+                { "name": "MATIC", "szDecimals": 1, "maxLeverage": 20, "isDelisted": true }
+            ]
+        }"#;
+
+        assert_eq!(
+            Hyperliquid::parse_as_listings(input).unwrap(),
+            vec![Listing::new("BTC", "USDC", "BTC", &[])]
+        );
+    }
+}
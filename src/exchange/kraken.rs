@@ -1,131 +1,264 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, thread, time};
 
-use anyhow::{anyhow, Error};
-use regex::Regex;
+use anyhow::Error;
 use reqwest::blocking::Client;
 use serde::Deserialize;
 
-use crate::{args::*, exchange::*, unit::*};
+use crate::{args::*, error::*, exchange::*, unit::*};
 
-//////////////////////////////////////////////////////////////////////////////
-
-/// Kraken was not implemented due to a buggy API.
-/// The `since` parameter does not work at all, and it is endlessly tedious
-/// to get data for the intended time period. There was some information on
-/// the official blog that you can use the `/Trades` endpoint in addition
-/// to the `/OHLC` endpoint, but I doubt that `since` works here too,
-/// and moreover I had no motivation to create my own OHLCV from this data.
-
-//////////////////////////////////////////////////////////////////////////////
+/// The `/OHLC` endpoint was not used because its `since` parameter does not work at all
+/// (only the latest 720 candles are returned), so candles are built from `/Trades` instead.
+/// It takes much longer than other exchanges for long periods (up to 1000 trades per request).
+const ENDPOINT: &str = "https://api.kraken.com/0/public/Trades";
+const ENDPOINT_ASSET_PAIRS: &str = "https://api.kraken.com/0/public/AssetPairs";
+const LIMIT: i32 = 1000;
+/// Public endpoints allow about one request per second
+const REQUEST_INTERVAL_MSEC: u64 = 1000;
+/// Currencies named differently on Kraken
+pub const ALIASES: &[(&str, &str)] = &[
+    ("LUNC", "LUNA"),
+    ("LUNA", "LUNA2"),
+    ("REP", "REPV2"),
+    ("REPV1", "REP"),
+    ("USTC", "UST"),
+    ("BTC", "XBT"),
+    ("DOGE", "XDG"),
+];
 
 #[derive(Debug, Clone)]
 pub struct Kraken {
+    params: Vec<(String, String)>,
     endpoint: String,
-    /// A little different from other exchanges. Not possible to specify in the request,
-    /// but up to 720 pieces of data after `since` will be returned.
-    _limit: i32,
+    interval: DurationAndUnit,
+    /// Nanoseconds cursor, the value of `last` in the previous response
+    since: i64,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnTime {
+    result: TimeInResponse,
+}
+
+#[derive(Deserialize)]
+struct TimeInResponse {
+    unixtime: i64,
 }
 
 #[derive(Deserialize)]
 struct Response {
     result: Option<serde_json::Map<String, serde_json::Value>>,
-    _error: Vec<String>,
+    error: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnAssetPairs {
+    result: Option<HashMap<String, AssetPair>>,
+    error: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct AssetPair {
+    /// Like `XBTUSD`
+    altname: String,
+    /// Like `XBT/USD`, missing on some pairs
+    wsname: Option<String>,
+    /// `online`, `cancel_only` and so on
+    status: String,
 }
 
 impl Kraken {
-    #[allow(dead_code)]
     pub fn new() -> Self {
         Kraken {
-            endpoint: "https://api.kraken.com/0/public/OHLC".to_string(),
-            _limit: 720,
+            params: Vec::new(),
+            endpoint: ENDPOINT.to_string(),
+            interval: DurationAndUnit(1, TermUnit::Min),
+            since: 0,
+        }
+    }
+
+    fn parse_as_listings(&self, data: &str) -> Result<Vec<Listing>, Error> {
+        let response = serde_json::from_str::<ResponseOnAssetPairs>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+        if let Some(error) = response.error.first() {
+            return Err(ExchangeResponseError::wrap_error(error.clone()));
         }
+
+        Ok(response
+            .result
+            .unwrap_or_default()
+            .values()
+            .filter(|pair| pair.status == "online")
+            .filter_map(|pair| {
+                let (base, quote) = pair.wsname.as_ref()?.split_once('/')?;
+                let listing = Listing::new(base, quote, &pair.altname, ALIASES);
+
+                (self.fit_symbol_to_req(&listing.symbol()).ok()? == pair.altname).then_some(listing)
+            })
+            .collect())
+    }
+
+    /// Returns trades in the page and the cursor for the next page.
+    fn parse_as_trades(&self, data: String) -> (Vec<Trade>, i64) {
+        let mut result = serde_json::from_str::<Response>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .result
+            .unwrap(/* Error handling has already been completed in `fetch()` */);
+
+        let last = result
+            .remove("last")
+            .and_then(|last| last.as_str().and_then(|last| last.parse::<i64>().ok()))
+            .unwrap_or(self.since);
+
+        // Since the key name changes dynamically depending on the requested symbol,
+        // retrieve the first key after removing `last` (which should be the only one)
+        let trades = result
+            .iter()
+            .next()
+            .map(|(_, trades)| {
+                trades
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|raws| {
+                        let raw = raws.as_array().unwrap();
+                        Trade {
+                            unixtime_msec: (raw[2].as_f64().unwrap() * 1000.0) as i64,
+                            price: raw[0].as_str().unwrap().parse::<f64>().unwrap(),
+                            size: raw[1].as_str().unwrap().parse::<f64>().unwrap(),
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        (trades, last)
     }
 }
 
 impl Retrieve for Kraken {
     // Kraken's timestamps are all non-millisec
 
-    fn fetch(&self, args: &ParsedArgs, client: &Client) -> Result<String, Error> {
-        let params = &[
-            ("pair", self.fit_symbol_to_req(&args.symbol)?),
-            ("interval", self.fit_interval_to_req(&args.interval)?),
-            ("since", (args.term_start.unwrap() / 1000).to_string()),
-        ];
+    fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        if !matches!(args.type_, MarketType::Spot) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
 
-        let res = client.get(&self.endpoint).query(params).send()?.text()?;
+        self.interval = args.interval.clone();
+        self.params = [
+            ("pair".to_string(), self.fit_symbol_to_req(&args.symbol)?),
+            ("since".to_string(), self.since.to_string()),
+            ("count".to_string(), LIMIT.to_string()),
+        ]
+        .to_vec();
 
-        let _response = serde_json::from_str::<Response>(&res)
-            .expect("Unexpected error! Failed to parse response (for error code) to json.");
+        Ok(())
+    }
+
+    /// Walk through trades from `term_start` with the cursor and build candles at the end,
+    /// because the generic loop cannot move on until a whole candle is fetched.
+    fn collect_klines(&mut self, args: &mut ParsedArgs) -> Result<Vec<Kline>, Error> {
+        let client = Client::new();
+        // Only whole candles, a partial one would have wrong prices and volume
+        let (term_start, term_end) = trade_term(
+            args.term_start.unwrap(),
+            args.term_end.unwrap(),
+            &args.interval,
+        );
+        if term_end < term_start {
+            return Ok(Vec::new());
+        }
+        let mut trades: Vec<Trade> = Vec::new();
+
+        self.since = term_start * 1000 * 1000;
+
+        loop {
+            self.prepare(args)?;
+
+            let res = self.fetch(&client)?;
+            let (page, last) = self.parse_as_trades(res);
 
-        // Error handling are not implemented
+            let reached_end = match page.last() {
+                Some(latest) => term_end < latest.unixtime_msec,
+                None => true,
+            };
+
+            trades.extend(page.into_iter().filter(|trade| {
+                term_start <= trade.unixtime_msec && trade.unixtime_msec <= term_end
+            }));
+
+            if reached_end || last == self.since {
+                break;
+            }
+            self.since = last;
+
+            thread::sleep(time::Duration::from_millis(REQUEST_INTERVAL_MSEC));
+        }
+
+        Ok(aggregate_trades(trades, &args.interval))
+    }
+
+    fn fetch(&self, client: &Client) -> Result<String, Error> {
+        let res = client
+            .get(&self.endpoint)
+            .query(&self.params)
+            .send()?
+            .text()?;
+
+        let response = serde_json::from_str::<Response>(&res)
+            .expect("Unexpected error! Failed to parse response (for error code) to json.");
+        if let Some(error) = response.error.first() {
+            match error.as_str() {
+                "EQuery:Unknown asset pair" => return Err(ExchangeResponseError::symbol()),
+                "EGeneral:Too many requests" | "EAPI:Rate limit exceeded" => {
+                    return Err(ExchangeResponseError::too_many_requests())
+                }
+                _ => return Err(ExchangeResponseError::wrap_error(error.to_string())),
+            }
+        }
 
         Ok(res)
     }
 
-    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
-        let mut aliases = HashMap::new();
-        aliases.insert("LUNC", "LUNA");
-        aliases.insert("LUNA", "LUNA2");
-        aliases.insert("REP", "REPV2");
-        aliases.insert("REPV1", "REP");
-        aliases.insert("USTC", "UST");
-        aliases.insert("BTC", "XBT");
-        aliases.insert("DOGE", "XDG");
-
-        let re = Regex::new(r"^(.*?)/(.*?)$").unwrap();
-        let matches = re.captures(symbol).ok_or(anyhow!(
-            "The symbol pair provided is incorrectly formatted."
-        ))?;
-
-        let base = aliases.get(&matches[1]).unwrap_or(&&matches[1]).to_owned();
-        let quote = aliases.get(&matches[2]).unwrap_or(&&matches[2]).to_owned();
+    fn server_time(
+        &self,
+        client: &Client,
+        _market_type: &MarketType,
+    ) -> Result<Option<i64>, Error> {
+        let res = client
+            .get("https://api.kraken.com/0/public/Time")
+            .send()?
+            .json::<ResponseOnTime>()?;
+
+        Ok(Some(res.result.unixtime * 1000))
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        if !matches!(market_type, MarketType::Spot) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
 
+        let res = client.get(ENDPOINT_ASSET_PAIRS).send()?.text()?;
+
+        self.parse_as_listings(&res)
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        let (base, quote) = split_symbol(symbol, ALIASES)?;
         Ok(format!("{}{}", base, quote))
     }
 
     fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
-        let result = match interval.1 {
-            TermUnit::Sec => return Err(anyhow!("OKX does not support candlestick of seconds")),
-            TermUnit::Min => interval.0.to_string(),
-            TermUnit::Hour => (interval.0 as i32 * 60).to_string(),
-            TermUnit::Day => (interval.0 as i32 * 60 * 24).to_string(),
-            TermUnit::Week => (interval.0 as i32 * 60 * 24 * 7).to_string(),
-            TermUnit::Month => return Err(anyhow!("OKX does not support candlestick of months")),
-        };
-        Ok(result)
+        // Not sent to Kraken, any interval can be built from trades
+        Ok(format!("{}{:?}", interval.0, interval.1).to_lowercase())
     }
 
     fn parse_as_kline(&self, data: String) -> Vec<Kline> {
-        // No test written
-
-        let mut result = serde_json::from_str::<Response>(&data)
-            .expect("Unexpected error! Failed to parse response to json.")
-            .result
-            .unwrap(/* Error handling has already been completed in `fetch()` */);
-
-        // Since the key name changes dynamically depending on the requested symbol,
-        // remove the irrelevant `last` and then retrieve the first key (which should be the only one)
-        result.remove("last");
-        result
-            .iter()
-            .next()
-            .unwrap()
-            .1
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|raws| {
-                let raw = raws.as_array().unwrap();
-                Kline {
-                    unixtime_msec: raw[0].as_i64().unwrap() * 1000,
-                    o: raw[1].as_str().unwrap().parse::<f64>().unwrap(),
-                    h: raw[2].as_str().unwrap().parse::<f64>().unwrap(),
-                    l: raw[3].as_str().unwrap().parse::<f64>().unwrap(),
-                    c: raw[4].as_str().unwrap().parse::<f64>().unwrap(),
-                    v: raw[5].as_str().unwrap().parse::<f64>().unwrap(),
-                }
-            })
-            .collect()
+        aggregate_trades(self.parse_as_trades(data).0, &self.interval)
     }
 }
 
@@ -146,15 +279,104 @@ mod tests {
     }
 
     #[rstest]
-    #[should_panic]
-    #[case(DurationAndUnit::from_str("1sec").unwrap(), "panic".to_string())]
-    #[case(DurationAndUnit::from_str("15min").unwrap(), "15".to_string())]
-    #[case(DurationAndUnit::from_str("4hour").unwrap(), "240".to_string())]
-    #[case(DurationAndUnit::from_str("1week").unwrap(), "10080".to_string())]
-    #[should_panic]
-    #[case(DurationAndUnit::from_str("2month").unwrap(), "panic".to_string())]
+    // Seconds and months cannot be used in `/OHLC`, but they can be built from trades
+    #[case(DurationAndUnit::from_str("1sec").unwrap(), "1sec".to_string())]
+    #[case(DurationAndUnit::from_str("15min").unwrap(), "15min".to_string())]
+    #[case(DurationAndUnit::from_str("2month").unwrap(), "2month".to_string())]
     fn test_fit_interval_to_req(#[case] input: DurationAndUnit, #[case] expected: String) {
         let kraken = Kraken::new();
         assert_eq!(kraken.fit_interval_to_req(&input).unwrap(), expected,);
     }
+
+    #[test]
+    fn test_parse_as_trades() {
+        let kraken = Kraken::new();
+
+        let input = r#"
+        {
+            "error": [],
+            "result": {
+                "XXBTZUSD": [
+                    [
+                        "30243.40000",
+                        "0.34507674",
+                        1688669597.8277369,
+                        "b",
+                        "m",
+                        "",
+                        61044952
+                    ],
+                    [
+                        "30243.30000",
+                        "0.00376960",
+                        1688669598.2804112,
+                        "s",
+                        "l",
+                        "",
+                        61044953
+                    ]
+                ],
+                "last": "1688669598280411266"
+            }
+        }"#
+        .to_string();
+        let result = kraken.parse_as_trades(input);
+        let expected = (
+            vec![
+                Trade {
+                    unixtime_msec: 1688669597827,
+                    price: 30243.4,
+                    size: 0.34507674,
+                },
+                Trade {
+                    unixtime_msec: 1688669598280,
+                    price: 30243.3,
+                    size: 0.0037696,
+                },
+            ],
+            1688669598280411266,
+        );
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_listings() {
+        let input = r#"
+        {
+            "error": [],
+            "result": {
+                "XXBTZUSD": {
+                    "altname": "XBTUSD",
+                    "wsname": "XBT/USD",
+                    "base": "XXBT",
+                    "quote": "ZUSD",
+                    "status": "online"
+                },
+                "XDGUSD": {
+                    "altname": "XDGUSD",
+                    "wsname": "XDG/USD",
+                    "base": "XXDG",
+                    "quote": "ZUSD",
+                    "status": "online"
+                },
+                "LUNAUSD": {
+                    "altname": "LUNAUSD",
+                    "wsname": "LUNA/USD",
+                    "base": "LUNA",
+                    "quote": "ZUSD",
+                    "status": "delisted"
+                }
+            }
+        }"#;
+
+        let mut result = Kraken::new().parse_as_listings(input).unwrap();
+        result.sort();
+
+        assert_eq!(
+            result.iter().map(Listing::symbol).collect::<Vec<String>>(),
+            vec!["BTC/USD", "DOGE/USD"]
+        );
+        assert_eq!(result[1].native, "XDGUSD");
+    }
 }
@@ -0,0 +1,534 @@
+use anyhow::Error;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+use crate::{args::*, error::*, exchange::*, unit::*};
+
+const ENDPOINT_SPOT: &str = "https://api.kucoin.com/api/v1/market/candles";
+const ENDPOINT_PERPETUAL: &str = "https://api-futures.kucoin.com/api/v1/kline/query";
+const ENDPOINT_CONTRACTS: &str = "https://api-futures.kucoin.com/api/v1/contracts";
+const ENDPOINT_SYMBOLS_SPOT: &str = "https://api.kucoin.com/api/v2/symbols";
+const ENDPOINT_TIME_SPOT: &str = "https://api.kucoin.com/api/v1/timestamp";
+const ENDPOINT_TIME_PERPETUAL: &str = "https://api-futures.kucoin.com/api/v1/timestamp";
+/// Spot returns up to this many candles (newest first)
+const LIMIT_SPOT: i64 = 1500;
+/// Futures returns up to this many candles (oldest first)
+const LIMIT_PERPETUAL: i64 = 500;
+
+#[derive(Debug, Clone)]
+pub struct Kucoin {
+    params: Vec<(String, String)>,
+    market_type: MarketType,
+    endpoint: String,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    code: String,
+    msg: Option<String>,
+    data: Option<Vec<Vec<serde_json::Value>>>,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnTime {
+    data: i64,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnContract {
+    code: String,
+    data: Option<ContractInResponse>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContractInResponse {
+    /// Size of a contract, in the base currency (negative for inverse contracts, in USD)
+    multiplier: f64,
+    is_inverse: bool,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnSymbols {
+    code: String,
+    msg: Option<String>,
+    data: Option<Vec<SymbolToList>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SymbolToList {
+    symbol: String,
+    base_currency: String,
+    quote_currency: String,
+    /// Spot only
+    #[serde(default)]
+    enable_trading: bool,
+    /// Perpetual only, `Open` and so on
+    #[serde(default)]
+    status: String,
+}
+
+impl Kucoin {
+    pub fn new() -> Self {
+        Kucoin {
+            params: Vec::new(),
+            market_type: MarketType::Spot,
+            endpoint: String::new(),
+        }
+    }
+
+    fn contract_size(data: &str) -> Result<NativeVolume, Error> {
+        let response = serde_json::from_str::<ResponseOnContract>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+        let contract = match (response.code.as_str(), response.data) {
+            ("200000", Some(contract)) => contract,
+            _ => return Err(ExchangeResponseError::symbol()),
+        };
+
+        Ok(NativeVolume::Contracts {
+            size: contract.multiplier.abs(),
+            inverse: contract.is_inverse,
+        })
+    }
+
+    /// Symbols which are tradable, `self` is of the market type to list.
+    fn parse_as_listings(&self, data: &str) -> Result<Vec<Listing>, Error> {
+        let response = serde_json::from_str::<ResponseOnSymbols>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+        let symbols = match (response.code.as_str(), response.data) {
+            ("200000", Some(symbols)) => symbols,
+            _ => {
+                return Err(ExchangeResponseError::wrap_error(
+                    response.msg.unwrap_or_default(),
+                ))
+            }
+        };
+
+        let aliases = match self.market_type {
+            MarketType::Spot => &[],
+            _ => BTC_AS_XBT,
+        };
+
+        Ok(symbols
+            .iter()
+            .filter(|symbol| symbol.enable_trading || symbol.status == "Open")
+            .map(|symbol| {
+                Listing::new(
+                    &symbol.base_currency,
+                    &symbol.quote_currency,
+                    &symbol.symbol,
+                    aliases,
+                )
+            })
+            // Futures with an expiry are also active contracts
+            .filter(|listing| {
+                self.fit_symbol_to_req(&listing.symbol())
+                    .is_ok_and(|symbol| symbol == listing.native)
+            })
+            .collect())
+    }
+}
+
+impl Retrieve for Kucoin {
+    // KuCoin's timestamps are non-millisec for spot, millisec for futures
+
+    fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        if let MarketType::Futures | MarketType::Option = args.type_ {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        self.market_type = args.type_.clone();
+        let interval = self.fit_interval_to_req(&args.interval)?;
+        let symbol = self.fit_symbol_to_req(&args.symbol)?;
+        let (term_start, term_end) = (args.term_start.unwrap(), args.term_end.unwrap());
+
+        match args.type_ {
+            MarketType::Spot => {
+                // Newest first, so the period is cut from `term_end`
+                let term_start =
+                    term_start.max(term_end - (LIMIT_SPOT - 1) * args.interval.to_msec());
+
+                self.endpoint = ENDPOINT_SPOT.to_string();
+                self.params = [
+                    ("symbol".to_string(), symbol),
+                    ("type".to_string(), interval),
+                    ("startAt".to_string(), (term_start / 1000).to_string()),
+                    ("endAt".to_string(), (term_end / 1000).to_string()),
+                ]
+                .to_vec();
+            }
+            MarketType::Perpetual => {
+                // There is no `limit`, so cut the period into windows (the generic loop moves on to the next one)
+                let term_end = term_end.min(term_start + self.window(args).unwrap());
+
+                self.endpoint = ENDPOINT_PERPETUAL.to_string();
+                self.params = [
+                    ("symbol".to_string(), symbol),
+                    ("granularity".to_string(), interval),
+                    ("from".to_string(), term_start.to_string()),
+                    ("to".to_string(), term_end.to_string()),
+                ]
+                .to_vec();
+            }
+            _ => unreachable!(/* Checked above */),
+        }
+
+        Ok(())
+    }
+
+    fn window(&self, args: &ParsedArgs) -> Option<i64> {
+        // Spot pages back from `term_end` by the limit
+        match args.type_ {
+            MarketType::Perpetual => Some((LIMIT_PERPETUAL - 1) * args.interval.to_msec()),
+            _ => None,
+        }
+    }
+
+    fn fetch(&self, client: &Client) -> Result<String, Error> {
+        let res = client
+            .get(&self.endpoint)
+            .query(&self.params)
+            .send()?
+            .text()?;
+
+        let response = serde_json::from_str::<Response>(&res)
+            .expect("Unexpected error! Failed to parse response (for error code) to json.");
+        match response.code.as_str() {
+            "200000" => {}
+            "429000" => return Err(ExchangeResponseError::too_many_requests()),
+            // If the validation of `interval` is complete, then the cause of the error
+            // can be identified as a symbol only
+            "400100" => return Err(ExchangeResponseError::symbol()),
+            _ => {
+                return Err(ExchangeResponseError::wrap_error(
+                    response.msg.unwrap_or(response.code),
+                ))
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn server_time(&self, client: &Client, market_type: &MarketType) -> Result<Option<i64>, Error> {
+        let endpoint = match market_type {
+            MarketType::Spot => ENDPOINT_TIME_SPOT,
+            _ => ENDPOINT_TIME_PERPETUAL,
+        };
+
+        let res = client.get(endpoint).send()?.json::<ResponseOnTime>()?;
+
+        Ok(Some(res.data))
+    }
+
+    fn native_volume(&self, client: &Client, args: &ParsedArgs) -> Result<NativeVolume, Error> {
+        if let MarketType::Spot = args.type_ {
+            return Ok(NativeVolume::Base);
+        }
+
+        let res = client
+            .get(format!(
+                "{}/{}",
+                ENDPOINT_CONTRACTS,
+                self.fit_symbol_to_req(&args.symbol)?
+            ))
+            .send()?
+            .text()?;
+
+        Self::contract_size(&res)
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        let endpoint = match market_type {
+            MarketType::Spot => ENDPOINT_SYMBOLS_SPOT.to_string(),
+            MarketType::Perpetual => format!("{}/active", ENDPOINT_CONTRACTS),
+            _ => return Err(ExchangeResponseError::no_support_type()),
+        };
+        let res = client.get(endpoint).send()?.text()?;
+
+        Kucoin {
+            market_type: market_type.clone(),
+            ..Kucoin::new()
+        }
+        .parse_as_listings(&res)
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        match self.market_type {
+            MarketType::Spot => {
+                let (base, quote) = split_symbol(symbol, &[])?;
+                Ok(format!("{}-{}", base, quote))
+            }
+            // Like `XBTUSDTM` (USDT-margined) and `XBTUSDM` (inverse)
+            _ => {
+                let (base, quote) = split_symbol(symbol, BTC_AS_XBT)?;
+                Ok(format!("{}{}M", base, quote))
+            }
+        }
+    }
+
+    fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
+        let result = match self.market_type {
+            MarketType::Spot => match (interval.0, &interval.1) {
+                (1 | 3 | 5 | 15 | 30, TermUnit::Min) => Some(format!("{}min", interval.0)),
+                (1 | 2 | 4 | 6 | 8 | 12, TermUnit::Hour) => Some(format!("{}hour", interval.0)),
+                (1, TermUnit::Day) => Some("1day".to_string()),
+                (1, TermUnit::Week) => Some("1week".to_string()),
+                _ => None,
+            },
+            // In minutes
+            _ => match (interval.0, &interval.1) {
+                (1 | 5 | 15 | 30, TermUnit::Min) => Some(interval.0.to_string()),
+                (1 | 2 | 4 | 8 | 12, TermUnit::Hour) => Some((interval.0 as u32 * 60).to_string()),
+                (1, TermUnit::Day) => Some("1440".to_string()),
+                (1, TermUnit::Week) => Some("10080".to_string()),
+                _ => None,
+            },
+        };
+
+        result.ok_or_else(|| {
+            ExchangeResponseError::interval(&ExchangeChoices::Kucoin, &self.market_type)
+        })
+    }
+
+    fn parse_as_kline(&self, data: String) -> Vec<Kline> {
+        let raws = serde_json::from_str::<Response>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .data
+            .unwrap(/* Error handling has already been completed in `fetch()` */);
+
+        match self.market_type {
+            // [time (sec), open, close, high, low, volume, turnover] in strings
+            MarketType::Spot => raws
+                .iter()
+                .map(|raw| Kline {
+                    unixtime_msec: raw[0].as_str().unwrap().parse::<i64>().unwrap() * 1000,
+                    o: raw[1].as_str().unwrap().parse::<f64>().unwrap(),
+                    h: raw[3].as_str().unwrap().parse::<f64>().unwrap(),
+                    l: raw[4].as_str().unwrap().parse::<f64>().unwrap(),
+                    c: raw[2].as_str().unwrap().parse::<f64>().unwrap(),
+                    v: raw[5].as_str().unwrap().parse::<f64>().unwrap(),
+                    extra: KlineExtra::default(),
+                })
+                .collect(),
+            // [time (msec), open, high, low, close, volume] in numbers
+            _ => raws
+                .iter()
+                .map(|raw| Kline {
+                    unixtime_msec: raw[0].as_i64().unwrap(),
+                    o: raw[1].as_f64().unwrap(),
+                    h: raw[2].as_f64().unwrap(),
+                    l: raw[3].as_f64().unwrap(),
+                    c: raw[4].as_f64().unwrap(),
+                    v: raw[5].as_f64().unwrap(),
+                    extra: KlineExtra::default(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case(MarketType::Spot, "BTC/USDT", "BTC-USDT".to_string())]
+    #[case(MarketType::Perpetual, "BTC/USDT", "XBTUSDTM".to_string())]
+    #[case(MarketType::Perpetual, "ETH/USD", "ETHUSDM".to_string())]
+    fn test_fit_symbol_to_req(
+        #[case] market_type: MarketType,
+        #[case] input: &str,
+        #[case] expected: String,
+    ) {
+        let mut kucoin = Kucoin::new();
+        kucoin.market_type = market_type;
+        assert_eq!(kucoin.fit_symbol_to_req(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(MarketType::Spot, DurationAndUnit::from_str("6hour").unwrap(), "6hour".to_string())]
+    #[case(MarketType::Perpetual, DurationAndUnit::from_str("8hour").unwrap(), "480".to_string())]
+    #[case(MarketType::Perpetual, DurationAndUnit::from_str("1week").unwrap(), "10080".to_string())]
+    #[should_panic]
+    #[case(MarketType::Perpetual, DurationAndUnit::from_str("3min").unwrap(), "panic".to_string())]
+    fn test_fit_interval_to_req(
+        #[case] market_type: MarketType,
+        #[case] input: DurationAndUnit,
+        #[case] expected: String,
+    ) {
+        let mut kucoin = Kucoin::new();
+        kucoin.market_type = market_type;
+        assert_eq!(kucoin.fit_interval_to_req(&input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline_spot() {
+        let kucoin = Kucoin::new();
+
+        let input = r#"
+        {
+            "code": "200000",
+            "data": [
+                [
+                    "1689811200",
+                    "29890.1",
+                    "29906.4",
+                    "29912.3",
+                    "29880",
+                    "12.50921",
+                    "374082.71"
+                ],
+                [
+                    "1689810300",
+                    "29870",
+                    "29890.1",
+                    "29895.5",
+                    "29866.2",
+                    "8.20133",
+                    "245072.26"
+                ]
+            ]
+        }"#
+        .to_string();
+        let result = kucoin.parse_as_kline(input);
+        let expected = vec![
+            Kline {
+                unixtime_msec: 1689811200000,
+                o: 29890.1,
+                h: 29912.3,
+                l: 29880.0,
+                c: 29906.4,
+                v: 12.50921,
+                extra: KlineExtra::default(),
+            },
+            Kline {
+                unixtime_msec: 1689810300000,
+                o: 29870.0,
+                h: 29895.5,
+                l: 29866.2,
+                c: 29890.1,
+                v: 8.20133,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline_perpetual() {
+        let mut kucoin = Kucoin::new();
+        kucoin.market_type = MarketType::Perpetual;
+
+        let input = r#"
+        {
+            "code": "200000",
+            "data": [
+                [1689810300000, 29868.0, 29894.0, 29865.0, 29889.0, 151029],
+                [1689811200000, 29889.0, 29911.0, 29879.0, 29905.0, 98423]
+            ]
+        }"#
+        .to_string();
+        let result = kucoin.parse_as_kline(input);
+        let expected = vec![
+            Kline {
+                unixtime_msec: 1689810300000,
+                o: 29868.0,
+                h: 29894.0,
+                l: 29865.0,
+                c: 29889.0,
+                v: 151029.0,
+                extra: KlineExtra::default(),
+            },
+            Kline {
+                unixtime_msec: 1689811200000,
+                o: 29889.0,
+                h: 29911.0,
+                l: 29879.0,
+                c: 29905.0,
+                v: 98423.0,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case(r#"{"code": "200000", "data": {"symbol": "XBTUSDTM", "multiplier": 0.001, "isInverse": false}}"#, Some(NativeVolume::Contracts { size: 0.001, inverse: false }))]
+    #[case(r#"{"code": "200000", "data": {"symbol": "XBTUSDM", "multiplier": -1, "isInverse": true}}"#, Some(NativeVolume::Contracts { size: 1.0, inverse: true }))]
+    #[case(r#"{"code": "40010", "msg": "Contract does not exist"}"#, None)]
+    fn test_contract_size(#[case] input: &str, #[case] expected: Option<NativeVolume>) {
+        assert_eq!(Kucoin::contract_size(input).ok(), expected);
+    }
+
+    #[rstest]
+    #[case(
+        MarketType::Spot,
+        r#"
+        {
+            "code": "200000",
+            "data": [
+                {
+                    "symbol": "BTC-USDT",
+                    "baseCurrency": "BTC",
+                    "quoteCurrency": "USDT",
+                    "enableTrading": true
+                },
+                {
+                    "symbol": "LUNA-USDT",
+                    "baseCurrency": "LUNA",
+                    "quoteCurrency": "USDT",
+                    "enableTrading": false
+                }
+            ]
+        }"#,
+        vec![Listing::new("BTC", "USDT", "BTC-USDT", &[])]
+    )]
+    #[case(
+        MarketType::Perpetual,
+        r#"
+        {
+            "code": "200000",
+            "data": [
+                {
+                    "symbol": "XBTUSDTM",
+                    "baseCurrency": "XBT",
+                    "quoteCurrency": "USDT",
+                    "type": "FFWCSX",
+                    "status": "Open"
+                },
+                {
+                    "symbol": "XBTMH24",
+                    "baseCurrency": "XBT",
+                    "quoteCurrency": "USD",
+                    "type": "FFICSX",
+                    "status": "Open"
+                }
+            ]
+        }"#,
+        vec![Listing::new("XBT", "USDT", "XBTUSDTM", BTC_AS_XBT)]
+    )]
+    fn test_parse_as_listings(
+        #[case] market_type: MarketType,
+        #[case] input: &str,
+        #[case] expected: Vec<Listing>,
+    ) {
+        let kucoin = Kucoin {
+            market_type,
+            ..Kucoin::new()
+        };
+
+        assert_eq!(kucoin.parse_as_listings(input).unwrap(), expected);
+    }
+}
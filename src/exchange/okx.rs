@@ -5,19 +5,64 @@ use serde::Deserialize;
 
 use crate::{args::*, error::*, exchange::*, unit::*};
 
+const ENDPOINT_BASE: &str = "https://www.okx.com/api/v5/market";
+const ENDPOINT_FUNDING: &str = "https://www.okx.com/api/v5/public/funding-rate-history";
+const LIMIT_FUNDING: i32 = 100;
+const ENDPOINT_OPEN_INTEREST: &str =
+    "https://www.okx.com/api/v5/rubik/stat/contracts/open-interest-history";
+const LIMIT_OPEN_INTEREST: i32 = 100;
+const ENDPOINT_INSTRUMENTS: &str = "https://www.okx.com/api/v5/public/instruments";
+
 #[derive(Debug, Clone)]
 pub struct Okx {
     params: Vec<(String, String)>,
     market_type: MarketType,
     endpoint: String,
     limit: i32,
+    price_type: PriceType,
 }
 
 #[derive(Deserialize)]
-struct Response {
+// Generic over the items of `data`, which are arrays for klines and objects for funding rates
+struct Response<T> {
     code: String,
     msg: String,
-    data: Vec<Vec<String>>,
+    data: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnTime {
+    data: Vec<TimeInResponse>,
+}
+
+#[derive(Deserialize)]
+struct TimeInResponse {
+    ts: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FundingRate {
+    funding_rate: String,
+    funding_time: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Instrument {
+    /// In the base currency for linear contracts, and in the quote currency for inverse ones
+    ct_val: String,
+    /// `linear` or `inverse`
+    ct_type: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InstrumentToList {
+    /// Like `BTC-USDT` and `BTC-USDT-SWAP`
+    inst_id: String,
+    /// `live`, `suspend`, `preopen` and so on
+    state: String,
 }
 
 impl Okx {
@@ -25,22 +70,88 @@ impl Okx {
         Okx {
             params: Vec::new(),
             market_type: MarketType::Spot,
-            endpoint: "https://www.okx.com/api/v5/market/history-candles".to_string(),
+            endpoint: format!("{}/history-candles", ENDPOINT_BASE),
             limit: 300,
+            price_type: PriceType::Last,
         }
     }
+
+    fn contract_size(data: &str) -> Result<NativeVolume, Error> {
+        let response = serde_json::from_str::<Response<Instrument>>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+        let instrument = response
+            .data
+            .first()
+            .ok_or(ExchangeResponseError::symbol())?;
+
+        Ok(NativeVolume::Contracts {
+            size: instrument
+                .ct_val
+                .parse::<f64>()
+                .map_err(|_| ExchangeResponseError::unknown())?,
+            inverse: instrument.ct_type == "inverse",
+        })
+    }
+
+    /// Instruments which are live, `self` is of the market type to list.
+    fn parse_as_listings(&self, data: &str) -> Result<Vec<Listing>, Error> {
+        let response = serde_json::from_str::<Response<InstrumentToList>>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+        if response.code != "0" {
+            return Err(ExchangeResponseError::wrap_error(response.msg));
+        }
+
+        Ok(response
+            .data
+            .iter()
+            .filter(|instrument| instrument.state == "live")
+            .filter_map(|instrument| {
+                let pair = match self.market_type {
+                    MarketType::Perpetual => instrument.inst_id.strip_suffix("-SWAP")?,
+                    _ => &instrument.inst_id,
+                };
+                let (base, quote) = pair.split_once('-')?;
+                let listing = Listing::new(base, quote, &instrument.inst_id, &[]);
+
+                (self.fit_symbol_to_req(&listing.symbol()).ok()? == pair).then_some(listing)
+            })
+            .collect())
+    }
 }
 
 impl Retrieve for Okx {
     fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        if let MarketType::Futures | MarketType::Option = args.type_ {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        let path = match (&args.type_, &args.price_type) {
+            (_, PriceType::Last) => "history-candles",
+            (MarketType::Spot, _) => {
+                return Err(anyhow!(
+                    "OKX does not support `--price-type` other than `last` with `--type spot`."
+                ))
+            }
+            (_, PriceType::Mark) => "history-mark-price-candles",
+            (_, PriceType::Index) => "history-index-candles",
+            (_, PriceType::Premium) => {
+                return Err(anyhow!("OKX does not support `--price-type premium`."))
+            }
+        };
+        self.endpoint = format!("{}/{}", ENDPOINT_BASE, path);
+
         self.params = [
             (
                 "instId".to_string(),
-                match args.type_ {
-                    MarketType::Spot => self.fit_symbol_to_req(&args.symbol)?,
-                    MarketType::Perpetual => {
+                match (&args.type_, &args.price_type) {
+                    // The index is of the pair, not of the contract
+                    (MarketType::Spot, _) | (_, PriceType::Index) => {
+                        self.fit_symbol_to_req(&args.symbol)?
+                    }
+                    (MarketType::Perpetual, _) => {
                         format!("{}-SWAP", self.fit_symbol_to_req(&args.symbol)?)
                     }
+                    _ => unreachable!(/* Checked above */),
                 },
             ),
             ("bar".to_string(), self.fit_interval_to_req(&args.interval)?),
@@ -56,11 +167,65 @@ impl Retrieve for Okx {
         ]
         .to_vec();
 
-        match args.type_ {
-            MarketType::Spot => self.market_type = MarketType::Spot,
-            MarketType::Perpetual => self.market_type = MarketType::Perpetual,
+        self.market_type = args.type_.clone();
+        self.price_type = args.price_type.clone();
+
+        Ok(())
+    }
+
+    fn prepare_funding(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        self.market_type = args.type_.clone();
+        self.endpoint = ENDPOINT_FUNDING.to_string();
+        self.params = [
+            (
+                "instId".to_string(),
+                format!("{}-SWAP", self.fit_symbol_to_req(&args.symbol)?),
+            ),
+            (
+                "before".to_string(), // Opposite of the word meaning
+                args.term_start.unwrap().to_string(),
+            ),
+            (
+                "after".to_string(), // Same as above
+                args.term_end.unwrap().to_string(),
+            ),
+            ("limit".to_string(), LIMIT_FUNDING.to_string()),
+        ]
+        .to_vec();
+
+        Ok(())
+    }
+
+    fn prepare_open_interest(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        let period = match (args.interval.0, &args.interval.1) {
+            (5 | 15 | 30, TermUnit::Min) => format!("{}m", args.interval.0),
+            (1 | 2 | 4 | 6 | 12, TermUnit::Hour) => format!("{}H", args.interval.0),
+            (1, TermUnit::Day) => "1D".to_string(),
+            _ => {
+                return Err(ExchangeResponseError::interval_of(
+                    &[
+                        "5min", "15min", "30min", "1hour", "2hour", "4hour", "6hour", "12hour",
+                        "1day",
+                    ]
+                    .map(String::from),
+                ))
+            }
         };
 
+        self.market_type = args.type_.clone();
+        self.endpoint = ENDPOINT_OPEN_INTEREST.to_string();
+        self.params = [
+            (
+                "instId".to_string(),
+                format!("{}-SWAP", self.fit_symbol_to_req(&args.symbol)?),
+            ),
+            ("period".to_string(), period),
+            ("begin".to_string(), args.term_start.unwrap().to_string()),
+            ("end".to_string(), args.term_end.unwrap().to_string()),
+            ("limit".to_string(), LIMIT_OPEN_INTEREST.to_string()),
+        ]
+        .to_vec();
+
         Ok(())
     }
 
@@ -71,7 +236,7 @@ impl Retrieve for Okx {
             .send()?
             .text()?;
 
-        let response = serde_json::from_str::<Response>(&res)
+        let response = serde_json::from_str::<Response<serde_json::Value>>(&res)
             .expect("Unexpected error! Failed to parse response (for error code) to json.");
         match response.code.as_str() {
             "0" => (/* Succeeded! */),
@@ -93,6 +258,66 @@ impl Retrieve for Okx {
         Ok(res)
     }
 
+    fn server_time(
+        &self,
+        client: &Client,
+        _market_type: &MarketType,
+    ) -> Result<Option<i64>, Error> {
+        let res = client
+            .get("https://www.okx.com/api/v5/public/time")
+            .send()?
+            .json::<ResponseOnTime>()?;
+
+        Ok(res
+            .data
+            .first()
+            .and_then(|time| time.ts.parse::<i64>().ok()))
+    }
+
+    fn native_volume(&self, client: &Client, args: &ParsedArgs) -> Result<NativeVolume, Error> {
+        if let MarketType::Spot = args.type_ {
+            return Ok(NativeVolume::Base);
+        }
+
+        let res = client
+            .get(ENDPOINT_INSTRUMENTS)
+            .query(&[
+                ("instType", "SWAP".to_string()),
+                (
+                    "instId",
+                    format!("{}-SWAP", self.fit_symbol_to_req(&args.symbol)?),
+                ),
+            ])
+            .send()?
+            .text()?;
+
+        Self::contract_size(&res)
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        let inst_type = match market_type {
+            MarketType::Spot => "SPOT",
+            MarketType::Perpetual => "SWAP",
+            _ => return Err(ExchangeResponseError::no_support_type()),
+        };
+        let res = client
+            .get(ENDPOINT_INSTRUMENTS)
+            .query(&[("instType", inst_type)])
+            .send()?
+            .text()?;
+
+        Okx {
+            market_type: market_type.clone(),
+            ..Okx::new()
+        }
+        .parse_as_listings(&res)
+    }
+
     fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
         // Almost same code as Binance, so the test already exists
 
@@ -121,7 +346,7 @@ impl Retrieve for Okx {
     }
 
     fn parse_as_kline(&self, data: String) -> Vec<Kline> {
-        serde_json::from_str::<Response>(&data)
+        serde_json::from_str::<Response<Vec<String>>>(&data)
             .expect("Unexpected error! Failed to parse response to json.")
             .data
             .iter()
@@ -131,7 +356,49 @@ impl Retrieve for Okx {
                 h: raw[2].as_str().to_owned().parse::<f64>().unwrap(),
                 l: raw[3].as_str().to_owned().parse::<f64>().unwrap(),
                 c: raw[4].as_str().to_owned().parse::<f64>().unwrap(),
-                v: raw[5].as_str().to_owned().parse::<f64>().unwrap(),
+                // Other than the last price, there is no volume (output as null by `retrieve()`,
+                // the 6th is whether it is confirmed)
+                v: match self.price_type {
+                    PriceType::Last => raw[5].as_str().to_owned().parse::<f64>().unwrap(),
+                    _ => 0.0,
+                },
+                // `volCcyQuote`, while `volCcy` is in the base currency for derivatives
+                // (and the same as `volCcyQuote` for spot)
+                extra: KlineExtra {
+                    quote_volume: match self.price_type {
+                        PriceType::Last => raw.get(7).and_then(|vol| vol.parse::<f64>().ok()),
+                        _ => None,
+                    },
+                    ..Default::default()
+                },
+            })
+            .collect()
+    }
+
+    fn parse_as_funding(&self, data: String) -> Vec<Funding> {
+        // Newest first
+        serde_json::from_str::<Response<FundingRate>>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .data
+            .iter()
+            .map(|funding| Funding {
+                unixtime_msec: funding.funding_time.parse::<i64>().unwrap(),
+                rate: funding.funding_rate.parse::<f64>().unwrap(),
+                mark_price: None,
+            })
+            .collect()
+    }
+
+    fn parse_as_open_interest(&self, data: String) -> Vec<OpenInterest> {
+        // Newest first, and each is `[ts, oi (contracts), oiCcy, oiUsd]`
+        serde_json::from_str::<Response<Vec<String>>>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .data
+            .iter()
+            .map(|raw| OpenInterest {
+                unixtime_msec: raw[0].parse::<i64>().unwrap(),
+                amount: raw[2].parse::<f64>().unwrap(),
+                value: raw[3].parse::<f64>().ok(),
             })
             .collect()
     }
@@ -184,6 +451,10 @@ mod tests {
                 l: 28499.9,
                 c: 28501.2,
                 v: 20.39751017,
+                extra: KlineExtra {
+                    quote_volume: Some(582029.970323109),
+                    ..Default::default()
+                },
             },
             Kline {
                 unixtime_msec: 1683040860000,
@@ -192,9 +463,205 @@ mod tests {
                 l: 28539.5,
                 c: 28547.9,
                 v: 45.63491347,
+                extra: KlineExtra {
+                    quote_volume: Some(1304118.941650916),
+                    ..Default::default()
+                },
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline_mark_price() {
+        let mut okx = Okx::new();
+        okx.price_type = PriceType::Mark;
+
+        let input = r#"
+        {
+            "code": "0",
+            "msg": "",
+            "data": [
+              [
+                "1683040920000",
+                "28550.1",
+                "28561.8",
+                "28502.3",
+                "28503.0",
+                "1"
+              ]
+            ]
+        }"#
+        .to_string();
+        let result = okx.parse_as_kline(input);
+        let expected = vec![Kline {
+            unixtime_msec: 1683040920000,
+            o: 28550.1,
+            h: 28561.8,
+            l: 28502.3,
+            c: 28503.0,
+            v: 0.0,
+            extra: KlineExtra::default(),
+        }];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_funding() {
+        let okx = Okx::new();
+
+        let input = r#"
+        {
+            "code": "0",
+            "msg": "",
+            "data": [
+                {
+                    "fundingRate": "0.0000746",
+                    "fundingTime": "1698796800000",
+                    "instId": "BTC-USDT-SWAP",
+                    "instType": "SWAP",
+                    "method": "current_period",
+                    "realizedRate": "0.0000746"
+                },
+                {
+                    "fundingRate": "0.0001",
+                    "fundingTime": "1698768000000",
+                    "instId": "BTC-USDT-SWAP",
+                    "instType": "SWAP",
+                    "method": "current_period",
+                    "realizedRate": "0.0001"
+                }
+            ]
+        }"#
+        .to_string();
+        let result = okx.parse_as_funding(input);
+        let expected = vec![
+            Funding {
+                unixtime_msec: 1698796800000,
+                rate: 0.0000746,
+                mark_price: None,
+            },
+            Funding {
+                unixtime_msec: 1698768000000,
+                rate: 0.0001,
+                mark_price: None,
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_open_interest() {
+        let okx = Okx::new();
+
+        let input = r#"
+        {
+            "code": "0",
+            "msg": "",
+            "data": [
+                ["1698796800000", "2902531.79", "29025.3179", "1006129543.2"],
+                ["1698793200000", "2899811.12", "28998.1112", "1002511312.7"]
+            ]
+        }"#
+        .to_string();
+        let result = okx.parse_as_open_interest(input);
+        let expected = vec![
+            OpenInterest {
+                unixtime_msec: 1698796800000,
+                amount: 29025.3179,
+                value: Some(1006129543.2),
+            },
+            OpenInterest {
+                unixtime_msec: 1698793200000,
+                amount: 28998.1112,
+                value: Some(1002511312.7),
             },
         ];
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_contract_size() {
+        let input = r#"
+        {
+            "code": "0",
+            "msg": "",
+            "data": [
+                {
+                    "instType": "SWAP",
+                    "instId": "BTC-USD-SWAP",
+                    "ctType": "inverse",
+                    "ctVal": "100",
+                    "ctValCcy": "USD",
+                    "settleCcy": "BTC"
+                }
+            ]
+        }"#;
+
+        assert_eq!(
+            Okx::contract_size(input).unwrap(),
+            NativeVolume::Contracts {
+                size: 100.0,
+                inverse: true,
+            }
+        );
+        assert!(Okx::contract_size(r#"{"code": "51001", "msg": "", "data": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_as_listings() {
+        let input = r#"
+        {
+            "code": "0",
+            "msg": "",
+            "data": [
+                {
+                    "instType": "SWAP",
+                    "instId": "BTC-USDT-SWAP",
+                    "uly": "BTC-USDT",
+                    "state": "live"
+                },
+                {
+                    "instType": "SWAP",
+                    "instId": "BTC-USD-SWAP",
+                    "uly": "BTC-USD",
+                    "state": "live"
+                },
+                {
+                    "instType": "SWAP",
+                    "instId": "TRUMP-USDT-SWAP",
+                    "uly": "TRUMP-USDT",
+                    "state": "preopen"
+                }
+            ]
+        }"#;
+
+        let okx = Okx {
+            market_type: MarketType::Perpetual,
+            ..Okx::new()
+        };
+        let result: Vec<(String, String)> = okx
+            .parse_as_listings(input)
+            .unwrap()
+            .into_iter()
+            .map(|listing| (listing.symbol(), listing.native))
+            .collect();
+
+        assert_eq!(
+            result,
+            vec![
+                ("BTC/USDT".to_string(), "BTC-USDT-SWAP".to_string()),
+                ("BTC/USD".to_string(), "BTC-USD-SWAP".to_string()),
+            ]
+        );
+        assert!(okx
+            .parse_as_listings(
+                r#"{"code": "51000", "msg": "Parameter instType error", "data": []}"#
+            )
+            .is_err());
+    }
 }
@@ -0,0 +1,307 @@
+use anyhow::Error;
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use reqwest::{blocking::Client, StatusCode};
+use serde::Deserialize;
+
+use crate::{args::*, error::*, exchange::*, unit::*};
+
+const HOST: &str = "https://api.upbit.com";
+const LIMIT: i32 = 200;
+
+#[derive(Debug, Clone)]
+pub struct Upbit {
+    params: Vec<(String, String)>,
+    market_type: MarketType,
+    endpoint: String,
+    /// Compatible APIs on other exchanges (Bithumb) differ only in these
+    host: &'static str,
+    exchange: ExchangeChoices,
+}
+
+#[derive(Deserialize)]
+struct ResponseOnError {
+    error: ErrorInResponse,
+}
+
+#[derive(Deserialize)]
+struct ErrorInResponse {
+    name: serde_json::Value,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct Candle {
+    candle_date_time_utc: String,
+    opening_price: f64,
+    high_price: f64,
+    low_price: f64,
+    trade_price: f64,
+    candle_acc_trade_volume: f64,
+}
+
+#[derive(Deserialize)]
+struct MarketToList {
+    /// Like `KRW-BTC`, the quote currency comes first
+    market: String,
+}
+
+impl Upbit {
+    pub fn new() -> Self {
+        Self::compatible(ExchangeChoices::Upbit, HOST)
+    }
+
+    /// For exchanges which provide the same API as Upbit on another host.
+    pub fn compatible(exchange: ExchangeChoices, host: &'static str) -> Self {
+        Upbit {
+            params: Vec::new(),
+            market_type: MarketType::Spot,
+            endpoint: String::new(),
+            host,
+            exchange,
+        }
+    }
+
+    fn unixtime_to_rfc3339(unixtime: i64) -> String {
+        Utc.timestamp_millis_opt(unixtime)
+            .unwrap()
+            .format("%Y-%m-%dT%H:%M:%SZ")
+            .to_string()
+    }
+
+    fn parse_as_listings(data: &str) -> Result<Vec<Listing>, Error> {
+        let markets = serde_json::from_str::<Vec<MarketToList>>(data)
+            .map_err(|_| ExchangeResponseError::unknown())?;
+
+        Ok(markets
+            .iter()
+            .filter_map(|market| {
+                let (quote, base) = market.market.split_once('-')?;
+                Some(Listing::new(base, quote, &market.market, &[]))
+            })
+            .collect())
+    }
+}
+
+impl Retrieve for Upbit {
+    fn prepare(&mut self, args: &ParsedArgs) -> Result<(), Error> {
+        if !matches!(args.type_, MarketType::Spot) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        // There is only `to` (exclusive), so pages are walked backwards from `term_end`
+        // and the candles before `term_start` are removed in `remove_unnecessary_raws()`.
+        // Round up to seconds so as not to lose the candle at `term_end`.
+        let to = (args.term_end.unwrap() + 999) / 1000 * 1000;
+
+        self.endpoint = format!(
+            "{}/v1/candles/{}",
+            self.host,
+            self.fit_interval_to_req(&args.interval)?
+        );
+        self.params = [
+            ("market".to_string(), self.fit_symbol_to_req(&args.symbol)?),
+            ("to".to_string(), Self::unixtime_to_rfc3339(to)),
+            ("count".to_string(), LIMIT.to_string()),
+        ]
+        .to_vec();
+
+        Ok(())
+    }
+
+    fn fetch(&self, client: &Client) -> Result<String, Error> {
+        let res = client.get(&self.endpoint).query(&self.params).send()?;
+
+        // The body is not JSON in this case
+        if res.status() == StatusCode::TOO_MANY_REQUESTS {
+            return Err(ExchangeResponseError::too_many_requests());
+        }
+
+        let res = res.text()?;
+
+        if let Ok(response) = serde_json::from_str::<ResponseOnError>(&res) {
+            match response.error.message.as_str() {
+                "Code not found" => return Err(ExchangeResponseError::symbol()),
+                _ => {
+                    return Err(ExchangeResponseError::wrap_error(format!(
+                        "{} ({})",
+                        response.error.message, response.error.name
+                    )))
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn list_symbols(
+        &self,
+        client: &Client,
+        market_type: &MarketType,
+        _contract: &Contract,
+    ) -> Result<Vec<Listing>, Error> {
+        if !matches!(market_type, MarketType::Spot) {
+            return Err(ExchangeResponseError::no_support_type());
+        }
+
+        let res = client
+            .get(format!("{}/v1/market/all", self.host))
+            .send()?
+            .text()?;
+
+        Self::parse_as_listings(&res)
+    }
+
+    fn fit_symbol_to_req(&self, symbol: &str) -> Result<String, Error> {
+        // The quote currency comes first like `KRW-BTC`
+        let (base, quote) = split_symbol(symbol, &[])?;
+        Ok(format!("{}-{}", quote, base))
+    }
+
+    fn fit_interval_to_req(&self, interval: &DurationAndUnit) -> Result<String, Error> {
+        // Not a parameter but a path, minutes are the only ones with units
+        let result = match (interval.0, &interval.1) {
+            (1 | 3 | 5 | 10 | 15 | 30, TermUnit::Min) => format!("minutes/{}", interval.0),
+            (1 | 4, TermUnit::Hour) => format!("minutes/{}", interval.0 as u32 * 60),
+            (1, TermUnit::Day) => "days".to_string(),
+            (1, TermUnit::Week) => "weeks".to_string(),
+            (1, TermUnit::Month) => "months".to_string(),
+            _ => {
+                return Err(ExchangeResponseError::interval(
+                    &self.exchange,
+                    &self.market_type,
+                ))
+            }
+        };
+        Ok(result)
+    }
+
+    fn parse_as_kline(&self, data: String) -> Vec<Kline> {
+        serde_json::from_str::<Vec<Candle>>(&data)
+            .expect("Unexpected error! Failed to parse response to json.")
+            .iter()
+            .map(|candle| Kline {
+                // In UTC but without any offset
+                unixtime_msec: NaiveDateTime::parse_from_str(
+                    &candle.candle_date_time_utc,
+                    "%Y-%m-%dT%H:%M:%S",
+                )
+                .unwrap()
+                .timestamp_millis(),
+                o: candle.opening_price,
+                h: candle.high_price,
+                l: candle.low_price,
+                c: candle.trade_price,
+                v: candle.candle_acc_trade_volume,
+                extra: KlineExtra::default(),
+            })
+            .collect()
+    }
+
+    fn remove_unnecessary_raws(raws: Vec<Kline>, term_start: i64, term_end: i64) -> Vec<Kline> {
+        retain_in_term(raws, term_start, term_end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case("BTC/KRW", "KRW-BTC".to_string())]
+    #[case("ETH/BTC", "BTC-ETH".to_string())]
+    fn test_fit_symbol_to_req(#[case] input: &str, #[case] expected: String) {
+        let upbit = Upbit::new();
+        assert_eq!(upbit.fit_symbol_to_req(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(DurationAndUnit::from_str("10min").unwrap(), "minutes/10".to_string())]
+    #[case(DurationAndUnit::from_str("4hour").unwrap(), "minutes/240".to_string())]
+    #[case(DurationAndUnit::from_str("1week").unwrap(), "weeks".to_string())]
+    #[should_panic]
+    #[case(DurationAndUnit::from_str("2hour").unwrap(), "panic".to_string())]
+    fn test_fit_interval_to_req(#[case] input: DurationAndUnit, #[case] expected: String) {
+        let upbit = Upbit::new();
+        assert_eq!(upbit.fit_interval_to_req(&input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parse_as_kline() {
+        let upbit = Upbit::new();
+
+        let input = r#"
+        [
+            {
+                "market": "KRW-BTC",
+                "candle_date_time_utc": "2023-07-20T00:15:00",
+                "candle_date_time_kst": "2023-07-20T09:15:00",
+                "opening_price": 38600000.0,
+                "high_price": 38622000.0,
+                "low_price": 38590000.0,
+                "trade_price": 38611000.0,
+                "timestamp": 1689812099923,
+                "candle_acc_trade_price": 420012345.12,
+                "candle_acc_trade_volume": 10.8799,
+                "unit": 15
+            },
+            {
+                "market": "KRW-BTC",
+                "candle_date_time_utc": "2023-07-20T00:00:00",
+                "candle_date_time_kst": "2023-07-20T09:00:00",
+                "opening_price": 38570000.0,
+                "high_price": 38610000.0,
+                "low_price": 38560000.0,
+                "trade_price": 38600000.0,
+                "timestamp": 1689811199803,
+                "candle_acc_trade_price": 612345678.9,
+                "candle_acc_trade_volume": 15.8713,
+                "unit": 15
+            }
+        ]"#
+        .to_string();
+        let result = upbit.parse_as_kline(input);
+        let expected = vec![
+            Kline {
+                unixtime_msec: 1689812100000,
+                o: 38600000.0,
+                h: 38622000.0,
+                l: 38590000.0,
+                c: 38611000.0,
+                v: 10.8799,
+                extra: KlineExtra::default(),
+            },
+            Kline {
+                unixtime_msec: 1689811200000,
+                o: 38570000.0,
+                h: 38610000.0,
+                l: 38560000.0,
+                c: 38600000.0,
+                v: 15.8713,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_parse_as_listings() {
+        let input = r#"
+        [
+            { "market": "KRW-BTC", "korean_name": "비트코인", "english_name": "Bitcoin" },
+            { "market": "BTC-ETH", "korean_name": "이더리움", "english_name": "Ethereum" }
+        ]"#;
+
+        assert_eq!(
+            Upbit::parse_as_listings(input).unwrap(),
+            vec![
+                Listing::new("BTC", "KRW", "KRW-BTC", &[]),
+                Listing::new("ETH", "BTC", "BTC-ETH", &[]),
+            ]
+        );
+    }
+}
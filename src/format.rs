@@ -1,7 +1,7 @@
 use clap::ValueEnum;
 use console::style;
 
-use crate::{pick::*, types::*};
+use crate::{exchange::*, pick::*, types::*};
 
 #[derive(
     Debug, Clone, ValueEnum, strum::Display, strum::IntoStaticStr, strum::EnumIter, strum::AsRefStr,
@@ -59,7 +59,7 @@ impl FormatType {
 
         for raw in data.iter() {
             for r in raw.iter() {
-                result.push_str(&r.iter().next().unwrap().1.as_string());
+                result.push_str(&r.iter().next().unwrap().1.as_cell());
                 result.push_str(delimiter);
             }
             result.truncate(result.len() - delimiter.len());
@@ -76,7 +76,7 @@ impl FormatType {
 
         for raw in data.iter() {
             for r in raw.iter() {
-                result.push_str(&r.iter().next().unwrap().1.as_string());
+                result.push_str(&r.iter().next().unwrap().1.as_cell());
                 result.push_str(delimiter);
             }
             result.truncate(result.len() - delimiter.len());
@@ -108,15 +108,16 @@ impl FormatType {
                 match r.iter().next().unwrap().0 {
                     Pick::T => {
                         if !keys.unixtime {
-                            result.push_str(&format!(
-                                r"{space_8}{}",
-                                style(r#""unixtime": "#).blue()
-                            ));
+                            // Formatted timestamps are no longer unixtime, so the key and quoting change
+                            let (key, value) = match r.iter().next().unwrap().1 {
+                                KlineNumber::Datetime(datetime) => {
+                                    (r#""timestamp": "#, format!(r#""{datetime}""#))
+                                }
+                                number => (r#""unixtime": "#, number.as_string()),
+                            };
+                            result.push_str(&format!(r"{space_8}{}", style(key).blue()));
                             keys.used(Pick::T);
-                            result.push_str(&format!(
-                                "{}",
-                                style(&r.iter().next().unwrap().1.as_string()).magenta()
-                            ));
+                            result.push_str(&format!("{}", style(&value).magenta()));
                             result.push_str(",\n");
                         }
                     }
@@ -176,6 +177,141 @@ impl FormatType {
                             result.push_str(",\n");
                         }
                     }
+                    Pick::IsClosed => {
+                        if !keys.is_closed {
+                            result.push_str(&format!(
+                                r"{space_8}{}",
+                                style(r#""is_closed": "#).blue()
+                            ));
+                            keys.used(Pick::IsClosed);
+                            result.push_str(&format!(
+                                "{}",
+                                style(&r.iter().next().unwrap().1.as_string()).magenta()
+                            ));
+                            result.push_str(",\n");
+                        }
+                    }
+                    Pick::Rate => {
+                        if !keys.rate {
+                            result.push_str(&format!(
+                                r"{space_8}{}",
+                                style(r#""funding_rate": "#).blue()
+                            ));
+                            keys.used(Pick::Rate);
+                            result.push_str(&format!(
+                                "{}",
+                                style(&r.iter().next().unwrap().1.as_string()).magenta()
+                            ));
+                            result.push_str(",\n");
+                        }
+                    }
+                    Pick::Mark => {
+                        if !keys.mark {
+                            result.push_str(&format!(
+                                r"{space_8}{}",
+                                style(r#""mark_price": "#).blue()
+                            ));
+                            keys.used(Pick::Mark);
+                            result.push_str(&format!(
+                                "{}",
+                                style(&r.iter().next().unwrap().1.as_string()).magenta()
+                            ));
+                            result.push_str(",\n");
+                        }
+                    }
+                    Pick::Oi => {
+                        if !keys.oi {
+                            result.push_str(&format!(
+                                r"{space_8}{}",
+                                style(r#""open_interest": "#).blue()
+                            ));
+                            keys.used(Pick::Oi);
+                            result.push_str(&format!(
+                                "{}",
+                                style(&r.iter().next().unwrap().1.as_string()).magenta()
+                            ));
+                            result.push_str(",\n");
+                        }
+                    }
+                    Pick::OiValue => {
+                        if !keys.oi_value {
+                            result.push_str(&format!(
+                                r"{space_8}{}",
+                                style(r#""open_interest_value": "#).blue()
+                            ));
+                            keys.used(Pick::OiValue);
+                            result.push_str(&format!(
+                                "{}",
+                                style(&r.iter().next().unwrap().1.as_string()).magenta()
+                            ));
+                            result.push_str(",\n");
+                        }
+                    }
+                    Pick::Qv => {
+                        if !keys.qv {
+                            result.push_str(&format!(
+                                r"{space_8}{}",
+                                style(r#""quote_volume": "#).blue()
+                            ));
+                            keys.used(Pick::Qv);
+                            result.push_str(&format!(
+                                "{}",
+                                style(&r.iter().next().unwrap().1.as_string()).magenta()
+                            ));
+                            result.push_str(",\n");
+                        }
+                    }
+                    Pick::Trades => {
+                        if !keys.trades {
+                            result
+                                .push_str(&format!(r"{space_8}{}", style(r#""trades": "#).blue()));
+                            keys.used(Pick::Trades);
+                            result.push_str(&format!(
+                                "{}",
+                                style(&r.iter().next().unwrap().1.as_string()).magenta()
+                            ));
+                            result.push_str(",\n");
+                        }
+                    }
+                    Pick::Tbv => {
+                        if !keys.tbv {
+                            result.push_str(&format!(
+                                r"{space_8}{}",
+                                style(r#""taker_buy_volume": "#).blue()
+                            ));
+                            keys.used(Pick::Tbv);
+                            result.push_str(&format!(
+                                "{}",
+                                style(&r.iter().next().unwrap().1.as_string()).magenta()
+                            ));
+                            result.push_str(",\n");
+                        }
+                    }
+                    Pick::Tbqv => {
+                        if !keys.tbqv {
+                            result.push_str(&format!(
+                                r"{space_8}{}",
+                                style(r#""taker_buy_quote_volume": "#).blue()
+                            ));
+                            keys.used(Pick::Tbqv);
+                            result.push_str(&format!(
+                                "{}",
+                                style(&r.iter().next().unwrap().1.as_string()).magenta()
+                            ));
+                            result.push_str(",\n");
+                        }
+                    }
+                    Pick::Vwap => {
+                        if !keys.vwap {
+                            result.push_str(&format!(r"{space_8}{}", style(r#""vwap": "#).blue()));
+                            keys.used(Pick::Vwap);
+                            result.push_str(&format!(
+                                "{}",
+                                style(&r.iter().next().unwrap().1.as_string()).magenta()
+                            ));
+                            result.push_str(",\n");
+                        }
+                    }
                 };
             }
             result.truncate(result.len() - 2 /* `,\n` */);
@@ -186,6 +322,78 @@ impl FormatType {
 
         result
     }
+
+    /// Output of `ro-soku symbols`, the pair in the notation of `--symbol`, the name on the exchange
+    /// and the expiry (only when there are futures).
+    pub fn format_listings(&self, listings: &[Listing]) -> String {
+        if let FormatType::Json = self {
+            return self.json_listings(listings);
+        }
+
+        let has_expiry = listings.iter().any(|listing| listing.expiry.is_some());
+        let cells = |listing: &Listing| {
+            let mut cells = vec![listing.symbol(), listing.native.clone()];
+            if has_expiry {
+                cells.push(
+                    listing
+                        .expiry
+                        .map_or(String::new(), |expiry| expiry.to_string()),
+                );
+            }
+            cells
+        };
+
+        listings
+            .iter()
+            .map(|listing| match self {
+                FormatType::Raw => format!(
+                    "{}{}{}",
+                    style("[").dim(),
+                    cells(listing).join(&format!("{}", style(", ").dim())),
+                    style("]").dim()
+                ),
+                FormatType::Csv => cells(listing).join(","),
+                FormatType::Tsv => cells(listing).join("\t"),
+                FormatType::Json => unreachable!(/* Returned above */),
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn json_listings(&self, listings: &[Listing]) -> String {
+        let space_4 = " ".repeat(4);
+        let space_8 = " ".repeat(8);
+
+        let objects: Vec<String> = listings
+            .iter()
+            .map(|listing| {
+                let mut pairs = vec![
+                    ("symbol", listing.symbol()),
+                    ("base", listing.base.clone()),
+                    ("quote", listing.quote.clone()),
+                    ("native", listing.native.clone()),
+                ];
+                if let Some(expiry) = listing.expiry {
+                    pairs.push(("expiry", expiry.to_string()));
+                }
+
+                let lines: Vec<String> = pairs
+                    .iter()
+                    .map(|(key, value)| {
+                        format!(
+                            "{space_8}{}{}",
+                            style(format!(r#""{key}": "#)).blue(),
+                            style(format!(r#""{value}""#)).magenta()
+                        )
+                    })
+                    .collect();
+
+                format!("{space_4}{{\n{}\n{space_4}}}", lines.join(",\n"))
+            })
+            .collect();
+
+        format!("[\n{}\n]", objects.join(",\n"))
+    }
 }
 
 struct JsonKeyManager {
@@ -195,6 +403,16 @@ struct JsonKeyManager {
     l: bool,
     c: bool,
     v: bool,
+    is_closed: bool,
+    rate: bool,
+    mark: bool,
+    oi: bool,
+    oi_value: bool,
+    qv: bool,
+    trades: bool,
+    tbv: bool,
+    tbqv: bool,
+    vwap: bool,
 }
 
 impl JsonKeyManager {
@@ -206,6 +424,16 @@ impl JsonKeyManager {
             l: false,
             c: false,
             v: false,
+            is_closed: false,
+            rate: false,
+            mark: false,
+            oi: false,
+            oi_value: false,
+            qv: false,
+            trades: false,
+            tbv: false,
+            tbqv: false,
+            vwap: false,
         }
     }
 
@@ -217,6 +445,16 @@ impl JsonKeyManager {
             Pick::L => self.l = true,
             Pick::C => self.c = true,
             Pick::V => self.v = true,
+            Pick::IsClosed => self.is_closed = true,
+            Pick::Rate => self.rate = true,
+            Pick::Mark => self.mark = true,
+            Pick::Oi => self.oi = true,
+            Pick::OiValue => self.oi_value = true,
+            Pick::Qv => self.qv = true,
+            Pick::Trades => self.trades = true,
+            Pick::Tbv => self.tbv = true,
+            Pick::Tbqv => self.tbqv = true,
+            Pick::Vwap => self.vwap = true,
         }
     }
 }
@@ -1,13 +1,14 @@
 use std::collections::HashMap;
 
-use anyhow::{ensure, Error};
-use chrono::{DateTime, Utc};
+use anyhow::{anyhow, ensure, Error};
+use chrono::{DateTime, NaiveDate};
+use clap::ValueEnum;
 use console::{style, Style, Term};
-use dialoguer::{theme::ColorfulTheme, Input, MultiSelect, Select};
+use dialoguer::{theme::ColorfulTheme, Completion, Input, MultiSelect, Select};
 use regex::Regex;
 use strum::IntoEnumIterator;
 
-use crate::{args::*, exchange::*, format::*, order::*, pick::*};
+use crate::{args::*, clock::*, exchange::*, format::*, order::*, pick::*, symbols, timestamp::*};
 
 const SPACE_4: &str = "    ";
 
@@ -16,6 +17,9 @@ pub struct Guide {
     command_set: CommandSet,
     exchange: Option<ExchangeChoices>,
     market_type: Option<MarketType>,
+    margin: Option<Margin>,
+    /// Pinned by `--now` or `--local-clock`, otherwise of the exchange once it is chosen
+    clock: Option<Clock>,
     date_format: String,
     regexp: String,
     theme: MyTheme,
@@ -28,7 +32,7 @@ pub struct CommandSet {
 }
 
 impl Guide {
-    pub fn new() -> Self {
+    pub fn new(clock: Option<Clock>) -> Self {
         Guide {
             command_set: CommandSet {
                 command: r"ro-soku \".to_string() + "\n",
@@ -36,6 +40,8 @@ impl Guide {
             },
             exchange: None,
             market_type: None,
+            margin: None,
+            clock,
             date_format: "%Y-%m-%d %H:%M:%S %:z".to_string(),
             regexp: r"^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2} (\+|-)\d{2}:\d{2}$".to_string(),
             theme: MyTheme(ColorfulTheme {
@@ -56,11 +62,17 @@ impl Guide {
 
         self.exchange()?;
         self.market_type()?;
+        self.clock();
+        // Before the symbol, as linear and inverse contracts list different ones
+        self.margin()?;
         self.symbol()?;
+        self.contract()?;
+        self.price_type()?;
         self.interval()?;
         self.term_start()?;
         self.term_end()?;
         self.pick()?;
+        self.timestamp()?;
         self.order()?;
         self.format()?;
 
@@ -106,9 +118,20 @@ impl Guide {
         match exchanges[index].to_lowercase().as_str() {
             "binance" => self.exchange = Some(ExchangeChoices::Binance),
             "bitbank" => self.exchange = Some(ExchangeChoices::Bitbank),
+            "bitflyer" => self.exchange = Some(ExchangeChoices::Bitflyer),
+            "bitget" => self.exchange = Some(ExchangeChoices::Bitget),
+            "bithumb" => self.exchange = Some(ExchangeChoices::Bithumb),
             "bitmex" => self.exchange = Some(ExchangeChoices::Bitmex),
             "bybit" => self.exchange = Some(ExchangeChoices::Bybit),
+            "coinbase" => self.exchange = Some(ExchangeChoices::Coinbase),
+            "deribit" => self.exchange = Some(ExchangeChoices::Deribit),
+            "gate.io" => self.exchange = Some(ExchangeChoices::Gate),
+            "gmo coin" => self.exchange = Some(ExchangeChoices::Gmo),
+            "hyperliquid" => self.exchange = Some(ExchangeChoices::Hyperliquid),
+            "kraken" => self.exchange = Some(ExchangeChoices::Kraken),
+            "kucoin" => self.exchange = Some(ExchangeChoices::Kucoin),
             "okx" => self.exchange = Some(ExchangeChoices::Okx),
+            "upbit" => self.exchange = Some(ExchangeChoices::Upbit),
             _ => unreachable!(),
         };
 
@@ -143,25 +166,50 @@ impl Guide {
         match market_types[index].to_lowercase().as_str() {
             "spot" => self.market_type = Some(MarketType::Spot),
             "perpetual" => self.market_type = Some(MarketType::Perpetual),
+            "futures" => self.market_type = Some(MarketType::Futures),
+            "option" => self.market_type = Some(MarketType::Option),
             _ => unreachable!(),
         };
 
         Ok(())
     }
 
+    fn clock(&mut self) {
+        // The initial text of the periods is "now" of the exchange, same as retrieving
+        if self.clock.is_none() {
+            let exchange = Exchange::from(&self.exchange.clone().unwrap());
+            self.clock = Some(Clock::System {
+                offset: exchange.clock_offset(&self.market_type.clone().unwrap()),
+            });
+        }
+    }
+
     fn symbol(&mut self) -> Result<(), Error> {
-        // Considering that the number of symbols will increase countless times in the future,
-        // it is not a good idea to let the user choose from a fixed list.
-        // On the other hand, it would be redundant to use an API to retrieve all the tickers
-        // each time (if it were made into a regularly executed batch, how often would it be done this time?
-        // there might be requests for additional functions between batches, etc., which would increase the trouble),
-        // so we decided that manual input would be the best solution.
-        // We also wrote an error handling system to check if a symbol exists for each exchange,
-        // so we can make use of that as well (except for BitMEX).
+        // The symbols listed by the exchange (cached for a day) are completed with Tab,
+        // and the input is checked against them with the same suggestions as retrieving.
+        // If they cannot be listed, any symbol is accepted and retrieving will tell whether it exists.
+        // Users who do not know the symbols can also look them up with `ro-soku symbols`.
+
+        let choice = self.exchange.clone().unwrap();
+        let exchange = Exchange::from(&choice);
+        let market_type = self.market_type.clone().unwrap();
+        let contract = Contract {
+            margin: self.margin.clone(),
+            ..Default::default()
+        };
+
+        let completion = SymbolCompletion(
+            symbols::load(&exchange, &choice, &market_type, &contract)
+                .map(|(listings, _)| listings.iter().map(Listing::symbol).collect())
+                .unwrap_or_default(),
+        );
 
         let symbol = Input::with_theme(&self.theme.0)
-            .with_prompt("Which symbol pair data do you want? (use `/` between currencies)")
+            .with_prompt(
+                "Which symbol pair data do you want? (use `/` between currencies, Tab to complete)",
+            )
             .with_initial_text("BTC/USDT")
+            .completion_with(&completion)
             .validate_with(|input: &String| {
                 ensure!(
                     input
@@ -170,7 +218,7 @@ impl Guide {
                         && input.contains('/'),
                     "Symbol pair must be in uppercase or number and contain `/`."
                 );
-                Ok(())
+                exchange.check_symbol(input, &market_type, &contract)
             })
             .interact_text()?;
 
@@ -182,6 +230,145 @@ impl Guide {
         Ok(())
     }
 
+    fn contract(&mut self) -> Result<(), Error> {
+        // Only dated market types need to specify which contract
+        let is_option = match self.market_type.clone().unwrap() {
+            MarketType::Futures => false,
+            MarketType::Option => true,
+            _ => return Ok(()),
+        };
+
+        let expiry = Input::with_theme(&self.theme.0)
+            .with_prompt("When does the contract expire? (YYYY-MM-DD)")
+            .validate_with(|input: &String| {
+                NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                    .map(|_| ())
+                    .map_err(|e| anyhow!("Invalid expiry date format: {}", e))
+            })
+            .interact_text()?;
+
+        self.command_set
+            .command
+            .push_str(&(format!(r"{}--expiry {} \", SPACE_4, expiry) + "\n"));
+        self.command_set.args.push(("--expiry".to_string(), expiry));
+
+        if !is_option {
+            return Ok(());
+        }
+
+        let strike = Input::with_theme(&self.theme.0)
+            .with_prompt("Which strike price do you want?")
+            .validate_with(|input: &String| {
+                ensure!(
+                    Regex::new(r"^\d+(\.\d+)?$").unwrap().is_match(input),
+                    "The strike price must be a positive number."
+                );
+                Ok(())
+            })
+            .interact_text()?;
+
+        self.command_set
+            .command
+            .push_str(&(format!(r"{}--strike {} \", SPACE_4, strike) + "\n"));
+        self.command_set.args.push(("--strike".to_string(), strike));
+
+        let rights: Vec<String> = OptionRight::iter()
+            .map(|right| right.as_ref().to_lowercase())
+            .collect();
+
+        let index = Select::with_theme(&self.theme.0)
+            .with_prompt("Call or put?")
+            .items(&rights)
+            .default(0)
+            .interact()?;
+
+        self.command_set
+            .command
+            .push_str(&(format!(r"{}--option-right {} \", SPACE_4, rights[index]) + "\n"));
+        self.command_set
+            .args
+            .push(("--option-right".to_string(), rights[index].clone()));
+
+        Ok(())
+    }
+
+    fn margin(&mut self) -> Result<(), Error> {
+        // Only Binance and Bybit have both linear and inverse contracts of the same pair
+        if !matches!(
+            self.exchange,
+            Some(ExchangeChoices::Binance | ExchangeChoices::Bybit)
+        ) || !matches!(
+            self.market_type,
+            Some(MarketType::Perpetual | MarketType::Futures)
+        ) {
+            return Ok(());
+        }
+
+        // Bybit futures are inverse only
+        let margins: Vec<String> = match (&self.exchange, &self.market_type) {
+            (Some(ExchangeChoices::Bybit), Some(MarketType::Futures)) => {
+                vec![Margin::Inverse.as_ref().to_lowercase()]
+            }
+            _ => Margin::iter()
+                .map(|margin| margin.as_ref().to_lowercase())
+                .collect(),
+        };
+
+        let index = Select::with_theme(&self.theme.0)
+            .with_prompt("Linear (margined in the quote currency) or inverse (margined in the base currency)?")
+            .items(&margins)
+            .default(0)
+            .interact()?;
+
+        self.command_set
+            .command
+            .push_str(&(format!(r"{}--margin {} \", SPACE_4, margins[index]) + "\n"));
+        self.command_set
+            .args
+            .push(("--margin".to_string(), margins[index].clone()));
+        self.margin = Margin::from_str(&margins[index], true).ok();
+
+        Ok(())
+    }
+
+    fn price_type(&mut self) -> Result<(), Error> {
+        // Only the derivatives of some exchanges have other than the last price
+        let price_types: Vec<String> = match (&self.exchange, &self.market_type) {
+            (
+                Some(ExchangeChoices::Binance | ExchangeChoices::Bybit),
+                Some(MarketType::Perpetual),
+            )
+            | (Some(ExchangeChoices::Bybit), Some(MarketType::Futures)) => PriceType::iter()
+                .map(|price_type| price_type.as_ref().to_lowercase())
+                .collect(),
+            (Some(ExchangeChoices::Okx), Some(MarketType::Perpetual)) => PriceType::iter()
+                .filter(|price_type| *price_type != PriceType::Premium)
+                .map(|price_type| price_type.as_ref().to_lowercase())
+                .collect(),
+            _ => return Ok(()),
+        };
+
+        let index = Select::with_theme(&self.theme.0)
+            .with_prompt("Which price do you want the candles of?")
+            .items(&price_types)
+            .default(0)
+            .interact()?;
+
+        // `last` is the default
+        if index == 0 {
+            return Ok(());
+        }
+
+        self.command_set
+            .command
+            .push_str(&(format!(r"{}--price-type {} \", SPACE_4, price_types[index]) + "\n"));
+        self.command_set
+            .args
+            .push(("--price-type".to_string(), price_types[index].clone()));
+
+        Ok(())
+    }
+
     fn interval(&mut self) -> Result<(), Error> {
         let data = include_str!("data/intervals.json");
 
@@ -211,7 +398,13 @@ impl Guide {
     }
 
     fn term_start(&mut self) -> Result<(), Error> {
-        let now_formatted = Utc::now().format(&self.date_format).to_string();
+        let now_formatted = self
+            .clock
+            .clone()
+            .unwrap()
+            .now()
+            .format(&self.date_format)
+            .to_string();
         let re = Regex::new(&self.regexp).unwrap();
 
         let term_start = Input::with_theme(&self.theme.0)
@@ -241,7 +434,13 @@ impl Guide {
     }
 
     fn term_end(&mut self) -> Result<(), Error> {
-        let now_formatted = Utc::now().format(&self.date_format).to_string();
+        let now_formatted = self
+            .clock
+            .clone()
+            .unwrap()
+            .now()
+            .format(&self.date_format)
+            .to_string();
         let re = Regex::new(&self.regexp).unwrap();
 
         let term_end = Input::with_theme(&self.theme.0)
@@ -274,8 +473,12 @@ impl Guide {
     fn pick(&mut self) -> Result<(), Error> {
         // No support to select duplicates and any order
 
-        let picks: Vec<String> = Pick::iter().map(|pick| pick.as_ref().to_string()).collect();
-        const COUNT: usize = 6;
+        let picks: Vec<Pick> = DataKind::Kline.picks().to_vec();
+        // OHLCV are selected by default, extra columns are not
+        let defaults: Vec<bool> = picks
+            .iter()
+            .map(|pick| DataKind::Kline.default_picks().contains(pick))
+            .collect();
         let mut indexes: Vec<usize>;
 
         loop {
@@ -283,8 +486,13 @@ impl Guide {
                 .with_prompt(
                     "Select the data you want. Use [Space] to select and [Enter] to confirm.",
                 )
-                .items(&picks)
-                .defaults(&[true; COUNT])
+                .items(
+                    &picks
+                        .iter()
+                        .map(|pick| pick.as_ref().to_string())
+                        .collect::<Vec<_>>(),
+                )
+                .defaults(&defaults)
                 .interact()?;
 
             if !indexes.is_empty() {
@@ -298,10 +506,9 @@ impl Guide {
             .iter()
             .map(|&index| {
                 picks[index]
-                    .chars()
-                    .next()
+                    .to_possible_value()
                     .unwrap()
-                    .to_lowercase()
+                    .get_name()
                     .to_string()
             })
             .collect::<Vec<_>>()
@@ -315,6 +522,51 @@ impl Guide {
         Ok(())
     }
 
+    fn timestamp(&mut self) -> Result<(), Error> {
+        // Custom strftime patterns are too free to select, so they are left to the command line
+
+        let formats = [
+            ("ms", "Unixtime (milliseconds)"),
+            ("sec", "Unixtime (seconds)"),
+            ("rfc3339", "RFC3339"),
+        ];
+
+        let index = Select::with_theme(&self.theme.0)
+            .with_prompt("How do you want the timestamps to be printed?")
+            .items(&formats.iter().map(|(_, item)| *item).collect::<Vec<_>>())
+            .default(0)
+            .interact()?;
+
+        self.command_set
+            .command
+            .push_str(&(format!(r"{}--timestamp-format {} \", SPACE_4, formats[index].0) + "\n"));
+        self.command_set.args.push((
+            "--timestamp-format".to_string(),
+            formats[index].0.to_string(),
+        ));
+
+        if formats[index].0 != "rfc3339" {
+            return Ok(());
+        }
+
+        let timezone = Input::with_theme(&self.theme.0)
+            .with_prompt(
+                "Which timezone do you want to use? (`utc`, `local` or a name like `Asia/Tokyo`)",
+            )
+            .with_initial_text("utc")
+            .validate_with(|input: &String| input.parse::<Timezone>().map(|_| ()))
+            .interact_text()?;
+
+        self.command_set
+            .command
+            .push_str(&(format!(r"{}--timezone {} \", SPACE_4, timezone) + "\n"));
+        self.command_set
+            .args
+            .push(("--timezone".to_string(), timezone));
+
+        Ok(())
+    }
+
     fn order(&mut self) -> Result<(), Error> {
         let orders: Vec<String> = Order::iter()
             .map(|order| order.as_ref().to_string())
@@ -358,6 +610,19 @@ impl Guide {
     }
 }
 
+/// Completes the input with the first listed symbol starting with it.
+struct SymbolCompletion(Vec<String>);
+
+impl Completion for SymbolCompletion {
+    fn get(&self, input: &str) -> Option<String> {
+        let input = input.to_uppercase();
+        self.0
+            .iter()
+            .find(|symbol| symbol.starts_with(&input))
+            .cloned()
+    }
+}
+
 struct MyTheme(ColorfulTheme);
 
 impl Clone for MyTheme {
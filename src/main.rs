@@ -5,16 +5,19 @@ use console::style;
 use dialoguer::Confirm;
 
 mod args;
+mod clock;
 mod error;
 mod exchange;
 mod format;
 mod guide;
 mod order;
 mod pick;
+mod symbols;
+mod timestamp;
 mod types;
 mod unit;
 
-use crate::{args::*, guide::*};
+use crate::{args::*, exchange::*, guide::*};
 
 fn main() -> Result<(), anyhow::Error> {
     let _timer = time::Instant::now();
@@ -22,7 +25,7 @@ fn main() -> Result<(), anyhow::Error> {
 
     match &args.command {
         Some(Commands::Guide {}) => {
-            let mut guide = Guide::new();
+            let mut guide = Guide::new(args.pinned_clock()?);
             let command = guide.generate()?;
 
             if Confirm::new()
@@ -43,6 +46,31 @@ fn main() -> Result<(), anyhow::Error> {
                 child.wait().unwrap();
             }
         }
+        Some(Commands::Symbols {
+            exchange,
+            type_,
+            margin,
+            base,
+            quote,
+            format,
+        }) => {
+            let contract = Contract {
+                margin: margin.clone(),
+                ..Default::default()
+            };
+            // Always fetched to refresh the cache used for checking `--symbol`
+            let listings: Vec<Listing> =
+                symbols::fetch(&Exchange::from(exchange), exchange, type_, &contract)?
+                    .into_iter()
+                    .filter(|listing| listing.is_of(base, quote))
+                    .collect();
+
+            if listings.is_empty() {
+                println!("No symbols");
+            } else {
+                println!("{}", format.format_listings(&listings));
+            }
+        }
         _ => {
             if env::args().collect::<Vec<String>>().len() == 1 {
                 // In case of executed with no options, it will show how to use `ro-soku guide`
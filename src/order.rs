@@ -14,14 +14,14 @@ pub enum Order {
 
 impl Order {
     /// Use to print finally result
-    pub fn sort(mut data: Vec<Kline>, order: &Self) -> Vec<Kline> {
+    pub fn sort<T: Timestamped>(mut data: Vec<T>, order: &Self) -> Vec<T> {
         if data.len() < 2 {
             return data;
         }
 
-        let compare = |a: &Kline, b: &Kline| {
-            let unixtime_a = a.unixtime_msec;
-            let unixtime_b = b.unixtime_msec;
+        let compare = |a: &T, b: &T| {
+            let unixtime_a = a.unixtime_msec();
+            let unixtime_b = b.unixtime_msec();
             unixtime_a
                 .partial_cmp(&unixtime_b)
                 .unwrap_or(Ordering::Equal)
@@ -1,6 +1,6 @@
 use clap::ValueEnum;
 
-use crate::{exchange::*, types::*};
+use crate::{exchange::*, timestamp::*, types::*};
 
 #[derive(
     Debug,
@@ -28,10 +28,49 @@ pub enum Pick {
     C,
     #[strum(serialize = "Volume")]
     V,
+    /// Whether the candle was already closed at the time of retrieval
+    #[strum(serialize = "Closed or not")]
+    #[value(name = "is_closed")]
+    IsClosed,
+    /// Funding rate (`--kind funding` only)
+    #[strum(serialize = "Funding rate")]
+    Rate,
+    /// Mark price at the funding (`--kind funding` only)
+    #[strum(serialize = "Mark price")]
+    #[value(name = "mark")]
+    Mark,
+    /// Open interest (`--kind open-interest` only)
+    #[strum(serialize = "Open interest")]
+    #[value(name = "oi")]
+    Oi,
+    /// Notional value of the open interest (`--kind open-interest` only)
+    #[strum(serialize = "Open interest value")]
+    #[value(name = "oi_value")]
+    OiValue,
+    /// Volume in the quote currency, i.e. turnover (available on some exchanges only)
+    #[strum(serialize = "Quote volume")]
+    Qv,
+    /// Number of trades (available on some exchanges only)
+    #[strum(serialize = "Number of trades")]
+    Trades,
+    /// Volume of trades whose taker was the buyer (available on some exchanges only)
+    #[strum(serialize = "Taker buy volume")]
+    Tbv,
+    /// Same as `tbv`, in the quote currency (available on some exchanges only)
+    #[strum(serialize = "Taker buy quote volume")]
+    Tbqv,
+    /// Volume weighted average price (available on some exchanges only)
+    #[strum(serialize = "VWAP")]
+    Vwap,
 }
 
 impl Pick {
-    pub fn up(data: Vec<Kline>, pick: &[Self]) -> Vec<Row> {
+    pub fn up(
+        data: Vec<Kline>,
+        pick: &[Self],
+        timestamp: &TimestampStyle,
+        is_closed: impl Fn(&Kline) -> bool,
+    ) -> Vec<Row> {
         use Pick::*;
 
         let mut result: Vec<Row> = Vec::new();
@@ -42,7 +81,7 @@ impl Pick {
                 match p {
                     T => {
                         result[i].push(
-                            [(T, KlineNumber::Unixtime(d.unixtime_msec))]
+                            [(T, timestamp.render(d.unixtime_msec))]
                                 .iter()
                                 .cloned()
                                 .collect(),
@@ -63,12 +102,90 @@ impl Pick {
                     V => {
                         result[i].push([(V, KlineNumber::Ohlcv(d.v))].iter().cloned().collect());
                     }
+                    IsClosed => {
+                        result[i].push(
+                            [(IsClosed, KlineNumber::Flag(is_closed(d)))]
+                                .iter()
+                                .cloned()
+                                .collect(),
+                        );
+                    }
+                    Qv | Trades | Tbv | Tbqv | Vwap => {
+                        let value = match p {
+                            Qv => d.extra.quote_volume,
+                            Trades => d.extra.trades.map(|trades| trades as f64),
+                            Tbv => d.extra.taker_buy_volume,
+                            Tbqv => d.extra.taker_buy_quote_volume,
+                            _ => d.extra.vwap,
+                        };
+                        result[i].push(
+                            [(
+                                p.clone(),
+                                value.map_or(KlineNumber::Null, KlineNumber::Ohlcv),
+                            )]
+                            .iter()
+                            .cloned()
+                            .collect(),
+                        );
+                    }
+                    // Not of klines, rejected in the validation of arguments
+                    Rate | Mark | Oi | OiValue => {
+                        result[i].push([(p.clone(), KlineNumber::Null)].iter().cloned().collect());
+                    }
                 };
             }
         }
 
         result
     }
+
+    /// Same as `up()`, for funding rates.
+    pub fn up_funding(data: Vec<Funding>, pick: &[Self], timestamp: &TimestampStyle) -> Vec<Row> {
+        use Pick::*;
+
+        data.iter()
+            .map(|d| {
+                pick.iter()
+                    .map(|p| {
+                        let value = match p {
+                            T => timestamp.render(d.unixtime_msec),
+                            Rate => KlineNumber::Ohlcv(d.rate),
+                            Mark => d.mark_price.map_or(KlineNumber::Null, KlineNumber::Ohlcv),
+                            // Not of funding rates, rejected in the validation of arguments
+                            _ => KlineNumber::Null,
+                        };
+                        [(p.clone(), value)].iter().cloned().collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Same as `up()`, for open interest.
+    pub fn up_open_interest(
+        data: Vec<OpenInterest>,
+        pick: &[Self],
+        timestamp: &TimestampStyle,
+    ) -> Vec<Row> {
+        use Pick::*;
+
+        data.iter()
+            .map(|d| {
+                pick.iter()
+                    .map(|p| {
+                        let value = match p {
+                            T => timestamp.render(d.unixtime_msec),
+                            Oi => KlineNumber::Ohlcv(d.amount),
+                            OiValue => d.value.map_or(KlineNumber::Null, KlineNumber::Ohlcv),
+                            // Not of open interest, rejected in the validation of arguments
+                            _ => KlineNumber::Null,
+                        };
+                        [(p.clone(), value)].iter().cloned().collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -246,6 +363,36 @@ mod tests {
                 l: 27339.35000000,
                 c: 27340.27000000,
                 v: 48.78558000,
+                extra: KlineExtra::default(),
+            },
+            Kline {
+                unixtime_msec: 1682325540000,
+                o: 27340.08000000,
+                h: 27366.68000000,
+                l: 27333.62000000,
+                c: 27348.14000000,
+                v: 55.99021000,
+                extra: KlineExtra::default(),
+            },
+        ];
+
+        assert_eq!(
+            Pick::up(data.to_vec(), &input, &TimestampStyle::new(), |_| true),
+            expected,
+        );
+    }
+
+    #[test]
+    fn test_up_is_closed() {
+        let data = vec![
+            Kline {
+                unixtime_msec: 1682325360000,
+                o: 27376.90000000,
+                h: 27387.04000000,
+                l: 27339.35000000,
+                c: 27340.27000000,
+                v: 48.78558000,
+                extra: KlineExtra::default(),
             },
             Kline {
                 unixtime_msec: 1682325540000,
@@ -254,9 +401,103 @@ mod tests {
                 l: 27333.62000000,
                 c: 27348.14000000,
                 v: 55.99021000,
+                extra: KlineExtra::default(),
             },
         ];
+        let expected: Vec<Row> = vec![
+            vec![[(IsClosed, KlineNumber::Flag(true))]
+                .iter()
+                .cloned()
+                .collect::<HashMap<_, _>>()],
+            vec![[(IsClosed, KlineNumber::Flag(false))]
+                .iter()
+                .cloned()
+                .collect::<HashMap<_, _>>()],
+        ];
+
+        let result = Pick::up(data, &[IsClosed], &TimestampStyle::new(), |kline| {
+            kline.unixtime_msec < 1682325540000
+        });
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_up_funding() {
+        let data = vec![
+            Funding {
+                unixtime_msec: 1698768000000,
+                rate: 0.0001,
+                mark_price: Some(34639.12),
+            },
+            Funding {
+                unixtime_msec: 1698796800000,
+                rate: -0.00002477,
+                mark_price: None,
+            },
+        ];
+        let expected: Vec<Row> = vec![
+            vec![
+                [(Rate, KlineNumber::Ohlcv(0.0001))]
+                    .iter()
+                    .cloned()
+                    .collect::<HashMap<_, _>>(),
+                [(Mark, KlineNumber::Ohlcv(34639.12))]
+                    .iter()
+                    .cloned()
+                    .collect::<HashMap<_, _>>(),
+            ],
+            vec![
+                [(Rate, KlineNumber::Ohlcv(-0.00002477))]
+                    .iter()
+                    .cloned()
+                    .collect::<HashMap<_, _>>(),
+                [(Mark, KlineNumber::Null)]
+                    .iter()
+                    .cloned()
+                    .collect::<HashMap<_, _>>(),
+            ],
+        ];
+
+        assert_eq!(
+            Pick::up_funding(data, &[Rate, Mark], &TimestampStyle::new()),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_up_extra() {
+        let data = vec![Kline {
+            unixtime_msec: 1681516800000,
+            o: 30295.09,
+            h: 30311.0,
+            l: 30290.55,
+            c: 30301.44,
+            v: 96.91281,
+            extra: KlineExtra {
+                quote_volume: Some(2936555.9405266),
+                trades: Some(2773),
+                ..Default::default()
+            },
+        }];
+        let expected: Vec<Row> = vec![vec![
+            [(Qv, KlineNumber::Ohlcv(2936555.9405266))]
+                .iter()
+                .cloned()
+                .collect::<HashMap<_, _>>(),
+            [(Trades, KlineNumber::Ohlcv(2773.0))]
+                .iter()
+                .cloned()
+                .collect::<HashMap<_, _>>(),
+            [(Vwap, KlineNumber::Null)]
+                .iter()
+                .cloned()
+                .collect::<HashMap<_, _>>(),
+        ]];
 
-        assert_eq!(Pick::up(data.to_vec(), &input), expected,);
+        assert_eq!(
+            Pick::up(data, &[Qv, Trades, Vwap], &TimestampStyle::new(), |_| true),
+            expected
+        );
     }
 }
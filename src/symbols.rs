@@ -0,0 +1,197 @@
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Error;
+use clap::ValueEnum;
+
+use crate::{args::*, exchange::*};
+
+/// Listings older than this are fetched again (a symbol which is not listed also triggers it)
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Number of suggestions for a symbol which is not listed
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Symbol pairs of the exchange, from the local cache if it is fresh.
+/// The second value is whether they came from the cache (and may miss new listings).
+pub fn load(
+    exchange: &Exchange,
+    choice: &ExchangeChoices,
+    market_type: &MarketType,
+    contract: &Contract,
+) -> Result<(Vec<Listing>, bool), Error> {
+    let path = cache_path(choice, market_type, contract);
+
+    if let Some(listings) = read_cache(&path, CACHE_TTL) {
+        return Ok((listings, true));
+    }
+
+    Ok((fetch(exchange, choice, market_type, contract)?, false))
+}
+
+/// Symbol pairs of the exchange from its API, which are also cached for `load()`.
+pub fn fetch(
+    exchange: &Exchange,
+    choice: &ExchangeChoices,
+    market_type: &MarketType,
+    contract: &Contract,
+) -> Result<Vec<Listing>, Error> {
+    let listings = exchange.list_symbols(market_type, contract)?;
+
+    // The cache is only for speed, so failing to write it is not an error
+    let _ = write_cache(&cache_path(choice, market_type, contract), &listings);
+
+    Ok(listings)
+}
+
+/// Listed symbols closest to `symbol` in the edit distance, also if the currencies are swapped.
+pub fn suggest(symbol: &str, listings: &[Listing]) -> Vec<String> {
+    let symbol = symbol.to_uppercase();
+    let swapped = symbol
+        .split_once('/')
+        .map(|(base, quote)| format!("{}/{}", quote, base));
+    // Farther ones are unlikely to be what was meant
+    let max_distance = (symbol.len() / 3).max(2);
+
+    let mut candidates: Vec<(usize, String)> = listings
+        .iter()
+        .map(|listing| {
+            let listed = listing.symbol();
+            let distance = swapped
+                .as_ref()
+                .map(|swapped| levenshtein(swapped, &listed) + 1)
+                .into_iter()
+                .chain([levenshtein(&symbol, &listed)])
+                .min()
+                .unwrap();
+            (distance, listed)
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    candidates.sort();
+    // Futures of the same pair are listed for each expiry
+    candidates.dedup_by(|a, b| a.1 == b.1);
+
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, listed)| listed)
+        .collect()
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Under the cache directory of the OS, like `~/.cache/ro-soku/symbols/binance-perpetual.json`.
+fn cache_path(choice: &ExchangeChoices, market_type: &MarketType, contract: &Contract) -> PathBuf {
+    let dir = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(env::temp_dir);
+
+    let mut name = format!(
+        "{}-{}",
+        choice.to_possible_value().unwrap().get_name(),
+        market_type.to_possible_value().unwrap().get_name()
+    );
+    if let Some(margin) = &contract.margin {
+        name.push_str(&format!(
+            "-{}",
+            margin.to_possible_value().unwrap().get_name()
+        ));
+    }
+
+    dir.join("ro-soku")
+        .join("symbols")
+        .join(format!("{}.json", name))
+}
+
+fn read_cache(path: &Path, ttl: Duration) -> Option<Vec<Listing>> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    if SystemTime::now().duration_since(modified).ok()? >= ttl {
+        return None;
+    }
+
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn write_cache(path: &Path, listings: &[Listing]) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string(listings)?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    fn listings(symbols: &[&str]) -> Vec<Listing> {
+        symbols
+            .iter()
+            .map(|symbol| {
+                let (base, quote) = symbol.split_once('/').unwrap();
+                Listing::new(base, quote, &format!("{}{}", base, quote), &[])
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case("kitten", "sitting", 3)]
+    #[case("BTC/USD", "BTC/USDT", 1)]
+    #[case("", "ETH", 3)]
+    #[case("ETH", "ETH", 0)]
+    fn test_levenshtein(#[case] a: &str, #[case] b: &str, #[case] expected: usize) {
+        assert_eq!(levenshtein(a, b), expected);
+    }
+
+    #[rstest]
+    #[case("BTC/USD", vec!["BTC/USDC", "BTC/USDT"])]
+    #[case("USDT/BTC", vec!["BTC/USDT", "BTC/USDC"])]
+    #[case("btc/jpy", vec!["BTC/JPY"])]
+    #[case("DOGE/EUR", vec![])]
+    fn test_suggest(#[case] symbol: &str, #[case] expected: Vec<&str>) {
+        let listings = listings(&["BTC/USDT", "BTC/USDC", "BTC/JPY", "ETH/USDT", "SOL/USDT"]);
+
+        assert_eq!(suggest(symbol, &listings), expected);
+    }
+
+    #[test]
+    fn test_cache() {
+        let path = env::temp_dir()
+            .join(format!("ro-soku-test-{}", std::process::id()))
+            .join("symbols.json");
+        let expected =
+            vec![Listing::new("BTC", "USD", "BTCUSDZ23", &[]).expiring_at(1703836800000)];
+
+        assert_eq!(read_cache(&path, CACHE_TTL), None);
+
+        write_cache(&path, &expected).unwrap();
+        assert_eq!(read_cache(&path, CACHE_TTL), Some(expected));
+        // Expired
+        assert_eq!(read_cache(&path, Duration::ZERO), None);
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
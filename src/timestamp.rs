@@ -0,0 +1,179 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local, SecondsFormat, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use thiserror::Error;
+
+use crate::exchange::KlineNumber;
+
+/// How the candle timestamp is rendered in every output format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimestampFormat {
+    Msec,
+    Sec,
+    Rfc3339,
+    /// Any strftime pattern supported by chrono (e.g. `%Y-%m-%d %H:%M`)
+    Custom(String),
+}
+
+/// Timezone used when the timestamp is rendered as a date string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Timezone {
+    Utc,
+    Local,
+    Named(Tz),
+}
+
+#[derive(Debug, Clone)]
+pub struct TimestampStyle {
+    pub format: TimestampFormat,
+    pub timezone: Timezone,
+}
+
+impl TimestampStyle {
+    pub fn new() -> Self {
+        TimestampStyle {
+            format: TimestampFormat::Msec,
+            timezone: Timezone::Utc,
+        }
+    }
+
+    /// Unixtime formats stay numbers (so that JSON keeps them unquoted),
+    /// the others become strings in the specified timezone.
+    pub fn render(&self, unixtime_msec: i64) -> KlineNumber {
+        match &self.format {
+            TimestampFormat::Msec => KlineNumber::Unixtime(unixtime_msec),
+            TimestampFormat::Sec => KlineNumber::Unixtime(unixtime_msec.div_euclid(1000)),
+            format => {
+                let utc = Utc.timestamp_millis_opt(unixtime_msec).unwrap();
+                KlineNumber::Datetime(match &self.timezone {
+                    Timezone::Utc => Self::format_datetime(utc, format),
+                    Timezone::Local => Self::format_datetime(utc.with_timezone(&Local), format),
+                    Timezone::Named(tz) => Self::format_datetime(utc.with_timezone(tz), format),
+                })
+            }
+        }
+    }
+
+    fn format_datetime<T: TimeZone>(datetime: DateTime<T>, format: &TimestampFormat) -> String
+    where
+        T::Offset: Display,
+    {
+        match format {
+            TimestampFormat::Rfc3339 => datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            TimestampFormat::Custom(pattern) => datetime.format(pattern).to_string(),
+            _ => unreachable!(/* Unixtime formats never reach here */),
+        }
+    }
+}
+
+impl FromStr for TimestampFormat {
+    type Err = ParseTimestampStyleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use ParseTimestampStyleError::*;
+
+        match s {
+            "ms" | "msec" => Ok(TimestampFormat::Msec),
+            "s" | "sec" => Ok(TimestampFormat::Sec),
+            "rfc3339" => Ok(TimestampFormat::Rfc3339),
+            pattern if pattern.contains('%') => {
+                // Check it here, otherwise chrono will panic while printing the result
+                if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+                    return Err(InvalidPattern(pattern.to_string()));
+                }
+                Ok(TimestampFormat::Custom(pattern.to_string()))
+            }
+            _ => Err(UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+impl FromStr for Timezone {
+    type Err = ParseTimestampStyleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "utc" | "z" => Ok(Timezone::Utc),
+            "local" => Ok(Timezone::Local),
+            _ => s
+                .parse::<Tz>()
+                .map(Timezone::Named)
+                .map_err(|_| ParseTimestampStyleError::UnknownTimezone(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ParseTimestampStyleError {
+    #[error("Unknown timestamp format: {0} (use `ms`, `sec`, `rfc3339` or a strftime pattern)")]
+    UnknownFormat(String),
+    #[error("Invalid strftime pattern: {0}")]
+    InvalidPattern(String),
+    #[error("Unknown timezone: {0} (use `utc`, `local` or a name like `Asia/Tokyo`)")]
+    UnknownTimezone(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case("ms", TimestampFormat::Msec)]
+    #[case("sec", TimestampFormat::Sec)]
+    #[case("rfc3339", TimestampFormat::Rfc3339)]
+    #[case("%Y/%m/%d", TimestampFormat::Custom("%Y/%m/%d".to_string()))]
+    #[should_panic]
+    #[case("%Y/%Q", TimestampFormat::Msec)]
+    #[should_panic]
+    #[case("minutes", TimestampFormat::Msec)]
+    fn test_timestamp_format_from_str(#[case] input: &str, #[case] expected: TimestampFormat) {
+        assert_eq!(TimestampFormat::from_str(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("UTC", Timezone::Utc)]
+    #[case("local", Timezone::Local)]
+    #[case("Asia/Tokyo", Timezone::Named(Tz::Asia__Tokyo))]
+    #[should_panic]
+    #[case("Mars/Olympus", Timezone::Utc)]
+    fn test_timezone_from_str(#[case] input: &str, #[case] expected: Timezone) {
+        assert_eq!(Timezone::from_str(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(
+        TimestampFormat::Msec,
+        Timezone::Utc,
+        KlineNumber::Unixtime(1614984720000)
+    )]
+    #[case(TimestampFormat::Sec, Timezone::Utc, KlineNumber::Unixtime(1614984720))]
+    #[case(
+        TimestampFormat::Rfc3339,
+        Timezone::Utc,
+        KlineNumber::Datetime("2021-03-05T22:52:00Z".to_string())
+    )]
+    #[case(
+        TimestampFormat::Rfc3339,
+        Timezone::Named(Tz::Asia__Tokyo),
+        KlineNumber::Datetime("2021-03-06T07:52:00+09:00".to_string())
+    )]
+    #[case(
+        TimestampFormat::Custom("%Y-%m-%d %H:%M".to_string()),
+        Timezone::Named(Tz::America__New_York),
+        KlineNumber::Datetime("2021-03-05 17:52".to_string())
+    )]
+    fn test_render(
+        #[case] format: TimestampFormat,
+        #[case] timezone: Timezone,
+        #[case] expected: KlineNumber,
+    ) {
+        let style = TimestampStyle { format, timezone };
+        assert_eq!(style.render(1614984720000), expected);
+    }
+}
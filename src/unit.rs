@@ -1,6 +1,6 @@
 use std::{num::ParseIntError, str::FromStr};
 
-use chrono::Duration;
+use chrono::{DateTime, Datelike, Duration, Months, TimeZone, Utc};
 use regex::Regex;
 use thiserror::Error;
 
@@ -31,6 +31,68 @@ impl DurationAndUnit {
         }
     }
 
+    /// Opening time of the candle which contains `unixtime_msec`, for building candles by ourselves.
+    /// Weeks start on Monday and months on the 1st (both in UTC), as most exchanges do.
+    pub fn opening_time(&self, unixtime_msec: i64) -> i64 {
+        let number = self.0 as i64;
+
+        match self.1 {
+            TermUnit::Week => {
+                // The unix epoch is Thursday, the first Monday is 4 days later
+                let first_monday = 4 * 24 * 60 * 60 * 1000;
+                unixtime_msec - (unixtime_msec - first_monday).rem_euclid(self.to_msec())
+            }
+            TermUnit::Month => {
+                let datetime = Utc.timestamp_millis_opt(unixtime_msec).unwrap();
+                let months = datetime.year() as i64 * 12 + datetime.month0() as i64;
+                let months = months - months.rem_euclid(number);
+                Utc.with_ymd_and_hms((months / 12) as i32, (months % 12) as u32 + 1, 1, 0, 0, 0)
+                    .unwrap()
+                    .timestamp_millis()
+            }
+            _ => unixtime_msec - unixtime_msec.rem_euclid(self.to_msec()),
+        }
+    }
+
+    /// Closing time of the candle which contains `unixtime_msec` (the opening time of the next one).
+    pub fn closing_time(&self, unixtime_msec: i64) -> i64 {
+        let opening_time = self.opening_time(unixtime_msec);
+
+        match self.1 {
+            TermUnit::Month => {
+                let datetime = Utc.timestamp_millis_opt(opening_time).unwrap();
+                let months = datetime.year() as i64 * 12 + datetime.month0() as i64 + self.0 as i64;
+                Utc.with_ymd_and_hms((months / 12) as i32, (months % 12) as u32 + 1, 1, 0, 0, 0)
+                    .unwrap()
+                    .timestamp_millis()
+            }
+            _ => opening_time + self.to_msec(),
+        }
+    }
+
+    /// `count` intervals after `unixtime_msec` (before it if negative), by the calendar for months.
+    /// Unlike `closing_time()`, the time is not aligned first, as some exchanges open weeks on
+    /// other days than Monday. Too far a shift stops at the limit of the datetime.
+    pub fn shift(&self, unixtime_msec: i64, count: i64) -> i64 {
+        match self.1 {
+            TermUnit::Month => {
+                let datetime = Utc.timestamp_millis_opt(unixtime_msec).unwrap();
+                let months = count.unsigned_abs().saturating_mul(self.0 as u64);
+                let months = Months::new(u32::try_from(months).unwrap_or(u32::MAX));
+                match count < 0 {
+                    true => datetime
+                        .checked_sub_months(months)
+                        .unwrap_or(DateTime::<Utc>::MIN_UTC),
+                    false => datetime
+                        .checked_add_months(months)
+                        .unwrap_or(DateTime::<Utc>::MAX_UTC),
+                }
+                .timestamp_millis()
+            }
+            _ => unixtime_msec.saturating_add(count.saturating_mul(self.to_msec())),
+        }
+    }
+
     pub fn past_duration(&self) -> Duration {
         let (number, unit) = (self.0, self.1.clone());
 
@@ -113,4 +175,45 @@ mod tests {
     fn test_to_msec(#[case] input: DurationAndUnit, #[case] expected: i64) {
         assert_eq!(input.to_msec(), expected);
     }
+
+    #[rstest]
+    // 2023-05-10T13:25:33.123Z
+    #[case(DurationAndUnit::from_str("1sec").unwrap(), 1683725133000)]
+    #[case(DurationAndUnit::from_str("15min").unwrap(), 1683724500000)]
+    #[case(DurationAndUnit::from_str("4hour").unwrap(), 1683720000000)]
+    #[case(DurationAndUnit::from_str("1day").unwrap(), 1683676800000)]
+    // Monday, 2023-05-08
+    #[case(DurationAndUnit::from_str("1week").unwrap(), 1683504000000)]
+    #[case(DurationAndUnit::from_str("1month").unwrap(), 1682899200000)]
+    // 2023-04-01
+    #[case(DurationAndUnit::from_str("3month").unwrap(), 1680307200000)]
+    fn test_opening_time(#[case] input: DurationAndUnit, #[case] expected: i64) {
+        assert_eq!(input.opening_time(1683725133123), expected);
+    }
+
+    #[rstest]
+    // 2023-05-10T13:25:33.123Z
+    #[case(DurationAndUnit::from_str("15min").unwrap(), 1683725400000)]
+    #[case(DurationAndUnit::from_str("1day").unwrap(), 1683763200000)]
+    // Monday, 2023-05-15
+    #[case(DurationAndUnit::from_str("1week").unwrap(), 1684108800000)]
+    // 2023-06-01
+    #[case(DurationAndUnit::from_str("1month").unwrap(), 1685577600000)]
+    // 2023-07-01
+    #[case(DurationAndUnit::from_str("3month").unwrap(), 1688169600000)]
+    fn test_closing_time(#[case] input: DurationAndUnit, #[case] expected: i64) {
+        assert_eq!(input.closing_time(1683725133123), expected);
+    }
+
+    #[rstest]
+    // 2023-06-01 to 2023-07-01 and 2023-03-01
+    #[case(DurationAndUnit::from_str("1month").unwrap(), 1, 1688169600000)]
+    #[case(DurationAndUnit::from_str("1month").unwrap(), -3, 1677628800000)]
+    #[case(DurationAndUnit::from_str("3month").unwrap(), -1, 1677628800000)]
+    // Not aligned to Monday
+    #[case(DurationAndUnit::from_str("1week").unwrap(), -2, 1685577600000 - 14 * 24 * 60 * 60 * 1000)]
+    #[case(DurationAndUnit::from_str("15min").unwrap(), 4, 1685577600000 + 60 * 60 * 1000)]
+    fn test_shift(#[case] input: DurationAndUnit, #[case] count: i64, #[case] expected: i64) {
+        assert_eq!(input.shift(1685577600000, count), expected);
+    }
 }